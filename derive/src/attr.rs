@@ -0,0 +1,202 @@
+use syn::{Attribute, ExprPath, LitStr};
+
+/// Attributes on the struct or enum
+#[derive(Default)]
+pub struct Container {
+    pub rename_all: Option<RenameRule>,
+    pub tag: Option<String>,
+}
+
+impl Container {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut c = Self::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("bencode")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename_all") {
+                    let s: LitStr = meta.value()?.parse()?;
+                    let rule = RenameRule::parse(&s.value()).ok_or_else(|| meta.error("unknown case"))?;
+                    c.rename_all = Some(rule);
+                    Ok(())
+                } else if meta.path.is_ident("tag") {
+                    let s: LitStr = meta.value()?.parse()?;
+                    c.tag = Some(s.value());
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown bencode attribute"))
+                }
+            })?;
+        }
+        Ok(c)
+    }
+}
+
+/// Attributes on an enum variant
+#[derive(Default)]
+pub struct Variant {
+    pub rename: Option<String>,
+    pub rename_all: Option<RenameRule>,
+}
+
+impl Variant {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut v = Self::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("bencode")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    let s: LitStr = meta.value()?.parse()?;
+                    v.rename = Some(s.value());
+                    Ok(())
+                } else if meta.path.is_ident("rename_all") {
+                    let s: LitStr = meta.value()?.parse()?;
+                    let rule = RenameRule::parse(&s.value()).ok_or_else(|| meta.error("unknown case"))?;
+                    v.rename_all = Some(rule);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown bencode attribute"))
+                }
+            })?;
+        }
+        Ok(v)
+    }
+}
+
+/// Attributes on a field
+#[derive(Default)]
+pub struct Field {
+    pub rename: Option<String>,
+    pub default: Option<DefaultValue>,
+    pub flatten: bool,
+}
+
+/// Value of a field whose key is missing
+pub enum DefaultValue {
+    /// `#[bencode(default)]` for `Default::default()`
+    Trait,
+    /// `#[bencode(default = "path")]` for a function
+    Path(ExprPath),
+}
+
+impl Field {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut f = Self::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("bencode")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    let s: LitStr = meta.value()?.parse()?;
+                    f.rename = Some(s.value());
+                    Ok(())
+                } else if meta.path.is_ident("default") {
+                    f.default = Some(if meta.input.peek(syn::Token![=]) {
+                        DefaultValue::Path(meta.value()?.parse::<LitStr>()?.parse()?)
+                    } else {
+                        DefaultValue::Trait
+                    });
+                    Ok(())
+                } else if meta.path.is_ident("flatten") {
+                    f.flatten = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown bencode attribute"))
+                }
+            })?;
+        }
+        Ok(f)
+    }
+}
+
+/// Case convention for `rename_all` (with the same names as in serde)
+#[derive(Clone, Copy)]
+pub enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "lowercase" => Self::Lower,
+            "UPPERCASE" => Self::Upper,
+            "PascalCase" => Self::Pascal,
+            "camelCase" => Self::Camel,
+            "snake_case" => Self::Snake,
+            "SCREAMING_SNAKE_CASE" => Self::ScreamingSnake,
+            "kebab-case" => Self::Kebab,
+            "SCREAMING-KEBAB-CASE" => Self::ScreamingKebab,
+            _ => return None,
+        })
+    }
+
+    /// Apply the rule to a `snake_case` field or `PascalCase` variant name
+    pub fn apply(self, name: &str) -> String {
+        let words = words(name);
+        let capitalized = || words.iter().map(|w| capitalize(w)).collect::<String>();
+        match self {
+            Self::Lower => words.concat(),
+            Self::Upper => words.concat().to_uppercase(),
+            Self::Pascal => capitalized(),
+            Self::Camel => {
+                let s = capitalized();
+                let mut c = s.chars();
+                c.next().map(|f| f.to_lowercase().chain(c).collect()).unwrap_or_default()
+            }
+            Self::Snake => words.join("_"),
+            Self::ScreamingSnake => words.join("_").to_uppercase(),
+            Self::Kebab => words.join("-"),
+            Self::ScreamingKebab => words.join("-").to_uppercase(),
+        }
+    }
+}
+
+/// Lowercase words of a name split at underscores and before uppercase letters
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for c in name.chars() {
+        if (c == '_' || c.is_uppercase()) && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        if c != '_' {
+            word.extend(c.to_lowercase());
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn capitalize(w: &str) -> String {
+    let mut c = w.chars();
+    c.next().map(|f| f.to_uppercase().chain(c).collect()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_rules() {
+        for (rule, expected) in [
+            ("lowercase", "infohash"),
+            ("UPPERCASE", "INFOHASH"),
+            ("PascalCase", "InfoHash"),
+            ("camelCase", "infoHash"),
+            ("snake_case", "info_hash"),
+            ("SCREAMING_SNAKE_CASE", "INFO_HASH"),
+            ("kebab-case", "info-hash"),
+            ("SCREAMING-KEBAB-CASE", "INFO-HASH"),
+        ] {
+            let rule = RenameRule::parse(rule).unwrap();
+            assert_eq!(rule.apply("info_hash"), expected);
+            assert_eq!(rule.apply("InfoHash"), expected);
+        }
+        assert_eq!(RenameRule::Kebab.apply("min_interval_2"), "min-interval-2");
+        assert!(RenameRule::parse("Title Case").is_none());
+    }
+}
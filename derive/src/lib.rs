@@ -0,0 +1,502 @@
+//! Derive macros for [bencode-minimal](https://docs.rs/bencode-minimal)
+//!
+//! Use them through the `derive` feature of `bencode-minimal` which re-exports them.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, Span, TokenStream as TokenStream2};
+use quote::{quote, ToTokens};
+mod attr;
+
+use syn::{
+    parse_macro_input, Data, DeriveInput, Fields, FieldsNamed, GenericArgument, GenericParam, Ident, Lifetime,
+    LifetimeParam, PathArguments, Type,
+};
+
+/// Derive `Encodable` for a struct with named fields or an enum
+///
+/// The struct is encoded as a dictionary with one entry per field, the field name being the key.
+/// Field types must be `Encodable` themselves. Fields of type `Option<T>` are left out if `None`.
+///
+/// Keys can be changed with `#[bencode(rename = "key")]` on a field or for all fields with
+/// `#[bencode(rename_all = "...")]` on the struct. The latter accepts the same case conventions
+/// as serde: `lowercase`, `UPPERCASE`, `PascalCase`, `camelCase`, `snake_case`,
+/// `SCREAMING_SNAKE_CASE`, `kebab-case` and `SCREAMING-KEBAB-CASE`.
+///
+/// The entries of a field with `#[bencode(flatten)]`, which must be a struct deriving `ToBencode`
+/// as well, are merged into the dictionary. Encoding panics if this results in duplicate keys.
+///
+/// Enums require `#[bencode(tag = "key")]` and are encoded as a dictionary with the variant name
+/// under that key. The fields of a struct variant are added like those of a struct, while the
+/// single field of a tuple variant is flattened. Variants are renamed like fields, with
+/// `rename_all` on the enum applying to variant names and on a variant to its fields.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// #[derive(ToBencode)]
+/// struct Ping<'a> {
+///     #[bencode(rename = "t")]
+///     transaction_id: &'a [u8],
+///     y: &'a str,
+///     q: &'a str,
+///     a: Args,
+/// }
+///
+/// #[derive(ToBencode)]
+/// #[bencode(rename_all = "kebab-case")]
+/// struct Args {
+///     node_id: [u8; 4],
+/// }
+///
+/// let ping = Ping { transaction_id: b"aa", y: "q", q: "ping", a: Args { node_id: *b"abcd" } };
+/// let mut buf = Vec::new();
+/// Encoder::new(&mut buf).encode(&ping);
+/// assert_eq!(buf, b"d1:ad7:node-id4:abcde1:q4:ping1:t2:aa1:y1:qe");
+///
+/// #[derive(ToBencode)]
+/// #[bencode(tag = "y", rename_all = "lowercase")]
+/// enum Message<'a> {
+///     Q(Ping<'a>),
+///     E { e: i64 },
+/// }
+///
+/// Encoder::new(&mut buf).encode(&Message::E { e: 201 });
+/// assert_eq!(buf, b"d1:ei201e1:y1:ee");
+/// ```
+#[proc_macro_derive(ToBencode, attributes(bencode))]
+pub fn derive_to_bencode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    to_bencode(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Derive `TryFromValue` and the standard `TryFrom<&Value>` for a struct with named fields or an enum
+///
+/// The value must be a dictionary with an entry for every field, the field name being the key.
+/// Other entries are ignored. Field types must be `TryFromValue` themselves. The type may have
+/// at most one lifetime parameter, which is the lifetime of the borrowed `Value`. Keys are
+/// renamed like for [ToBencode](derive@ToBencode).
+///
+/// Fields of type `Option<T>` are `None` if their key is missing. Fields with
+/// `#[bencode(default)]` are set to `Default::default()` and fields with
+/// `#[bencode(default = "path")]` to the result of calling the function at `path`. A present
+/// value that cannot be converted is an error in any case. `TryFromValue::convert` and `TryFrom`
+/// report the key of the offending field as part of the error path.
+///
+/// A field with `#[bencode(flatten)]` is converted from the whole dictionary, so it sees the
+/// entries of the struct's own fields as well.
+///
+/// An enum is converted to the variant whose name is stored under the tag key. A missing or
+/// unknown tag is an error.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// #[derive(FromBencode, Debug, PartialEq)]
+/// struct Ping<'a> {
+///     t: &'a [u8],
+///     y: &'a str,
+///     v: Option<&'a [u8]>,
+///     a: Args,
+/// }
+///
+/// #[derive(FromBencode, Debug, PartialEq)]
+/// struct Args {
+///     id: [u8; 4],
+///     #[bencode(default = "default_port")]
+///     port: u32,
+///     #[bencode(default)]
+///     implied_port: i64,
+/// }
+///
+/// fn default_port() -> u32 {
+///     6881
+/// }
+///
+/// let v = Value::decode(b"d1:ad2:id4:abcde1:q4:ping1:t2:aa1:y1:qe", 100).unwrap();
+/// let ping = <Ping as TryFromValue>::try_from(&v).unwrap();
+/// let args = Args { id: *b"abcd", port: 6881, implied_port: 0 };
+/// assert_eq!(ping, Ping { t: b"aa", y: "q", v: None, a: args });
+///
+/// let v = Value::decode(b"d1:ad2:id4:abcde1:t2:aa1:vi1e1:y1:qe", 100).unwrap();
+/// let e = <Ping as TryFrom<_>>::try_from(&v).unwrap_err();
+/// assert_eq!(e.to_string(), "cannot convert Int to &[u8] at v");
+/// ```
+#[proc_macro_derive(FromBencode, attributes(bencode))]
+pub fn derive_from_bencode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from_bencode(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+struct Field {
+    ident: Ident,
+    ty: Type,
+    key: String,
+    default: Option<attr::DefaultValue>,
+    /// The inner type if the field is an [Option]
+    option: Option<Type>,
+    flatten: bool,
+}
+
+/// An enum variant whose name is stored under the tag key
+struct Variant {
+    ident: Ident,
+    name: String,
+    kind: VariantKind,
+}
+
+enum VariantKind {
+    Unit,
+    /// Fields encoded next to the tag like those of a struct
+    Named(Vec<Field>),
+    /// A single unnamed field whose entries are merged like a flattened field
+    Newtype(Type),
+}
+
+enum Shape {
+    Struct(Vec<Field>),
+    Enum { tag: String, variants: Vec<Variant> },
+}
+
+fn shape(input: &DeriveInput, derive: &str) -> syn::Result<Shape> {
+    let container = attr::Container::parse(&input.attrs)?;
+    match &input.data {
+        Data::Struct(s) => {
+            let Fields::Named(named) = &s.fields else {
+                return Err(syn::Error::new_spanned(input, format!("{} requires named fields", derive)));
+            };
+            if container.tag.is_some() {
+                return Err(syn::Error::new_spanned(input, "tag is only supported on enums"));
+            }
+            Ok(Shape::Struct(fields(named, container.rename_all, None)?))
+        }
+        Data::Enum(e) => {
+            let Some(tag) = container.tag else {
+                let msg = format!("{} requires #[bencode(tag = \"...\")] on enums", derive);
+                return Err(syn::Error::new_spanned(input, msg));
+            };
+            if e.variants.is_empty() {
+                let msg = format!("{} cannot be derived for enums without variants", derive);
+                return Err(syn::Error::new_spanned(input, msg));
+            }
+            let mut variants: Vec<Variant> = Vec::new();
+            for v in &e.variants {
+                let attrs = attr::Variant::parse(&v.attrs)?;
+                let name = match (attrs.rename, container.rename_all) {
+                    (Some(name), _) => name,
+                    (None, Some(rule)) => rule.apply(&unraw(&v.ident)),
+                    (None, None) => unraw(&v.ident),
+                };
+                if variants.iter().any(|other| other.name == name) {
+                    return Err(syn::Error::new_spanned(v, format!("duplicate variant name \"{}\"", name)));
+                }
+                let kind = match &v.fields {
+                    Fields::Unit => VariantKind::Unit,
+                    Fields::Named(named) => VariantKind::Named(fields(named, attrs.rename_all, Some(&tag))?),
+                    Fields::Unnamed(u) if u.unnamed.len() == 1 => VariantKind::Newtype(u.unnamed[0].ty.clone()),
+                    Fields::Unnamed(u) => {
+                        return Err(syn::Error::new_spanned(u, "tuple variants must have exactly one field"));
+                    }
+                };
+                variants.push(Variant { ident: v.ident.clone(), name, kind });
+            }
+            Ok(Shape::Enum { tag, variants })
+        }
+        Data::Union(_) => {
+            Err(syn::Error::new_spanned(input, format!("{} can only be derived for structs and enums", derive)))
+        }
+    }
+}
+
+/// Named fields in declaration order, `tag` being a key reserved for the enum tag
+fn fields(named: &FieldsNamed, rename_all: Option<attr::RenameRule>, tag: Option<&str>) -> syn::Result<Vec<Field>> {
+    let mut fields: Vec<Field> = Vec::new();
+    for f in &named.named {
+        let ident = f.ident.clone().expect("named field");
+        let attrs = attr::Field::parse(&f.attrs)?;
+        let option = option_inner(&f.ty);
+        if attrs.flatten && (attrs.rename.is_some() || attrs.default.is_some() || option.is_some()) {
+            return Err(syn::Error::new_spanned(f, "flatten cannot be combined with rename, default or Option"));
+        }
+        let key = match (attrs.rename, rename_all) {
+            (Some(key), _) => key,
+            (None, Some(rule)) => rule.apply(&unraw(&ident)),
+            (None, None) => unraw(&ident),
+        };
+        if !attrs.flatten && tag == Some(key.as_str()) {
+            return Err(syn::Error::new_spanned(f, format!("key \"{}\" is used as the tag", key)));
+        }
+        if !attrs.flatten && fields.iter().any(|other| !other.flatten && other.key == key) {
+            return Err(syn::Error::new_spanned(f, format!("duplicate key \"{}\"", key)));
+        }
+        fields.push(Field { ident, ty: f.ty.clone(), key, default: attrs.default, option, flatten: attrs.flatten });
+    }
+    Ok(fields)
+}
+
+/// The name of an identifier without the `r#` prefix
+fn unraw(ident: &Ident) -> String {
+    ident.to_string().trim_start_matches("r#").to_string()
+}
+
+/// `T` if `ty` is written as `Option<T>`
+fn option_inner(ty: &Type) -> Option<Type> {
+    let Type::Path(p) = ty else {
+        return None;
+    };
+    let last = p.path.segments.last()?;
+    let PathArguments::AngleBracketed(args) = &last.arguments else {
+        return None;
+    };
+    match (last.ident == "Option", args.args.first(), args.args.len()) {
+        (true, Some(GenericArgument::Type(t)), 1) => Some(t.clone()),
+        _ => None,
+    }
+}
+
+/// Statements adding fields and the `(tag, name)` of a variant to the dictionary encoder `d`
+///
+/// `access` returns a reference to a field. Entries are in ascending key order, except for those
+/// of flattened fields.
+fn entries(fields: &[Field], tag: Option<(&str, &str)>, access: impl Fn(&Ident) -> TokenStream2) -> TokenStream2 {
+    let mut entries: Vec<(&str, TokenStream2)> = fields
+        .iter()
+        .map(|f| {
+            let (value, key) = (access(&f.ident), Literal::byte_string(f.key.as_bytes()));
+            let entry = match (f.flatten, &f.option) {
+                (true, _) => quote! { ::bencode_minimal::private::EncodeEntries::encode_entries(#value, d); },
+                (false, Some(_)) => quote! {
+                    if let ::core::option::Option::Some(v) = #value {
+                        d.entry(#key, v);
+                    }
+                },
+                (false, None) => quote! { d.entry(#key, #value); },
+            };
+            (f.key.as_str(), entry)
+        })
+        .collect();
+    if let Some((tag, name)) = tag {
+        let (key, name) = (Literal::byte_string(tag.as_bytes()), Literal::byte_string(name.as_bytes()));
+        entries.push((tag, quote! { d.entry(#key, #name); }));
+    }
+    entries.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+    let entries = entries.into_iter().map(|(_, entry)| entry);
+    quote! { #(#entries)* }
+}
+
+/// The variable a field is bound to when matching a variant
+///
+/// The prefix keeps it from shadowing the encoder.
+fn binding(ident: &Ident) -> Ident {
+    Ident::new(&format!("__{}", unraw(ident)), Span::call_site())
+}
+
+fn to_bencode(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let shape = shape(input, "ToBencode")?;
+    // Keys of flattened fields are only known at runtime and need sorting then
+    let sorted = quote! { e.sorted_dict_with(|d| ::bencode_minimal::private::EncodeEntries::encode_entries(self, d)); };
+    let mut flattened = Vec::new();
+    let (body, entries) = match &shape {
+        Shape::Struct(fields) => {
+            flattened.extend(fields.iter().filter(|f| f.flatten).map(|f| &f.ty));
+            let entries = entries(fields, None, |ident| quote! { &self.#ident });
+            let body = match fields.iter().any(|f| f.flatten) {
+                true => sorted,
+                false => quote! { e.dict_with(|d| { #entries }); },
+            };
+            (body, entries)
+        }
+        Shape::Enum { tag, variants } => {
+            let (mut body, mut arms) = (Vec::new(), Vec::new());
+            for v in variants {
+                let ident = &v.ident;
+                let tag = Some((tag.as_str(), v.name.as_str()));
+                match &v.kind {
+                    VariantKind::Unit => {
+                        let entries = entries(&[], tag, |_| unreachable!());
+                        body.push(quote! { Self::#ident => e.dict_with(|d| { #entries }), });
+                        arms.push(quote! { Self::#ident => { #entries } });
+                    }
+                    VariantKind::Named(fields) => {
+                        flattened.extend(fields.iter().filter(|f| f.flatten).map(|f| &f.ty));
+                        let idents = fields.iter().map(|f| &f.ident);
+                        let bindings = fields.iter().map(|f| binding(&f.ident));
+                        let pattern = quote! { Self::#ident { #(#idents: #bindings),* } };
+                        let entries = entries(fields, tag, |ident| binding(ident).into_token_stream());
+                        body.push(match fields.iter().any(|f| f.flatten) {
+                            true => quote! { Self::#ident { .. } => { #sorted } },
+                            false => quote! { #pattern => e.dict_with(|d| { #entries }), },
+                        });
+                        arms.push(quote! { #pattern => { #entries } });
+                    }
+                    VariantKind::Newtype(ty) => {
+                        flattened.push(ty);
+                        let entries = entries(&[], tag, |_| unreachable!());
+                        body.push(quote! { Self::#ident(_) => { #sorted } });
+                        arms.push(quote! {
+                            Self::#ident(v) => {
+                                #entries
+                                ::bencode_minimal::private::EncodeEntries::encode_entries(v, d);
+                            }
+                        });
+                    }
+                }
+            }
+            (quote! { match self { #(#body)* } }, quote! { match self { #(#arms)* } })
+        }
+    };
+
+    let mut generics = input.generics.clone();
+    for p in &mut generics.params {
+        if let GenericParam::Type(t) = p {
+            t.bounds.push(syn::parse_quote!(::bencode_minimal::Encodable));
+        }
+    }
+    for ty in flattened {
+        generics.make_where_clause().predicates.push(syn::parse_quote!(#ty: ::bencode_minimal::private::EncodeEntries));
+    }
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::bencode_minimal::Encodable for #name #ty_generics #where_clause {
+            fn encode(&self, e: &mut ::bencode_minimal::Encoder) {
+                #body
+            }
+        }
+
+        impl #impl_generics ::bencode_minimal::private::EncodeEntries for #name #ty_generics #where_clause {
+            fn encode_entries(&self, d: &mut ::bencode_minimal::SortedDictEncoder<'_, '_>) {
+                #entries
+            }
+        }
+    })
+}
+
+fn from_bencode(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let shape = shape(input, "FromBencode")?;
+    // The lifetime of the type (if any) is the lifetime of the borrowed value
+    let mut generics = input.generics.clone();
+    let mut lifetimes = input.generics.lifetimes();
+    let lifetime = match (lifetimes.next(), lifetimes.next()) {
+        (_, Some(l)) => return Err(syn::Error::new_spanned(l, "FromBencode supports at most one lifetime parameter")),
+        (Some(l), None) => l.lifetime.clone(),
+        (None, None) => {
+            let l = Lifetime::new("'__a", Span::call_site());
+            generics.params.insert(0, GenericParam::Lifetime(LifetimeParam::new(l.clone())));
+            l
+        }
+    };
+    for p in &mut generics.params {
+        if let GenericParam::Type(t) = p {
+            t.bounds.push(syn::parse_quote!(::bencode_minimal::TryFromValue<#lifetime>));
+        }
+    }
+    let values = |fields: &[Field]| {
+        let values = fields.iter().map(|f| {
+            let (ident, key) = (&f.ident, Literal::byte_string(f.key.as_bytes()));
+            let ty = f.option.as_ref().unwrap_or(&f.ty);
+            if f.flatten {
+                return quote! { #ident: <#ty as ::bencode_minimal::TryFromValue<#lifetime>>::convert(value)?, };
+            }
+            let convert = quote! {
+                <#ty as ::bencode_minimal::TryFromValue<#lifetime>>::convert(v).map_err(|e| e.at_key(#key))?
+            };
+            let missing = match (&f.default, &f.option) {
+                (Some(attr::DefaultValue::Trait), _) => quote! { ::core::default::Default::default() },
+                (Some(attr::DefaultValue::Path(path)), _) => quote! { #path() },
+                (None, Some(_)) => quote! { ::core::option::Option::None },
+                (None, None) => quote! {
+                    return ::core::result::Result::Err(::bencode_minimal::ConversionError::missing::<#ty>(#key))
+                },
+            };
+            let present = match f.option {
+                Some(_) => quote! { ::core::option::Option::Some(#convert) },
+                None => convert,
+            };
+            quote! {
+                #ident: match d.get(#key.as_ref()) {
+                    ::core::option::Option::Some(v) => #present,
+                    ::core::option::Option::None => #missing,
+                },
+            }
+        });
+        quote! { #(#values)* }
+    };
+    let body = match &shape {
+        Shape::Struct(fields) => {
+            let values = values(fields);
+            quote! { ::core::result::Result::Ok(Self { #values }) }
+        }
+        Shape::Enum { tag, variants } => {
+            let arms = variants.iter().map(|v| {
+                let (ident, name) = (&v.ident, Literal::byte_string(v.name.as_bytes()));
+                let variant = match &v.kind {
+                    VariantKind::Unit => quote! { Self::#ident },
+                    VariantKind::Newtype(ty) => {
+                        quote! { Self::#ident(<#ty as ::bencode_minimal::TryFromValue<#lifetime>>::convert(value)?) }
+                    }
+                    VariantKind::Named(fields) => {
+                        let values = values(fields);
+                        quote! { Self::#ident { #values } }
+                    }
+                };
+                quote! { #name => #variant, }
+            });
+            let tag = Literal::byte_string(tag.as_bytes());
+            quote! {
+                let ::core::option::Option::Some(tag) = d.get(#tag.as_ref()) else {
+                    return ::core::result::Result::Err(::bencode_minimal::ConversionError::missing::<&str>(#tag));
+                };
+                let ::bencode_minimal::Value::Str(name) = tag else {
+                    let e = ::bencode_minimal::ConversionError::new::<&str>(tag);
+                    return ::core::result::Result::Err(e.at_key(#tag));
+                };
+                ::core::result::Result::Ok(match &name[..] {
+                    #(#arms)*
+                    _ => {
+                        let e = ::bencode_minimal::ConversionError::new::<Self>(tag);
+                        return ::core::result::Result::Err(e.at_key(#tag));
+                    }
+                })
+            }
+        }
+    };
+    let name = &input.ident;
+    let name_str = Literal::string(&name.to_string());
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+    let mut try_from_generics = generics.clone();
+    try_from_generics.params.insert(1, syn::parse_quote!('__b));
+    let (try_from_impl_generics, _, _) = try_from_generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::bencode_minimal::TryFromValue<#lifetime> for #name #ty_generics #where_clause {
+            const NAME: &'static str = #name_str;
+
+            fn try_from(value: &#lifetime ::bencode_minimal::Value) -> ::core::option::Option<Self> {
+                <Self as ::bencode_minimal::TryFromValue>::convert(value).ok()
+            }
+
+            fn convert(
+                value: &#lifetime ::bencode_minimal::Value,
+            ) -> ::core::result::Result<Self, ::bencode_minimal::ConversionError> {
+                let ::bencode_minimal::Value::Dict(d) = value else {
+                    return ::core::result::Result::Err(::bencode_minimal::ConversionError::new::<Self>(value));
+                };
+                #body
+            }
+        }
+
+        impl #try_from_impl_generics ::core::convert::TryFrom<&#lifetime ::bencode_minimal::Value<'__b>>
+            for #name #ty_generics #where_clause
+        {
+            type Error = ::bencode_minimal::ConversionError;
+
+            fn try_from(
+                value: &#lifetime ::bencode_minimal::Value<'__b>,
+            ) -> ::core::result::Result<Self, ::bencode_minimal::ConversionError> {
+                <Self as ::bencode_minimal::TryFromValue>::convert(value)
+            }
+        }
+    })
+}
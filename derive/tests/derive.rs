@@ -0,0 +1,212 @@
+use bencode_minimal::*;
+
+fn encode<T: Encodable>(v: &T) -> Vec<u8> {
+    let mut buf = Vec::new();
+    Encoder::new(&mut buf).encode(v);
+    buf
+}
+
+fn decode<T: for<'a> TryFromValue<'a>>(buf: &[u8]) -> Option<T> {
+    decode_as(buf, &DecodeOptions::default())
+}
+
+#[derive(ToBencode, FromBencode, Debug, PartialEq)]
+struct Peer {
+    ip: String,
+    port: u32,
+}
+
+#[derive(ToBencode, FromBencode, Debug, PartialEq)]
+struct Response {
+    interval: i64,
+    peers: Vec<Peer>,
+    r#type: Vec<u8>,
+}
+
+#[derive(ToBencode, FromBencode, Debug, PartialEq)]
+struct Wrapper<T> {
+    inner: T,
+}
+
+#[test]
+fn test_round_trip() {
+    let r =
+        Response { interval: 1800, peers: vec![Peer { ip: "10.0.0.1".into(), port: 6881 }], r#type: b"\xff".to_vec() };
+    let buf = encode(&r);
+    assert_eq!(buf, b"d8:intervali1800e5:peersld2:ip8:10.0.0.14:porti6881eee4:type1:\xffe");
+    assert_eq!(decode(&buf), Some(r));
+
+    let w = Wrapper { inner: 7i64 };
+    assert_eq!(encode(&w), b"d5:inneri7ee");
+    assert_eq!(decode(b"d5:inneri7ee"), Some(w));
+}
+
+#[test]
+fn test_decode_errors() {
+    // Unknown keys are ignored
+    assert_eq!(decode(b"d2:ip1:x4:porti1e1:zi0ee"), Some(Peer { ip: "x".into(), port: 1 }));
+    assert_eq!(decode::<Peer>(b"d2:ip1:xe"), None);
+    assert_eq!(decode::<Peer>(b"d2:ip1:x4:porti-1ee"), None);
+    assert_eq!(decode::<Peer>(b"l2:ip1:xe"), None);
+
+    let v = Value::decode(b"i1e", 10).unwrap();
+    let e = <Peer as TryFrom<_>>::try_from(&v).unwrap_err();
+    assert_eq!((e.expected, e.actual), ("Peer", Some(Kind::Int)));
+
+    // Errors point at the offending field
+    for (buf, error) in [
+        (b"d8:intervali0e5:peersld2:ip1:x4:porti-1eeee".as_ref(), "cannot convert Int to u32 at peers[0].port"),
+        (b"d8:intervali0e5:peersle4:typei0ee", "cannot convert Int to Vec<u8> at type"),
+        (b"d8:intervali0e5:peersld2:ip1:xeee", "missing u32 at peers[0].port"),
+    ] {
+        let v = Value::decode(buf, 100).unwrap();
+        assert_eq!(<Response as TryFromValue>::convert(&v).unwrap_err().to_string(), error);
+    }
+}
+
+#[derive(ToBencode, FromBencode, Debug, PartialEq)]
+#[bencode(rename_all = "kebab-case")]
+struct Announce {
+    min_interval: i64,
+    #[bencode(rename = "failure reason")]
+    failure_reason: String,
+    #[bencode(rename = "a")]
+    zzz: i64,
+}
+
+#[test]
+fn test_rename() {
+    let a = Announce { min_interval: 60, failure_reason: "x".into(), zzz: 1 };
+    let buf = encode(&a);
+    assert_eq!(buf, b"d1:ai1e14:failure reason1:x12:min-intervali60ee");
+    assert_eq!(decode(&buf), Some(a));
+}
+
+fn default_interval() -> i64 {
+    1800
+}
+
+#[derive(ToBencode, FromBencode, Debug, PartialEq)]
+struct Tracker<'a> {
+    #[bencode(rename = "tracker id")]
+    tracker_id: Option<&'a [u8]>,
+    #[bencode(default = "default_interval")]
+    interval: i64,
+    #[bencode(default)]
+    peers: Vec<Peer>,
+    #[bencode(default)]
+    warning: Option<String>,
+}
+
+#[test]
+fn test_optional_and_default() {
+    let t = Tracker { tracker_id: None, interval: 60, peers: vec![], warning: None };
+    assert_eq!(encode(&t), b"d8:intervali60e5:peerslee");
+    let v = Value::decode(b"de", 10).unwrap();
+    let t = <Tracker as TryFromValue>::try_from(&v).unwrap();
+    assert_eq!(t, Tracker { tracker_id: None, interval: 1800, peers: vec![], warning: None });
+
+    let v = Value::decode(b"d10:tracker id1:x7:warning2:ahe", 10).unwrap();
+    let t = <Tracker as TryFromValue>::try_from(&v).unwrap();
+    assert_eq!((t.tracker_id, t.warning.as_deref()), (Some(b"x".as_ref()), Some("ah")));
+    assert_eq!(encode(&t), b"d8:intervali1800e5:peersle10:tracker id1:x7:warning2:ahe");
+
+    // Present values must be valid
+    for buf in [b"d10:tracker idi1ee".as_ref(), b"d8:interval0:e", b"d7:warningi0ee"] {
+        let v = Value::decode(buf, 10).unwrap();
+        assert_eq!(<Tracker as TryFromValue>::try_from(&v), None);
+    }
+}
+
+#[derive(ToBencode, FromBencode, Debug, PartialEq)]
+struct Query<'a, A> {
+    t: &'a [u8],
+    #[bencode(flatten)]
+    kind: QueryKind<'a>,
+    a: A,
+}
+
+#[derive(ToBencode, FromBencode, Debug, PartialEq)]
+struct QueryKind<'a> {
+    q: &'a str,
+    y: &'a str,
+}
+
+#[derive(ToBencode, FromBencode, Debug, PartialEq)]
+struct Outer<'a> {
+    #[bencode(flatten)]
+    query: Query<'a, Peer>,
+    z: i64,
+}
+
+#[test]
+fn test_flatten() {
+    let q = Query { t: b"aa", kind: QueryKind { q: "ping", y: "q" }, a: Peer { ip: "x".into(), port: 1 } };
+    let buf = encode(&q);
+    assert_eq!(buf, b"d1:ad2:ip1:x4:porti1ee1:q4:ping1:t2:aa1:y1:qe");
+    let v = Value::decode(&buf, 100).unwrap();
+    assert_eq!(<Query<Peer> as TryFromValue>::try_from(&v), Some(q));
+
+    let v = Value::decode(b"d1:ad2:ip1:x4:porti1ee1:q4:ping1:t2:aa1:y1:q1:zi0ee", 100).unwrap();
+    let o = <Outer as TryFromValue>::try_from(&v).unwrap();
+    assert_eq!(o.query.kind.q, "ping");
+    assert_eq!(encode(&o), v.encode());
+}
+
+#[derive(ToBencode)]
+struct Clash {
+    q: i64,
+    #[bencode(flatten)]
+    kind: QueryKind<'static>,
+}
+
+#[test]
+#[should_panic(expected = "dictionary keys must be unique")]
+fn test_flatten_duplicate() {
+    encode(&Clash { q: 1, kind: QueryKind { q: "ping", y: "q" } });
+}
+
+#[derive(ToBencode, FromBencode, Debug, PartialEq)]
+#[bencode(tag = "y", rename_all = "lowercase")]
+enum Message<'a> {
+    Q(Peer),
+    R {
+        t: &'a [u8],
+        #[bencode(rename = "r")]
+        response: Peer,
+    },
+    #[bencode(rename = "e", rename_all = "UPPERCASE")]
+    Error {
+        e: i64,
+        d: Option<i64>,
+    },
+    Ping,
+}
+
+#[test]
+fn test_enum() {
+    for (m, buf) in [
+        (Message::Q(Peer { ip: "x".into(), port: 1 }), b"d2:ip1:x4:porti1e1:y1:qe".as_ref()),
+        (Message::R { t: b"aa", response: Peer { ip: "x".into(), port: 1 } }, b"d1:rd2:ip1:x4:porti1ee1:t2:aa1:y1:re"),
+        (Message::Error { e: 201, d: None }, b"d1:Ei201e1:y1:ee"),
+        (Message::Error { e: 201, d: Some(1) }, b"d1:Di1e1:Ei201e1:y1:ee"),
+        (Message::Ping, b"d1:y4:pinge"),
+    ] {
+        assert_eq!(encode(&m), buf);
+        let v = Value::decode(buf, 10).unwrap();
+        assert_eq!(<Message as TryFromValue>::try_from(&v), Some(m));
+    }
+
+    // Missing, unknown or mistyped tags and invalid variant fields
+    for (buf, error) in [
+        (b"de".as_ref(), "missing &str at y"),
+        (b"d1:y1:xe", "cannot convert Str to Message at y"),
+        (b"d1:yi0ee", "cannot convert Int to &str at y"),
+        (b"d1:y1:re", "missing &[u8] at t"),
+        (b"d1:Ei201ee", "missing &str at y"),
+    ] {
+        let v = Value::decode(buf, 10).unwrap();
+        assert_eq!(<Message as TryFromValue>::try_from(&v), None);
+        assert_eq!(<Message as TryFrom<_>>::try_from(&v).unwrap_err().to_string(), error);
+    }
+}
@@ -0,0 +1,256 @@
+//! Bencode as a format for application settings and session state
+//!
+//! Many torrent clients persist their state as Bencode files. [Schema] stores a version number in
+//! such a file, upgrades older files through registered migrations on load and writes files
+//! atomically so a crash never leaves a truncated file behind.
+use super::{DecodeError, DecodeOptions, Value};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A migration from one schema version to the next (see [Schema::migration])
+type Migration = Box<dyn Fn(&mut Value<'_>) + Send + Sync>;
+
+/// The current version of a settings format and how to upgrade older versions
+///
+/// The version is stored as an integer under [Self::key] in the top-level dictionary. Files without
+/// it are considered version 0.
+///
+/// ```rust
+/// use bencode_minimal::*;
+/// use bencode_minimal::config::Schema;
+///
+/// // Version 1 renamed `port` to `listen-port`, version 2 added `dht`
+/// let schema = Schema::new(2)
+///     .migration(0, |v| {
+///         if let Some(port) = v.remove_path("port") {
+///             v.insert("listen-port", port);
+///         }
+///     })
+///     .migration(1, |v| {
+///         v.insert("dht", int!(1));
+///     });
+///
+/// let old = Value::decode(b"d4:porti6881ee", 10).unwrap();
+/// let v = schema.upgrade(old).unwrap();
+/// assert_eq!(v.encode(), b"d3:dhti1e11:listen-porti6881e7:versioni2ee");
+/// ```
+pub struct Schema {
+    version: i64,
+    key: Cow<'static, [u8]>,
+    migrations: BTreeMap<i64, Migration>,
+}
+
+impl Schema {
+    /// A schema at `version` storing it under the key `version`
+    pub fn new(version: i64) -> Self {
+        Self { version, key: Cow::Borrowed(b"version"), migrations: BTreeMap::new() }
+    }
+
+    /// Store the version under `key` instead
+    pub fn with_key(mut self, key: &'static str) -> Self {
+        self.key = Cow::Borrowed(key.as_bytes());
+        self
+    }
+
+    /// Register the migration from version `from` to `from + 1`
+    pub fn migration(mut self, from: i64, f: impl Fn(&mut Value<'_>) + Send + Sync + 'static) -> Self {
+        self.migrations.insert(from, Box::new(f));
+        self
+    }
+
+    pub fn version(&self) -> i64 {
+        self.version
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Bring `value` from its stored version to the current one
+    ///
+    /// Fails if `value` is not a dictionary, was written by a newer version or a migration on the
+    /// way is missing.
+    pub fn upgrade<'a>(&self, mut value: Value<'a>) -> Result<Value<'a>, ConfigError> {
+        let Value::Dict(d) = &value else {
+            return Err(ConfigError::Invalid);
+        };
+        let mut version = match d.get(self.key()) {
+            Some(Value::Int(v)) => *v,
+            Some(_) => return Err(ConfigError::Invalid),
+            None => 0,
+        };
+        if version > self.version {
+            return Err(ConfigError::UnsupportedVersion(version));
+        }
+        while version < self.version {
+            let f = self.migrations.get(&version).ok_or(ConfigError::MissingMigration(version))?;
+            f(&mut value);
+            version += 1;
+        }
+        self.stamp(&mut value)?;
+        Ok(value)
+    }
+
+    /// Read, decode and upgrade a settings file
+    pub fn load(&self, path: impl AsRef<Path>, opts: &DecodeOptions) -> Result<Value<'static>, ConfigError> {
+        let buf = std::fs::read(path).map_err(ConfigError::Io)?;
+        let value = Value::decode_with(&buf, opts).map_err(ConfigError::Decode)?.into_owned();
+        self.upgrade(value)
+    }
+
+    /// Encode `value` with the current version and replace the file at `path` atomically (see [write_atomic])
+    pub fn save(&self, path: impl AsRef<Path>, value: &Value<'_>) -> Result<(), ConfigError> {
+        let mut value = value.clone();
+        self.stamp(&mut value)?;
+        write_atomic(path, &value.encode()).map_err(ConfigError::Io)
+    }
+
+    fn stamp(&self, value: &mut Value<'_>) -> Result<(), ConfigError> {
+        let Value::Dict(d) = value else {
+            return Err(ConfigError::Invalid);
+        };
+        d.insert(Cow::Owned(self.key.to_vec()), Value::Int(self.version));
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Schema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let migrations: Vec<_> = self.migrations.keys().collect();
+        f.debug_struct("Schema").field("version", &self.version).field("migrations", &migrations).finish()
+    }
+}
+
+/// Replace the file at `path` with `data` without ever leaving a partially written file
+///
+/// The data is written to a temporary file in the same directory, flushed to disk and then renamed
+/// over `path`. The temporary file has a name unique to the call, so concurrent saves of the same
+/// file do not interfere and the last rename wins. On unix, the directory is flushed as well so
+/// the rename survives a crash.
+pub fn write_atomic(path: impl AsRef<Path>, data: &[u8]) -> std::io::Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = path.as_ref();
+    let name = path.file_name().ok_or(std::io::ErrorKind::InvalidInput)?;
+    let (tmp, mut f) = loop {
+        let mut tmp_name = name.to_os_string();
+        tmp_name.push(format!(".{}.{}.tmp", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+        let tmp = path.with_file_name(tmp_name);
+        // A leftover of a crashed process with the same id is skipped rather than overwritten
+        match OpenOptions::new().write(true).create_new(true).open(&tmp) {
+            Ok(f) => break (tmp, f),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    };
+    let r = (|| {
+        f.write_all(data)?;
+        f.sync_all()?;
+        std::fs::rename(&tmp, path)
+    })();
+    if r.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+        return r;
+    }
+    #[cfg(unix)]
+    {
+        let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Reason for a failed [Schema::load], [Schema::save] or [Schema::upgrade]
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Decode(DecodeError),
+    /// The top level is not a dictionary or the version is not an integer
+    Invalid,
+    /// The file was written by a newer version
+    UnsupportedVersion(i64),
+    /// No migration from this version is registered
+    MissingMigration(i64),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Decode(e) => write!(f, "{}", e),
+            Self::Invalid => write!(f, "not a versioned dictionary"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported version {}", v),
+            Self::MissingMigration(v) => write!(f, "no migration from version {}", v),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("bencode-minimal-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.benc");
+
+        let v1 = Schema::new(1).with_key("v");
+        v1.save(&path, &Value::empty_dict()).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"d1:vi1ee");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        let v3 = Schema::new(3).with_key("v").migration(1, |v| {
+            v.insert("a", Value::Int(1));
+        });
+        assert!(matches!(v3.load(&path, &DecodeOptions::default()), Err(ConfigError::MissingMigration(2))));
+        let v3 = v3.migration(2, |v| {
+            v.insert("b", Value::Int(2));
+        });
+        let v = v3.load(&path, &DecodeOptions::default()).unwrap();
+        assert_eq!(v.encode(), b"d1:ai1e1:bi2e1:vi3ee");
+        v3.save(&path, &v).unwrap();
+        assert!(matches!(v1.load(&path, &DecodeOptions::default()), Err(ConfigError::UnsupportedVersion(3))));
+
+        std::fs::write(&path, b"li1ee").unwrap();
+        assert!(matches!(v1.load(&path, &DecodeOptions::default()), Err(ConfigError::Invalid)));
+        std::fs::write(&path, b"d1:v").unwrap();
+        assert!(matches!(v1.load(&path, &DecodeOptions::default()), Err(ConfigError::Decode(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(v1.load(&path, &DecodeOptions::default()), Err(ConfigError::Io(_))));
+    }
+
+    #[test]
+    fn test_concurrent_saves() {
+        let dir = std::env::temp_dir().join(format!("bencode-minimal-concurrent-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.benc");
+        std::thread::scope(|s| {
+            for i in 0..8 {
+                let path = &path;
+                s.spawn(move || {
+                    for _ in 0..20 {
+                        write_atomic(path, &Value::Int(i).encode()).unwrap();
+                    }
+                });
+            }
+        });
+        let buf = std::fs::read(&path).unwrap();
+        assert!(matches!(Value::decode(&buf, 10), Ok(Value::Int(0..8))));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
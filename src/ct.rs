@@ -0,0 +1,59 @@
+use super::Value;
+
+/// Compare two byte strings in constant time
+///
+/// The run time only depends on the lengths, not on the content, so comparing a received DHT token
+/// or signature against the expected one does not leak how many leading bytes matched. Lengths are
+/// not considered secret and differing lengths return early.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let v = dict! { "token" => str!("aoeusnth") };
+/// let token = v.get::<&[u8]>("token").unwrap();
+/// assert!(ct_eq(token, b"aoeusnth"));
+/// assert!(!ct_eq(token, b"aoeusntx"));
+/// ```
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut x = 0u8;
+    for (a, b) in a.iter().zip(b) {
+        x |= std::hint::black_box(a ^ b);
+    }
+    std::hint::black_box(x) == 0
+}
+
+impl Value<'_> {
+    /// Whether this is a byte string equal to `other`, compared in constant time (see [ct_eq])
+    pub fn ct_eq_str(&self, other: &[u8]) -> bool {
+        match self {
+            Value::Str(s) => ct_eq(s, other),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_ct_eq() {
+        assert!(ct_eq(b"", b""));
+        assert!(ct_eq(&[7; 20], &[7; 20]));
+        assert!(!ct_eq(&[7; 20], &[7; 19]));
+        let mut x = [7; 20];
+        x[19] = 8;
+        assert!(!ct_eq(&[7; 20], &x));
+    }
+
+    #[test]
+    fn test_ct_eq_str() {
+        assert!(Value::Str(Cow::Borrowed(b"abc")).ct_eq_str(b"abc"));
+        assert!(!Value::Str(Cow::Borrowed(b"abc")).ct_eq_str(b"abd"));
+        assert!(!Value::Int(1).ct_eq_str(b"i1e"));
+    }
+}
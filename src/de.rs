@@ -0,0 +1,704 @@
+use super::decoder::{DICT_ENTRY_SIZE, LIST_ITEM_SIZE};
+use super::{peek_kind, DecodeError, DecodeOptions, Decoder, DuplicateKeys, Kind, SerdeError, Str, Value};
+use serde::de::{self, Deserialize, DeserializeSeed, IgnoredAny, Unexpected, Visitor};
+use std::borrow::Cow;
+use std::collections::{btree_map, BTreeMap, BTreeSet};
+
+/// Deserialize any [Deserialize] type from Bencode with [DecodeOptions::default]
+///
+/// Strings and byte slices borrow from `buf` where the target type allows it. Fields may appear in
+/// any order and missing `Option` fields are `None`. Integers `0` and `1` are accepted as `bool`,
+/// and map keys that are integers are parsed from their decimal string (see [to_bytes](super::to_bytes)).
+/// Bytes after the value are an error. The input is deserialized as it is decoded, without
+/// building a [Value] first.
+///
+/// ```rust
+/// use bencode_minimal::*;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// struct Ping<'a> {
+///     t: &'a [u8],
+///     y: char,
+///     q: &'a str,
+///     id: Option<u32>,
+/// }
+///
+/// let ping: Ping = from_bytes(b"d1:q4:ping1:t2:\xff\x011:y1:qe").unwrap();
+/// assert_eq!(ping, Ping { t: b"\xff\x01", y: 'q', q: "ping", id: None });
+/// assert!(from_bytes::<Ping>(b"d1:q4:ping1:t2:aa1:yi1ee").is_err());
+/// ```
+pub fn from_bytes<'de, T: Deserialize<'de>>(buf: &'de [u8]) -> Result<T, SerdeError> {
+    from_bytes_with(buf, &DecodeOptions::default())
+}
+
+/// Like [from_bytes], but decode with the given [DecodeOptions]
+///
+/// The input is checked and the limits apply as when decoding a [Value], including ignored fields.
+/// With [DuplicateKeys::LastWins], each dictionary is scanned for duplicates before its entries
+/// are deserialized.
+pub fn from_bytes_with<'de, T: Deserialize<'de>>(buf: &'de [u8], opts: &DecodeOptions) -> Result<T, SerdeError> {
+    let mut d = Decoder::with_options(buf, opts);
+    let r = T::deserialize(Stream { d: &mut d, text: false });
+    finish(&mut d, buf, r)
+}
+
+/// Check that the value was the whole input and report the result like [Decoder::take_value]
+pub(crate) fn finish<'de, T>(d: &mut Decoder<'de>, buf: &'de [u8], r: Result<T, SerdeError>) -> Result<T, SerdeError> {
+    let r = r.and_then(|v| match d.remaining() {
+        0 => Ok(v),
+        _ => Err(SerdeError::Decode(DecodeError::Invalid)),
+    });
+    match &r {
+        Ok(_) => d.finish(Ok(peek_kind(buf).unwrap_or(Kind::Int))),
+        Err(SerdeError::Decode(e)) => d.finish(Err(*e)),
+        Err(_) => (),
+    }
+    r
+}
+
+/// A [serde::Deserializer] reading from a decoded [Value]
+///
+/// Strings borrowed by the value can be borrowed by the deserialized type as well. The value is
+/// self-describing, so this also works with generic tools like `serde_transcode`.
+pub struct Deserializer<'de> {
+    value: Value<'de>,
+}
+
+impl<'de> Deserializer<'de> {
+    /// Raw values are decoded first and invalid ones fail to deserialize
+    pub fn new(value: Value<'de>) -> Self {
+        let value = match &value {
+            Value::Raw(s) => Value::unraw(s).unwrap_or(value),
+            _ => value,
+        };
+        Self { value }
+    }
+
+    fn unexpected(&self) -> Unexpected<'_> {
+        match &self.value {
+            Value::Int(i) => Unexpected::Signed(*i),
+            Value::Str(s) => Unexpected::Bytes(s),
+            Value::List(_) => Unexpected::Seq,
+            Value::Dict(_) => Unexpected::Map,
+            Value::Raw(_) => Unexpected::Other("invalid raw value"),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, SerdeError> {
+        match &mut self.value {
+            Value::Int(i) => visitor.visit_i64(*i),
+            Value::Str(Cow::Borrowed(s)) => visitor.visit_borrowed_bytes(s),
+            Value::Str(Cow::Owned(s)) => visitor.visit_byte_buf(std::mem::take(s)),
+            Value::List(l) => visitor.visit_seq(List(std::mem::take(l).into_iter())),
+            Value::Dict(d) => visitor.visit_map(Dict { iter: std::mem::take(d).into_iter(), value: None }),
+            Value::Raw(_) => Err(de::Error::invalid_type(self.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            Value::Int(0) => visitor.visit_bool(false),
+            Value::Int(1) => visitor.visit_bool(true),
+            _ => Err(de::Error::invalid_type(self.unexpected(), &"0 or 1")),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, SerdeError> {
+        match &mut self.value {
+            &mut Value::Str(Cow::Borrowed(s)) => match std::str::from_utf8(s) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => visitor.visit_borrowed_bytes(s),
+            },
+            Value::Str(Cow::Owned(s)) => match String::from_utf8(std::mem::take(s)) {
+                Ok(s) => visitor.visit_string(s),
+                Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        // `None` is never written, so a value that is present is always `Some`
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        mut self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match &mut self.value {
+            Value::Str(variant) => visitor.visit_enum(Enum { variant: std::mem::take(variant), value: None }),
+            Value::Dict(d) if d.len() == 1 => {
+                let (variant, value) = std::mem::take(d).into_iter().next().unwrap_or_default();
+                visitor.visit_enum(Enum { variant, value: Some(value) })
+            }
+            _ => Err(de::Error::invalid_type(self.unexpected(), &"string or dictionary with one entry")),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 seq tuple tuple_struct map struct
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+struct List<'de>(std::vec::IntoIter<Value<'de>>);
+
+impl<'de> de::SeqAccess<'de> for List<'de> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, SerdeError> {
+        self.0.next().map(|v| seed.deserialize(Deserializer::new(v))).transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct Dict<'de> {
+    iter: btree_map::IntoIter<Cow<'de, [u8]>, Value<'de>>,
+    // Value of the entry whose key was returned last
+    value: Option<Value<'de>>,
+}
+
+impl<'de> de::MapAccess<'de> for Dict<'de> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, SerdeError> {
+        let Some((k, v)) = self.iter.next() else {
+            return Ok(None);
+        };
+        self.value = Some(v);
+        seed.deserialize(MapKey(k)).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, SerdeError> {
+        let value = self.value.take().ok_or_else(|| SerdeError::Custom("map value without key".into()))?;
+        seed.deserialize(Deserializer::new(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct Enum<'de> {
+    variant: Cow<'de, [u8]>,
+    // Content of variants with data
+    value: Option<Value<'de>>,
+}
+
+impl<'de> de::EnumAccess<'de> for Enum<'de> {
+    type Error = SerdeError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(mut self, seed: V) -> Result<(V::Value, Self), SerdeError> {
+        let variant = std::mem::take(&mut self.variant);
+        Ok((seed.deserialize(MapKey(variant))?, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Enum<'de> {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        match self.value {
+            None => Ok(()),
+            Some(_) => Err(de::Error::invalid_type(Unexpected::Map, &"unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, SerdeError> {
+        match self.value {
+            Some(v) => seed.deserialize(Deserializer::new(v)),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &"newtype variant")),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            Some(v) => de::Deserializer::deserialize_seq(Deserializer::new(v), visitor),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &"tuple variant")),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _: &'static [&'static str], visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            Some(v) => de::Deserializer::deserialize_map(Deserializer::new(v), visitor),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &"struct variant")),
+        }
+    }
+}
+
+/// Reads a dictionary key which may also be parsed as integer
+struct MapKey<'de>(Cow<'de, [u8]>);
+
+impl MapKey<'_> {
+    fn parse<T: std::str::FromStr>(&self) -> Result<T, SerdeError> {
+        let s = std::str::from_utf8(&self.0).ok().and_then(|s| s.parse().ok());
+        s.ok_or_else(|| de::Error::invalid_type(Unexpected::Bytes(&self.0), &"integer key"))
+    }
+}
+
+macro_rules! deserialize_int_key {
+    ($($method:ident => $visit:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for MapKey<'de> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        Deserializer::new(Value::Str(self.0)).deserialize_str(visitor)
+    }
+
+    deserialize_int_key! {
+        deserialize_i8 => visit_i8, deserialize_i16 => visit_i16, deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64, deserialize_i128 => visit_i128, deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16, deserialize_u32 => visit_u32, deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_enum(Enum { variant: self.0, value: None })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool f32 f64 char str string bytes byte_buf option unit unit_struct seq tuple tuple_struct map
+        struct identifier ignored_any
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Deserializes straight from a [Decoder] without building a [Value] first
+pub(crate) struct Stream<'s, 'de> {
+    pub(crate) d: &'s mut Decoder<'de>,
+    // Present strings that are valid UTF-8 as text, for transcoding into text formats
+    pub(crate) text: bool,
+}
+
+impl<'de> Stream<'_, 'de> {
+    fn reborrow(&mut self) -> Stream<'_, 'de> {
+        Stream { d: self.d, text: self.text }
+    }
+
+    fn peek_str(&self) -> Result<bool, DecodeError> {
+        Ok(self.d.peek_u8()?.is_ascii_digit())
+    }
+
+    /// Check a value without deserializing it
+    fn ignore(self) -> Result<(), SerdeError> {
+        de::Deserializer::deserialize_any(self, IgnoredAny).map(drop)
+    }
+
+    fn visit_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.d.take_str()? {
+            Cow::Borrowed(s) => match std::str::from_utf8(s) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => visitor.visit_borrowed_bytes(s),
+            },
+            Cow::Owned(s) => match String::from_utf8(s) {
+                Ok(s) => visitor.visit_string(s),
+                Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+            },
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Stream<'_, 'de> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.d.peek_u8()? {
+            b'0'..=b'9' if self.text => return self.visit_str(visitor),
+            b'0'..=b'9' => {
+                return match self.d.take_str()? {
+                    Cow::Borrowed(s) => visitor.visit_borrowed_bytes(s),
+                    Cow::Owned(s) => visitor.visit_byte_buf(s),
+                };
+            }
+            _ => (),
+        }
+        match self.d.peek_u8()? {
+            b'i' => visitor.visit_i64(self.d.take_int()?),
+            b'l' => {
+                self.d.begin_list()?;
+                let mut list = StreamList { s: self.reborrow(), done: false };
+                let v = visitor.visit_seq(&mut list)?;
+                while de::SeqAccess::next_element::<IgnoredAny>(&mut &mut list)?.is_some() {}
+                Ok(v)
+            }
+            b'd' => {
+                self.d.begin_dict()?;
+                let mut dict = StreamDict::new(self.reborrow())?;
+                let v = visitor.visit_map(&mut dict)?;
+                while de::MapAccess::next_entry::<IgnoredAny, IgnoredAny>(&mut &mut dict)?.is_some() {}
+                Ok(v)
+            }
+            _ => Err(DecodeError::Invalid.into()),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.d.take_int()? {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            i => Err(de::Error::invalid_type(Unexpected::Signed(i), &"0 or 1")),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.peek_str()? {
+            true => self.visit_str(visitor),
+            false => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        // `None` is never written, so a value that is present is always `Some`
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.ignore()?;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        if self.peek_str()? {
+            return visitor.visit_enum(Enum { variant: self.d.take_str()?, value: None });
+        }
+        self.d.begin_dict()?;
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.ignore()?;
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 bytes byte_buf seq tuple tuple_struct map struct
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+struct StreamList<'s, 'de> {
+    s: Stream<'s, 'de>,
+    // Whether the end of the list was consumed
+    done: bool,
+}
+
+impl<'de> de::SeqAccess<'de> for &mut StreamList<'_, 'de> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, SerdeError> {
+        if self.done || !self.s.d.next_item()? {
+            self.done = true;
+            return Ok(None);
+        }
+        self.s.d.alloc(LIST_ITEM_SIZE)?;
+        seed.deserialize(self.s.reborrow()).map(Some)
+    }
+}
+
+struct StreamDict<'s, 'de> {
+    s: Stream<'s, 'de>,
+    // Whether the end of the dictionary was consumed
+    done: bool,
+    seen: BTreeSet<Str<'de>>,
+    // Occurrences of each key from the current one on, to skip all but the last one
+    ahead: Option<BTreeMap<Str<'de>, usize>>,
+}
+
+impl<'s, 'de> StreamDict<'s, 'de> {
+    fn new(s: Stream<'s, 'de>) -> Result<Self, DecodeError> {
+        let ahead = match s.d.duplicate_keys {
+            DuplicateKeys::LastWins if !s.d.strict => {
+                let checkpoint = s.d.save();
+                let mut ahead = BTreeMap::new();
+                while let Some(k) = s.d.next_key()? {
+                    *ahead.entry(k).or_insert(0) += 1;
+                    s.d.skip_value()?;
+                }
+                s.d.restore(checkpoint);
+                Some(ahead)
+            }
+            _ => None,
+        };
+        Ok(Self { s, done: false, seen: BTreeSet::new(), ahead })
+    }
+}
+
+impl<'de> de::MapAccess<'de> for &mut StreamDict<'_, 'de> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, SerdeError> {
+        loop {
+            let Some(key) = (if self.done { None } else { self.s.d.next_key()? }) else {
+                self.done = true;
+                return Ok(None);
+            };
+            self.s.d.alloc(DICT_ENTRY_SIZE)?;
+            let keep = match self.seen.insert(key.clone()) {
+                true => true,
+                false => self.s.d.duplicate_key(key.clone())?,
+            };
+            let last = match &mut self.ahead {
+                Some(ahead) => {
+                    let n = ahead.get_mut(&key).expect("keys are counted in advance");
+                    *n -= 1;
+                    *n == 0
+                }
+                None => true,
+            };
+            if keep && last {
+                return seed.deserialize(MapKey(key)).map(Some);
+            }
+            self.s.reborrow().ignore()?;
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, SerdeError> {
+        seed.deserialize(self.s.reborrow())
+    }
+}
+
+/// A dictionary with one entry from the variant name to its content
+impl<'de> de::EnumAccess<'de> for Stream<'_, 'de> {
+    type Error = SerdeError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), SerdeError> {
+        let Some(variant) = self.d.next_key()? else {
+            return Err(de::Error::invalid_type(Unexpected::Map, &"string or dictionary with one entry"));
+        };
+        self.d.alloc(DICT_ENTRY_SIZE)?;
+        Ok((seed.deserialize(MapKey(variant))?, self))
+    }
+}
+
+impl<'de> Stream<'_, 'de> {
+    /// Consume the end of an enum dictionary after the variant's content
+    fn end_enum<T>(self, content: Result<T, SerdeError>) -> Result<T, SerdeError> {
+        let content = content?;
+        match self.d.next_key()? {
+            None => Ok(content),
+            Some(_) => Err(de::Error::invalid_type(Unexpected::Map, &"string or dictionary with one entry")),
+        }
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Stream<'_, 'de> {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        Err(de::Error::invalid_type(Unexpected::Map, &"unit variant"))
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(mut self, seed: T) -> Result<T::Value, SerdeError> {
+        let r = seed.deserialize(self.reborrow());
+        self.end_enum(r)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(mut self, _: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        let r = de::Deserializer::deserialize_seq(self.reborrow(), visitor);
+        self.end_enum(r)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        mut self,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        let r = de::Deserializer::deserialize_map(self.reborrow(), visitor);
+        self.end_enum(r)
+    }
+}
+
+impl de::Error for SerdeError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::to_bytes;
+    use serde::{Deserialize, Serialize};
+    use std::collections::{BTreeMap, HashMap};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Message {
+        Ping,
+        Error(i64, String),
+        Announce { port: u16, token: Option<Vec<u8>> },
+        Id(u32),
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for m in [
+            Message::Ping,
+            Message::Error(201, "x".into()),
+            Message::Announce { port: 6881, token: Some(b"ab".to_vec()) },
+            Message::Announce { port: 6881, token: None },
+            Message::Id(7),
+        ] {
+            assert_eq!(from_bytes::<Message>(&to_bytes(&m).unwrap()), Ok(m));
+        }
+        let m = HashMap::from([(3u32, true), (10, false)]);
+        assert_eq!(from_bytes::<HashMap<u32, bool>>(&to_bytes(&m).unwrap()), Ok(m));
+        let t = (1u8, 'ä', vec![-1i64]);
+        assert_eq!(from_bytes::<(u8, char, Vec<i64>)>(&to_bytes(&t).unwrap()), Ok(t));
+    }
+
+    #[test]
+    fn test_borrowing() {
+        let buf = b"d1:a2:xy1:b2:\xff\xffe";
+        let m: BTreeMap<&str, &[u8]> = from_bytes(buf).unwrap();
+        assert_eq!(m["a"], b"xy");
+        assert_eq!(m["b"].as_ptr(), buf[13..].as_ptr());
+        assert!(from_bytes::<BTreeMap<&str, &str>>(buf).is_err());
+        assert!(from_bytes::<BTreeMap<String, String>>(b"d1:a1:xe").is_ok());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(from_bytes::<i64>(b"i1ex"), Err(SerdeError::Decode(DecodeError::Invalid)));
+        assert_eq!(from_bytes::<i64>(b"i1"), Err(SerdeError::Decode(DecodeError::Incomplete { needed: 1 })));
+        assert!(matches!(from_bytes::<u8>(b"i256e"), Err(SerdeError::Custom(_))));
+        assert!(matches!(from_bytes::<bool>(b"i2e"), Err(SerdeError::Custom(_))));
+        assert!(matches!(from_bytes::<Message>(b"d4:Pingi1ee"), Err(SerdeError::Custom(_))));
+        assert!(matches!(from_bytes::<Message>(b"d2:Id1:x1:zi0ee"), Err(SerdeError::Custom(_))));
+        assert!(matches!(from_bytes::<HashMap<u8, i64>>(b"d1:xi0ee"), Err(SerdeError::Custom(_))));
+        let opts = DecodeOptions { max_allocs: 1, ..Default::default() };
+        assert_eq!(
+            from_bytes_with::<Vec<i64>>(b"li1ei2ee", &opts),
+            Err(SerdeError::Decode(DecodeError::LimitExceeded))
+        );
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Port {
+        port: u16,
+    }
+
+    #[test]
+    fn test_options() {
+        // Ignored fields are skipped but still checked
+        assert_eq!(from_bytes::<Port>(b"d1:ali1ee4:porti1ee"), Ok(Port { port: 1 }));
+        assert!(from_bytes::<Port>(b"d1:ali1x4:porti1ee").is_err());
+        assert_eq!(from_bytes::<Port>(b"d1:ad1:ai0e1:ai0ee4:porti1ee"), Err(SerdeError::Decode(DecodeError::Invalid)));
+
+        let buf = b"d4:porti1e4:porti2ee";
+        assert_eq!(from_bytes::<Port>(buf), Err(SerdeError::Decode(DecodeError::Invalid)));
+        let opts = DecodeOptions::default().with_duplicate_keys(DuplicateKeys::FirstWins);
+        assert_eq!(from_bytes_with::<Port>(buf, &opts), Ok(Port { port: 1 }));
+        let opts = DecodeOptions::default().with_duplicate_keys(DuplicateKeys::LastWins);
+        assert_eq!(from_bytes_with::<Port>(buf, &opts), Ok(Port { port: 2 }));
+        assert_eq!(from_bytes_with::<Port>(b"d4:porti1e1:ai0e4:porti3e1:ai0ee", &opts), Ok(Port { port: 3 }));
+
+        let opts = DecodeOptions::default().with_strict(true);
+        assert_eq!(from_bytes_with::<Port>(b"d4:porti1e1:ai0ee", &opts), Err(SerdeError::Decode(DecodeError::Invalid)));
+        assert_eq!(from_bytes_with::<Port>(b"d1:ai0e4:porti1ee", &opts), Ok(Port { port: 1 }));
+
+        // An enum dictionary has exactly one entry
+        assert!(from_bytes::<Message>(b"d2:Idi1e2:Idi2ee").is_err());
+        assert!(from_bytes::<Message>(b"de").is_err());
+        assert_eq!(from_bytes::<Vec<Message>>(b"ld2:Idi1eee"), Ok(vec![Message::Id(1)]));
+    }
+}
@@ -0,0 +1,172 @@
+use super::decoder::Decoder;
+use super::{DecodeError, Value};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// Conversion from encoded input without building a [Value] tree
+///
+/// This is the direct counterpart to [TryFromValue](super::TryFromValue) for performance-critical
+/// consumers. Structs typically walk a dictionary with [Decoder::next_key] and skip unknown keys:
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// struct Ping<'a> {
+///     id: &'a [u8],
+/// }
+///
+/// impl<'a> Decodable<'a> for Ping<'a> {
+///     fn decode(d: &mut Decoder<'a>) -> Result<Self, DecodeError> {
+///         let mut id = None;
+///         d.begin_dict()?;
+///         while let Some(key) = d.next_key()? {
+///             match key.as_ref() {
+///                 b"id" => id = Some(d.decode()?),
+///                 _ => d.skip_value()?,
+///             }
+///         }
+///         Ok(Ping { id: id.ok_or(DecodeError::Invalid)? })
+///     }
+/// }
+///
+/// let mut d = Decoder::with_options(b"d2:id3:abc1:xi1ee", &DecodeOptions::default());
+/// let ping = d.decode::<Ping>().unwrap();
+/// assert_eq!(ping.id, b"abc");
+/// ```
+pub trait Decodable<'a>: Sized {
+    fn decode(d: &mut Decoder<'a>) -> Result<Self, DecodeError>;
+}
+
+impl<'a> Decodable<'a> for i64 {
+    fn decode(d: &mut Decoder<'a>) -> Result<Self, DecodeError> {
+        d.take_int()
+    }
+}
+
+impl<'a> Decodable<'a> for Cow<'a, [u8]> {
+    fn decode(d: &mut Decoder<'a>) -> Result<Self, DecodeError> {
+        d.take_str()
+    }
+}
+
+/// Fails with [DecodeError::Invalid] for strings spanning multiple slices of chained input
+impl<'a> Decodable<'a> for &'a [u8] {
+    fn decode(d: &mut Decoder<'a>) -> Result<Self, DecodeError> {
+        match d.take_str()? {
+            Cow::Borrowed(s) => Ok(s),
+            Cow::Owned(_) => Err(DecodeError::Invalid),
+        }
+    }
+}
+
+impl<'a> Decodable<'a> for &'a str {
+    fn decode(d: &mut Decoder<'a>) -> Result<Self, DecodeError> {
+        std::str::from_utf8(d.decode()?).map_err(|_| DecodeError::Invalid)
+    }
+}
+
+impl<'a, const N: usize> Decodable<'a> for [u8; N] {
+    fn decode(d: &mut Decoder<'a>) -> Result<Self, DecodeError> {
+        d.take_str()?.as_ref().try_into().map_err(|_| DecodeError::Invalid)
+    }
+}
+
+impl<'a> Decodable<'a> for Value<'a> {
+    fn decode(d: &mut Decoder<'a>) -> Result<Self, DecodeError> {
+        d.take_value()
+    }
+}
+
+impl<'a, T: Decodable<'a>> Decodable<'a> for Vec<T> {
+    fn decode(d: &mut Decoder<'a>) -> Result<Self, DecodeError> {
+        let mut v = Vec::new();
+        d.begin_list()?;
+        while d.next_item()? {
+            d.alloc(std::mem::size_of::<T>())?;
+            v.push(d.decode()?);
+        }
+        Ok(v)
+    }
+}
+
+/// Handles duplicate keys according to [DecodeOptions::duplicate_keys](super::DecodeOptions::duplicate_keys)
+impl<'a, T: Decodable<'a>> Decodable<'a> for BTreeMap<Cow<'a, [u8]>, T> {
+    fn decode(d: &mut Decoder<'a>) -> Result<Self, DecodeError> {
+        let mut m = BTreeMap::new();
+        d.begin_dict()?;
+        while let Some(k) = d.next_key()? {
+            d.alloc(std::mem::size_of::<(Cow<[u8]>, T)>())?;
+            let v = d.decode()?;
+            d.insert_entry(&mut m, k, v)?;
+        }
+        Ok(m)
+    }
+}
+
+/// Decodes a list of at least two items, further items are skipped
+impl<'a, A: Decodable<'a>, B: Decodable<'a>> Decodable<'a> for (A, B) {
+    fn decode(d: &mut Decoder<'a>) -> Result<Self, DecodeError> {
+        d.begin_list()?;
+        let a = if d.next_item()? { d.decode()? } else { return Err(DecodeError::Invalid) };
+        let b = if d.next_item()? { d.decode()? } else { return Err(DecodeError::Invalid) };
+        while d.next_item()? {
+            d.skip_value()?;
+        }
+        Ok((a, b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ContinueHook, DecodeOptions};
+
+    fn decode<'a, T: Decodable<'a>>(buf: &'a [u8]) -> Result<T, DecodeError> {
+        Decoder::with_options(buf, &DecodeOptions::default()).decode()
+    }
+
+    #[test]
+    fn test_primitives() {
+        assert_eq!(decode::<i64>(b"i-3e"), Ok(-3));
+        assert_eq!(decode::<&[u8]>(b"3:abc"), Ok(b"abc".as_ref()));
+        assert_eq!(decode::<&str>(b"3:abc"), Ok("abc"));
+        assert_eq!(decode::<&str>(b"1:\xff"), Err(DecodeError::Invalid));
+        assert_eq!(decode::<[u8; 3]>(b"3:abc"), Ok(*b"abc"));
+        assert_eq!(decode::<[u8; 2]>(b"3:abc"), Err(DecodeError::Invalid));
+    }
+
+    #[test]
+    fn test_containers() {
+        assert_eq!(decode::<Vec<i64>>(b"li1ei2ee"), Ok(vec![1, 2]));
+        assert_eq!(decode::<Vec<i64>>(b"li1e1:xe"), Err(DecodeError::Invalid));
+        assert_eq!(decode::<(i64, &str)>(b"li1e1:xi3ee"), Ok((1, "x")));
+        assert_eq!(decode::<(i64, &str)>(b"li1ee"), Err(DecodeError::Invalid));
+        let m = decode::<BTreeMap<Cow<[u8]>, i64>>(b"d1:bi2e1:ai1ee").unwrap();
+        assert_eq!(
+            m.into_iter().collect::<Vec<_>>(),
+            vec![(Cow::Borrowed(b"a".as_ref()), 1), (Cow::Borrowed(b"b".as_ref()), 2)]
+        );
+        assert_eq!(decode::<BTreeMap<Cow<[u8]>, i64>>(b"d1:ai2e1:ai1ee"), Err(DecodeError::Invalid));
+    }
+
+    #[test]
+    fn test_alloc_limit() {
+        let opts = DecodeOptions { max_allocs: 1, ..Default::default() };
+        assert_eq!(Decoder::with_options(b"li1ei2ee", &opts).decode::<Vec<i64>>(), Err(DecodeError::LimitExceeded));
+    }
+
+    #[test]
+    fn test_steps() {
+        let opts = DecodeOptions::default().with_max_steps(2);
+        assert_eq!(Decoder::with_options(b"li1ee", &opts).decode::<Vec<i64>>(), Ok(vec![1]));
+        assert_eq!(Decoder::with_options(b"li1ei2ee", &opts).decode::<Vec<i64>>(), Err(DecodeError::LimitExceeded));
+        assert_eq!(
+            Decoder::with_options(b"d1:ai1ee", &opts).decode::<BTreeMap<Cow<[u8]>, i64>>().map(|m| m.len()),
+            Ok(1)
+        );
+
+        let buf = [b"l".as_ref(), &b"i1e".repeat(DecodeOptions::CANCEL_CHECK_INTERVAL), b"e"].concat();
+        let opts = DecodeOptions::default().with_should_continue(ContinueHook::new(|| false));
+        assert_eq!(Decoder::with_options(&buf, &opts).decode::<Vec<i64>>(), Err(DecodeError::Cancelled));
+    }
+}
@@ -0,0 +1,88 @@
+use super::{DecodeError, DecodeOptions, Value};
+use std::iter::FusedIterator;
+
+/// Decode the back-to-back values in `buf` one after the other
+///
+/// Meant for captures and append-only record files that store one document after another. The
+/// iterator ends at the end of `buf` or after yielding the first error. Each value is decoded with
+/// its own limits as if by [Value::decode_prefix].
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let opts = DecodeOptions::default();
+/// let values: Vec<_> = decode_all(b"i1e3:abcle", &opts).collect();
+/// assert_eq!(values, [Ok(int!(1)), Ok(str!("abc")), Ok(list![])]);
+///
+/// let mut it = decode_all(b"i1ei2", &opts);
+/// assert_eq!(it.next(), Some(Ok(int!(1))));
+/// assert_eq!(it.next(), Some(Err(DecodeError::Incomplete { needed: 1 })));
+/// assert_eq!(it.rest(), b"i2");
+/// assert_eq!(it.next(), None);
+/// ```
+pub fn decode_all<'a>(buf: &'a [u8], opts: &DecodeOptions) -> DecodeAll<'a> {
+    DecodeAll { rest: buf, opts: opts.clone(), failed: false }
+}
+
+/// Iterator over the values in a buffer (see [decode_all])
+#[derive(Clone, Debug)]
+pub struct DecodeAll<'a> {
+    rest: &'a [u8],
+    opts: DecodeOptions,
+    failed: bool,
+}
+
+impl<'a> DecodeAll<'a> {
+    /// The input not decoded yet, starting with the failed value after an error
+    pub fn rest(&self) -> &'a [u8] {
+        self.rest
+    }
+}
+
+impl<'a> Iterator for DecodeAll<'a> {
+    type Item = Result<Value<'a>, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.rest.is_empty() {
+            return None;
+        }
+        match Value::decode_prefix(self.rest, &self.opts) {
+            Ok((v, rest)) => {
+                self.rest = rest;
+                Some(Ok(v))
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl FusedIterator for DecodeAll<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_all() {
+        let opts = DecodeOptions::default();
+        assert_eq!(decode_all(b"", &opts).next(), None);
+
+        let buf = b"d1:ai1ee" as &[u8];
+        let values: Vec<_> = decode_all(&buf.repeat(3), &opts).map(|v| v.map(Value::into_owned)).collect();
+        assert_eq!(values, vec![Value::decode_with(buf, &opts).map(Value::into_owned); 3]);
+
+        let mut it = decode_all(b"i1exi2e", &opts);
+        assert_eq!(it.next(), Some(Ok(Value::Int(1))));
+        assert_eq!(it.next(), Some(Err(DecodeError::Invalid)));
+        assert_eq!(it.rest(), b"xi2e");
+        assert_eq!(it.next(), None);
+
+        // Limits apply to each value on its own
+        let opts = opts.with_max_allocs(1);
+        assert_eq!(decode_all(b"li1eeli2ee", &opts).filter(Result::is_ok).count(), 2);
+        assert_eq!(decode_all(b"li1ei2ee", &opts).next(), Some(Err(DecodeError::LimitExceeded)));
+    }
+}
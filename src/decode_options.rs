@@ -0,0 +1,331 @@
+use super::DecodeError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Limits applied while decoding (see [Value::decode_with](super::Value::decode_with))
+///
+/// The default imposes no limit on the number of allocations but restricts the decoded structure
+/// to [DEFAULT_MAX_SIZE_FACTOR](Self::DEFAULT_MAX_SIZE_FACTOR) times the input length and its
+/// nesting to [DEFAULT_MAX_DEPTH](Self::DEFAULT_MAX_DEPTH) levels. Options can be set field by
+/// field or with the `with_` methods.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let opts = DecodeOptions { max_size_factor: Some(4), ..Default::default() };
+/// assert!(Value::decode_with(b"d4:name10:helloworlde", &opts).is_ok());
+/// assert_eq!(Value::decode_with(b"li1ei2ei3ee", &opts), Err(DecodeError::LimitExceeded));
+///
+/// let opts = DecodeOptions::default().with_max_depth(2).with_max_str_bytes(8);
+/// assert!(Value::decode_with(b"d4:infold2:idi1eeee", &opts).is_err());
+/// assert!(Value::decode_with(b"d4:name10:helloworlde", &opts).is_err());
+/// assert!(Value::decode_with(b"d4:infoli1eee", &opts).is_ok());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// Maximum number of list items and dictionary entries (see [Value::decode](super::Value::decode))
+    pub max_allocs: usize,
+    /// Maximum size of the decoded structure as a multiple of the input length
+    ///
+    /// The size is the memory occupied by list items and dictionary entries. Byte strings are
+    /// borrowed from the input and therefor not counted. Each list item needs at least 2 bytes
+    /// of input (`0:`), so factors below `size_of::<Value>() / 2` may reject valid but dense
+    /// input. `None` disables the check.
+    pub max_size_factor: Option<usize>,
+    /// Maximum number of values (including nested ones) to decode
+    ///
+    /// Decoding a single value takes constant time apart from inserting into a dictionary
+    /// (logarithmic in its size), so this bounds the work of a decode call independently of
+    /// the input length. Integers and string lengths with more digits than any value needs
+    /// (i.e. leading zeros) take one more step per extra digit. The step is counted by the
+    /// [Decoder](super::Decoder) methods that read a value, so [Decodable](super::Decodable)
+    /// implementations are bounded as well.
+    pub max_steps: usize,
+    /// Maximum nesting depth of lists and dictionaries
+    ///
+    /// A plain integer or string has depth 0. Unlike the other limits, this also applies when
+    /// [skipping](super::Decoder::skip_value) values.
+    ///
+    /// Decoding a [Value](super::Value) recurses once per level, so this is what
+    /// protects the stack from hostile input like `llll...`. Each level takes up to about
+    /// 2 KiB of stack in debug builds and a quarter of that with optimizations.
+    pub max_depth: usize,
+    /// Maximum total length of all byte strings including dictionary keys
+    ///
+    /// Strings are borrowed from the input, but this bounds the memory taken by owned copies (see
+    /// [Value::into_owned](super::Value::into_owned)). Skipped strings are not counted.
+    pub max_str_bytes: usize,
+    /// Reject input that is valid but not canonical
+    ///
+    /// Canonical Bencode has no leading zeros in integers and string lengths, no `i-0e` and
+    /// dictionary keys in strictly ascending order. Protocols that sign or hash what they received
+    /// (like BEP 44 items or infohashes) need this to make sure that re-encoding the decoded value
+    /// yields the same bytes. Key order is not checked when skipping values or walking dictionaries
+    /// with [Decoder::next_key](super::Decoder::next_key).
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let opts = DecodeOptions::default().with_strict(true);
+    /// for buf in [b"i03e".as_ref(), b"i-0e", b"01:a", b"d1:bi1e1:ai2ee"] {
+    ///     assert!(Value::decode(buf, 10).is_ok());
+    ///     assert_eq!(Value::decode_with(buf, &opts), Err(DecodeError::Invalid));
+    /// }
+    /// assert!(Value::decode_with(b"d1:ai0e1:bi-3ee", &opts).is_ok());
+    /// ```
+    pub strict: bool,
+    /// How to handle dictionary keys that appear more than once
+    ///
+    /// Duplicate keys are invalid Bencode, but some clients produce them anyway. They are always
+    /// rejected in [strict](Self::strict) mode. The keys seen twice are available from
+    /// [Decoder::duplicates](super::Decoder::duplicates).
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let buf = b"d1:ai1e1:bi2e1:ai3ee";
+    /// let opts = DecodeOptions::default();
+    /// assert_eq!(Value::decode_with(buf, &opts), Err(DecodeError::Invalid));
+    /// let opts = opts.with_duplicate_keys(DuplicateKeys::FirstWins);
+    /// assert_eq!(Value::decode_with(buf, &opts).unwrap().encode(), b"d1:ai1e1:bi2ee");
+    /// let opts = opts.with_duplicate_keys(DuplicateKeys::LastWins);
+    /// assert_eq!(Value::decode_with(buf, &opts).unwrap().encode(), b"d1:ai3e1:bi2ee");
+    /// ```
+    pub duplicate_keys: DuplicateKeys,
+    /// Called whenever decoding a top-level value fails, whatever it is decoded into (see
+    /// [RejectHook])
+    pub on_reject: Option<RejectHook>,
+    /// Polled every [CANCEL_CHECK_INTERVAL](Self::CANCEL_CHECK_INTERVAL) values to abort decoding
+    /// with [DecodeError::Cancelled] (see [ContinueHook])
+    pub should_continue: Option<ContinueHook>,
+}
+
+impl DecodeOptions {
+    pub const DEFAULT_MAX_SIZE_FACTOR: usize = 16;
+    /// Far deeper than any real-world message, but shallow enough for small thread stacks
+    pub const DEFAULT_MAX_DEPTH: usize = 256;
+    /// Number of decoded values between two calls of [Self::should_continue] (a power of two)
+    pub const CANCEL_CHECK_INTERVAL: usize = 1024;
+
+    /// Set [Self::max_allocs]
+    pub fn with_max_allocs(mut self, n: usize) -> Self {
+        self.max_allocs = n;
+        self
+    }
+
+    /// Set [Self::max_size_factor]
+    pub fn with_max_size_factor(mut self, factor: Option<usize>) -> Self {
+        self.max_size_factor = factor;
+        self
+    }
+
+    /// Set [Self::max_steps]
+    pub fn with_max_steps(mut self, n: usize) -> Self {
+        self.max_steps = n;
+        self
+    }
+
+    /// Set [Self::max_depth]
+    pub fn with_max_depth(mut self, n: usize) -> Self {
+        self.max_depth = n;
+        self
+    }
+
+    /// Set [Self::max_str_bytes]
+    pub fn with_max_str_bytes(mut self, n: usize) -> Self {
+        self.max_str_bytes = n;
+        self
+    }
+
+    /// Set [Self::strict]
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set [Self::duplicate_keys]
+    pub fn with_duplicate_keys(mut self, policy: DuplicateKeys) -> Self {
+        self.duplicate_keys = policy;
+        self
+    }
+
+    /// Set [Self::on_reject]
+    pub fn with_on_reject(mut self, hook: RejectHook) -> Self {
+        self.on_reject = Some(hook);
+        self
+    }
+
+    /// Set [Self::should_continue]
+    pub fn with_should_continue(mut self, hook: ContinueHook) -> Self {
+        self.should_continue = Some(hook);
+        self
+    }
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            max_allocs: usize::MAX,
+            max_size_factor: Some(Self::DEFAULT_MAX_SIZE_FACTOR),
+            max_steps: usize::MAX,
+            max_depth: Self::DEFAULT_MAX_DEPTH,
+            max_str_bytes: usize::MAX,
+            strict: false,
+            duplicate_keys: DuplicateKeys::Reject,
+            on_reject: None,
+            should_continue: None,
+        }
+    }
+}
+
+/// What to do with a dictionary key that appears more than once (see [DecodeOptions::duplicate_keys])
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DuplicateKeys {
+    /// Fail with [DecodeError::Invalid]
+    Reject,
+    /// Keep the value of the first occurrence
+    FirstWins,
+    /// Keep the value of the last occurrence
+    LastWins,
+}
+
+/// Details about a failed decode passed to a [RejectHook]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rejection<'a> {
+    /// Position in the input at which decoding failed
+    pub offset: usize,
+    pub error: DecodeError,
+    /// The start of the input, at most [Self::MAX_PREFIX_LEN] bytes
+    pub prefix: &'a [u8],
+}
+
+impl Rejection<'_> {
+    pub const MAX_PREFIX_LEN: usize = 64;
+}
+
+/// A callback invoked on every rejected input (see [DecodeOptions::on_reject])
+///
+/// This allows to collect statistics about malformed packets without parsing them a second time.
+/// Hooks compare equal if they are clones of each other.
+///
+/// ```rust
+/// use bencode_minimal::*;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// let invalid = Arc::new(AtomicUsize::new(0));
+/// let counter = invalid.clone();
+/// let opts = DecodeOptions {
+///     on_reject: Some(RejectHook::new(move |r| {
+///         assert_eq!((r.offset, r.prefix), (3, b"li1x".as_ref()));
+///         counter.fetch_add(1, Ordering::Relaxed);
+///     })),
+///     ..Default::default()
+/// };
+/// assert_eq!(Value::decode_with(b"li1x", &opts), Err(DecodeError::Invalid));
+/// assert_eq!(invalid.load(Ordering::Relaxed), 1);
+/// ```
+#[derive(Clone)]
+pub struct RejectHook(Arc<dyn Fn(&Rejection<'_>) + Send + Sync>);
+
+impl RejectHook {
+    pub fn new(f: impl Fn(&Rejection<'_>) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub fn call(&self, rejection: &Rejection<'_>) {
+        (self.0)(rejection)
+    }
+}
+
+impl std::fmt::Debug for RejectHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RejectHook")
+    }
+}
+
+impl PartialEq for RejectHook {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for RejectHook {}
+
+/// A callback deciding whether a long-running decode should go on (see [DecodeOptions::should_continue])
+///
+/// This lets a service abort decoding a huge or adversarial buffer when the request is cancelled or
+/// a deadline passes. Hooks compare equal if they are clones of each other.
+///
+/// ```rust
+/// use bencode_minimal::*;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::sync::Arc;
+///
+/// let cancelled = Arc::new(AtomicBool::new(false));
+/// let opts = DecodeOptions { should_continue: Some(ContinueHook::unless(cancelled.clone())), ..Default::default() };
+/// let buf = [b"l".as_ref(), &b"i1e".repeat(5000), b"e"].concat();
+/// assert!(Value::decode_with(&buf, &opts).is_ok());
+///
+/// cancelled.store(true, Ordering::Relaxed);
+/// assert_eq!(Value::decode_with(&buf, &opts), Err(DecodeError::Cancelled));
+/// // Small inputs finish before the hook is consulted
+/// assert!(Value::decode_with(b"li1ee", &opts).is_ok());
+/// ```
+#[derive(Clone)]
+pub struct ContinueHook(Arc<dyn Fn() -> bool + Send + Sync>);
+
+impl ContinueHook {
+    /// Continue as long as `f` returns `true`
+    pub fn new(f: impl Fn() -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    /// Continue until `flag` is set
+    pub fn unless(flag: Arc<AtomicBool>) -> Self {
+        Self::new(move || !flag.load(Ordering::Relaxed))
+    }
+
+    /// Continue until `deadline` has passed
+    pub fn until(deadline: Instant) -> Self {
+        Self::new(move || Instant::now() < deadline)
+    }
+
+    pub fn call(&self) -> bool {
+        (self.0)()
+    }
+}
+
+impl std::fmt::Debug for ContinueHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ContinueHook")
+    }
+}
+
+impl PartialEq for ContinueHook {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for ContinueHook {}
+
+/// Budget consumed by a successful decode (see [Value::decode_with_stats](super::Value::decode_with_stats))
+///
+/// Comparing these numbers against the configured [DecodeOptions] on real traffic helps to choose
+/// limits that are tight but do not reject legitimate messages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DecodeStats {
+    /// Number of list items and dictionary entries (counted against `max_allocs`)
+    pub allocs: usize,
+    /// Size of the decoded structure in bytes (counted against `max_size_factor`)
+    pub size: usize,
+    /// Number of decoded values and excess digits (counted against `max_steps`)
+    pub steps: usize,
+    /// Maximum nesting depth of lists and dictionaries (counted against `max_depth`)
+    pub depth: usize,
+    /// Total length of all byte strings (counted against `max_str_bytes`)
+    pub str_bytes: usize,
+}
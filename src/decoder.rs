@@ -1,104 +1,1024 @@
-use super::Value;
+use super::path::Segment;
+use super::telemetry;
+use super::{
+    ContinueHook, Decodable, DecodeError, DecodeErrorKind, DecodeOptions, DecodeStats, DuplicateKeys, Kind, Path,
+    RejectHook, Rejection, Str, Value,
+};
 use std::borrow::Cow;
+use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
 
+pub(crate) const LIST_ITEM_SIZE: usize = std::mem::size_of::<Value>();
+pub(crate) const DICT_ENTRY_SIZE: usize = std::mem::size_of::<Str>() + std::mem::size_of::<Value>();
+// Digits needed for any i64 magnitude and u64 length, more can only be leading zeros
+const I64_DIGITS: usize = 19;
+const U64_DIGITS: usize = 20;
+
+/// A map dictionary entries are collected in (see [Decoder::insert_entry])
+pub(crate) trait DictMap<'k, V> {
+    /// Insert the entry if `key` is new or return it with the present value otherwise
+    fn insert_new(&mut self, key: Str<'k>, value: V) -> Option<(Str<'k>, &mut V, V)>;
+}
+
+impl<'k, V> DictMap<'k, V> for BTreeMap<Str<'k>, V> {
+    fn insert_new(&mut self, key: Str<'k>, value: V) -> Option<(Str<'k>, &mut V, V)> {
+        match self.entry(key) {
+            Entry::Vacant(e) => {
+                e.insert(value);
+                None
+            }
+            Entry::Occupied(e) => Some((e.key().clone(), e.into_mut(), value)),
+        }
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<'k, V> DictMap<'k, V> for indexmap::IndexMap<Str<'k>, V> {
+    fn insert_new(&mut self, key: Str<'k>, value: V) -> Option<(Str<'k>, &mut V, V)> {
+        match self.entry(key) {
+            indexmap::map::Entry::Vacant(e) => {
+                e.insert(value);
+                None
+            }
+            indexmap::map::Entry::Occupied(e) => Some((e.key().clone(), e.into_mut(), value)),
+        }
+    }
+}
+
+/// A position of a [Decoder] to return to (see [Decoder::save])
+#[derive(Clone, Copy, Debug)]
+pub struct Checkpoint<'a> {
+    buf: &'a [u8],
+    rest: &'a [&'a [u8]],
+    depth: usize,
+}
+
+/// A low-level decoder reading values from a buffer one by one
+///
+/// Besides building [Value]s, the decoder allows to walk lists and dictionaries item by item, so
+/// [Decodable] types can be decoded without building a [Value] tree first. After a failure, the
+/// input position is unspecified and the decoder should be discarded or [restored](Self::restore).
 pub struct Decoder<'a> {
     buf: &'a [u8],
-    rem_allocs: usize,
+    // Parts of chained input after `buf`, borrowed so that contiguous input needs no allocation
+    rest: &'a [&'a [u8]],
+    max_allocs: usize,
+    max_size: usize,
+    max_steps: usize,
+    max_depth: usize,
+    max_str_bytes: usize,
+    pub(crate) strict: bool,
+    pub(crate) duplicate_keys: DuplicateKeys,
+    // Keys seen twice and kept according to `duplicate_keys`
+    duplicates: Vec<Str<'a>>,
+    // Depth and last key of each open dictionary in strict mode, innermost last
+    last_keys: Vec<(usize, Option<Str<'a>>)>,
+    depth: usize,
+    stats: DecodeStats,
+    // Start and length of the input for rejection reports
+    head: &'a [u8],
+    len: usize,
+    on_reject: Option<RejectHook>,
+    should_continue: Option<ContinueHook>,
+    // Path segments collected innermost first while an error propagates
+    crumbs: Vec<Segment>,
+    // Cause of the last error that cannot be told from the error and input alone
+    kind: Option<DecodeErrorKind>,
 }
 
 impl<'a> Decoder<'a> {
+    /// Create a decoder limited to `max_allocs` allocations (see [Value::decode])
     pub fn new(buf: &'a [u8], max_allocs: usize) -> Self {
-        Self { buf, rem_allocs: max_allocs }
+        let opts = DecodeOptions { max_allocs, max_size_factor: None, ..Default::default() };
+        Self::with_options(buf, &opts)
+    }
+
+    /// Create a decoder with the given limits (see [Value::decode_with])
+    pub fn with_options(buf: &'a [u8], opts: &DecodeOptions) -> Self {
+        let mut d = Self::empty();
+        d.reset(buf, opts);
+        d
+    }
+
+    /// Decode from a sequence of slices as if they were concatenated
+    ///
+    /// Byte strings spanning multiple slices are copied, all others are borrowed.
+    pub fn chained(parts: &'a [&'a [u8]], opts: &DecodeOptions) -> Self {
+        let mut d = Self::empty();
+        d.reset_chained(parts, opts);
+        d
+    }
+
+    fn empty() -> Self {
+        Self {
+            buf: &[],
+            rest: &[],
+            max_allocs: 0,
+            max_size: 0,
+            max_steps: 0,
+            max_depth: 0,
+            max_str_bytes: 0,
+            strict: false,
+            duplicate_keys: DuplicateKeys::Reject,
+            duplicates: Vec::new(),
+            last_keys: Vec::new(),
+            depth: 0,
+            stats: DecodeStats::default(),
+            head: &[],
+            len: 0,
+            on_reject: None,
+            should_continue: None,
+            crumbs: Vec::new(),
+            kind: None,
+        }
+    }
+
+    /// Start over with new input and limits
+    ///
+    /// This allows to keep a single decoder (and its internal allocations) around in a hot loop
+    /// instead of creating a new one per message. It is fine to reset after a failure.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let opts = DecodeOptions::default();
+    /// let packets: &[&[u8]] = &[b"i1e", b"i2x", b"3:abc"];
+    /// let mut d = Decoder::with_options(&[], &opts);
+    /// let mut ok = 0;
+    /// for p in packets {
+    ///     d.reset(p, &opts);
+    ///     ok += d.take_value().is_ok() as usize;
+    /// }
+    /// assert_eq!(ok, 2);
+    /// ```
+    pub fn reset(&mut self, buf: &'a [u8], opts: &DecodeOptions) {
+        self.buf = buf;
+        self.rest = &[];
+        self.reset_limits(buf.len(), opts);
+    }
+
+    /// Like [Self::reset], but with a sequence of slices (see [Self::chained])
+    pub fn reset_chained(&mut self, parts: &'a [&'a [u8]], opts: &DecodeOptions) {
+        self.rest = parts;
+        self.buf = self.next_part();
+        self.reset_limits(parts.iter().map(|x| x.len()).sum(), opts);
+    }
+
+    fn reset_limits(&mut self, len: usize, opts: &DecodeOptions) {
+        self.max_allocs = opts.max_allocs;
+        self.max_size = opts.max_size_factor.map_or(usize::MAX, |k| k.saturating_mul(len));
+        self.max_steps = opts.max_steps;
+        self.max_depth = opts.max_depth;
+        self.max_str_bytes = opts.max_str_bytes;
+        self.strict = opts.strict;
+        self.duplicate_keys = opts.duplicate_keys;
+        self.duplicates.clear();
+        self.last_keys.clear();
+        self.depth = 0;
+        self.stats = DecodeStats::default();
+        self.head = self.buf;
+        self.len = len;
+        self.on_reject.clone_from(&opts.on_reject);
+        self.should_continue.clone_from(&opts.should_continue);
+        self.crumbs.clear();
+        self.kind = None;
+    }
+
+    pub fn stats(&self) -> DecodeStats {
+        self.stats
+    }
+
+    /// Continue with the budget and nesting of an earlier decoder that ran out of input
+    pub(crate) fn resume(&mut self, stats: DecodeStats, depth: usize, max_size: usize) {
+        self.stats = stats;
+        self.depth = depth;
+        self.max_size = max_size;
+    }
+
+    /// Dictionary keys that appeared more than once since the last reset, in input order
+    ///
+    /// Only duplicates allowed by [DecodeOptions::duplicate_keys] are recorded. Keys are not
+    /// qualified with the path of their dictionary.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let opts = DecodeOptions::default().with_duplicate_keys(DuplicateKeys::LastWins);
+    /// let mut d = Decoder::with_options(b"ld1:ai1e1:ai2eed1:bi1e1:bi2e1:bi3eee", &opts);
+    /// d.take_value().unwrap();
+    /// assert_eq!(d.duplicates(), [b"a".as_ref(), b"b", b"b"]);
+    /// ```
+    pub fn duplicates(&self) -> &[Str<'a>] {
+        &self.duplicates
+    }
+
+    /// Apply [DecodeOptions::duplicate_keys] to a `key` seen before in the current dictionary
+    ///
+    /// Returns whether the new value replaces the previous one. [Decodable] implementations
+    /// building maps should call this instead of rejecting duplicates themselves.
+    pub fn duplicate_key(&mut self, key: Str<'a>) -> Result<bool, DecodeError> {
+        if self.strict || self.duplicate_keys == DuplicateKeys::Reject {
+            let error = self.fail(DecodeError::Invalid, DecodeErrorKind::DuplicateKey);
+            return self.crumb(Err(error), || Segment::Key(key.to_vec()));
+        }
+        self.duplicates.push(key);
+        Ok(self.duplicate_keys == DuplicateKeys::LastWins)
+    }
+
+    /// Add an entry to a dictionary being built, applying [Self::duplicate_key] to a known key
+    ///
+    /// Returns whether `value` was stored.
+    pub(crate) fn insert_entry<'k: 'a, V>(
+        &mut self,
+        dict: &mut impl DictMap<'k, V>,
+        key: Str<'k>,
+        value: V,
+    ) -> Result<bool, DecodeError> {
+        let Some((key, present, value)) = dict.insert_new(key, value) else {
+            return Ok(true);
+        };
+        let keep = self.duplicate_key(key)?;
+        if keep {
+            *present = value;
+        }
+        Ok(keep)
+    }
+
+    /// Reject `key` for following a greater one in [strict](DecodeOptions::strict) mode
+    pub(crate) fn unsorted_key<T>(&mut self, key: &[u8]) -> Result<T, DecodeError> {
+        let error = self.fail(DecodeError::Invalid, DecodeErrorKind::NonCanonical);
+        self.crumb(Err(error), || Segment::Key(key.to_vec()))
+    }
+
+    /// The path of the innermost value being decoded when the last error occurred
+    ///
+    /// The path is recorded by lists and dictionaries decoded with [Self::take_value] and friends
+    /// while the error propagates, so successful decoding does not pay for it. [Decodable] types
+    /// walking containers themselves do not contribute segments.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let mut d = Decoder::with_options(b"d5:filesld6:lengthi1x", &DecodeOptions::default());
+    /// assert_eq!(d.take_value(), Err(DecodeError::Invalid));
+    /// assert_eq!(d.error_path().to_string(), "files[0].length");
+    /// ```
+    pub fn error_path(&self) -> Path {
+        let mut path = Path::new();
+        for s in self.crumbs.iter().rev() {
+            match s {
+                Segment::Key(k) => path.push_key(k),
+                Segment::Index(i) => path.push_index(*i),
+            }
+        }
+        path
     }
 
-    pub fn take_int(&mut self) -> Option<i64> {
+    /// The detailed cause of `error` which was just returned by this decoder
+    ///
+    /// Like the [error path](Self::error_path), this is only meaningful right after the failure.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let mut d = Decoder::with_options(b"d1:ai1e1:ai2ee", &DecodeOptions::default());
+    /// let e = d.take_value().unwrap_err();
+    /// assert_eq!((e, d.error_kind(e)), (DecodeError::Invalid, DecodeErrorKind::DuplicateKey));
+    ///
+    /// let mut d = Decoder::with_options(b"li1ex", &DecodeOptions::default());
+    /// let e = d.take_value().unwrap_err();
+    /// assert_eq!(d.error_kind(e), DecodeErrorKind::UnexpectedByte(b'x'));
+    /// ```
+    pub fn error_kind(&self, error: DecodeError) -> DecodeErrorKind {
+        use DecodeErrorKind::*;
+        match (error, self.kind) {
+            (DecodeError::Incomplete { .. }, _) => Truncated,
+            (DecodeError::LengthTooLarge, _) => LengthTooLarge,
+            (DecodeError::Cancelled, _) => Cancelled,
+            (DecodeError::Invalid, Some(k @ (DuplicateKey | IntegerOverflow | NonCanonical))) => k,
+            (DecodeError::LimitExceeded, Some(k @ (AllocLimit | SizeLimit | StepLimit | DepthLimit | StrLimit))) => k,
+            // Decoding stops in front of a byte it does not expect
+            (DecodeError::Invalid, _) => self.buf.first().map_or(Other, |b| UnexpectedByte(*b)),
+            (DecodeError::LimitExceeded, _) => Other,
+        }
+    }
+
+    /// Remember `kind` as the cause of the returned `error`
+    fn fail(&mut self, error: DecodeError, kind: DecodeErrorKind) -> DecodeError {
+        self.kind = Some(kind);
+        error
+    }
+
+    /// Record `segment` as part of the error path if `r` is an error
+    fn crumb<T>(&mut self, r: Result<T, DecodeError>, segment: impl FnOnce() -> Segment) -> Result<T, DecodeError> {
+        if r.is_err() {
+            self.crumbs.push(segment());
+        }
+        r
+    }
+
+    /// Decode the next value as `T` (see [Decodable])
+    pub fn decode<T: Decodable<'a>>(&mut self) -> Result<T, DecodeError> {
+        T::decode(self)
+    }
+
+    /// Consume the start of a list
+    ///
+    /// Call [Self::next_item] before each item which also consumes the end of the list.
+    pub fn begin_list(&mut self) -> Result<(), DecodeError> {
+        self.step()?;
+        self.take_u8_eq(b'l')?;
+        self.enter()?;
+        Ok(())
+    }
+
+    /// Consume the start of a dictionary
+    ///
+    /// Call [Self::next_key] before each value which also consumes the end of the dictionary.
+    pub fn begin_dict(&mut self) -> Result<(), DecodeError> {
+        self.step()?;
+        self.take_u8_eq(b'd')?;
+        self.enter()?;
+        if self.strict {
+            self.last_keys.push((self.depth, None));
+        }
+        Ok(())
+    }
+
+    /// Return whether another list item follows or consume the end of the list otherwise
+    pub fn next_item(&mut self) -> Result<bool, DecodeError> {
+        if self.peek_u8()? == b'e' {
+            self.take_u8_eq(b'e')?;
+            self.leave();
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Return the key of the next dictionary entry or consume the end of the dictionary otherwise
+    ///
+    /// The caller must decode or skip the entry's value before calling this again. Keys are
+    /// returned in input order. In [strict](DecodeOptions::strict) mode, a key following a greater
+    /// one is rejected, but duplicates are only detected by [Self::duplicate_key].
+    pub fn next_key(&mut self) -> Result<Option<Cow<'a, [u8]>>, DecodeError> {
+        let depth = self.depth;
+        if self.peek_u8()? == b'e' {
+            self.take_u8_eq(b'e')?;
+            if self.last_keys.last().is_some_and(|(d, _)| *d == depth) {
+                self.last_keys.pop();
+            }
+            self.leave();
+            return Ok(None);
+        }
+        let key = self.read_str()?;
+        if let Some(last) = self.last_keys.last_mut().filter(|(d, _)| *d == depth).map(|(_, last)| last) {
+            if last.as_ref().is_some_and(|last| *last > key) {
+                return self.unsorted_key(&key);
+            }
+            *last = Some(key.clone());
+        }
+        Ok(Some(key))
+    }
+
+    pub fn take_int(&mut self) -> Result<i64, DecodeError> {
+        self.step()?;
+        self.read_int()
+    }
+
+    /// Like [Self::take_int], but without counting a step
+    fn read_int(&mut self) -> Result<i64, DecodeError> {
         self.take_u8_eq(b'i')?;
         let i = self.take_i64()?;
         self.take_u8_eq(b'e')?;
-        Some(i)
+        Ok(i)
     }
 
-    pub fn take_list(&mut self) -> Option<Vec<Value<'a>>> {
-        self.take_u8_eq(b'l')?;
+    pub fn take_list(&mut self) -> Result<Vec<Value<'a>>, DecodeError> {
         let mut list = Vec::new();
-        while self.buf.get(0)? != &b'e' {
-            self.alloc(1)?;
-            list.push(self.take_value()?);
+        self.take_list_into(&mut list)?;
+        Ok(list)
+    }
+
+    /// Like [Self::take_list], but decode into `list` reusing its capacity and items
+    ///
+    /// Existing items are overwritten with [Self::take_value_into] and surplus items are dropped.
+    pub fn take_list_into(&mut self, list: &mut Vec<Value<'a>>) -> Result<(), DecodeError> {
+        self.begin_list()?;
+        let mut n = 0;
+        while self.peek_u8()? != b'e' {
+            self.alloc(LIST_ITEM_SIZE)?;
+            let r = match list.get_mut(n) {
+                Some(v) => self.take_value_into(v),
+                None => self.take_value().map(|v| list.push(v)),
+            };
+            self.crumb(r, || Segment::Index(n))?;
+            n += 1;
         }
+        list.truncate(n);
         self.take_u8_eq(b'e')?;
-        Some(list)
+        self.leave();
+        Ok(())
+    }
+
+    pub fn take_str(&mut self) -> Result<Cow<'a, [u8]>, DecodeError> {
+        self.step()?;
+        self.read_str()
     }
 
-    pub fn take_str(&mut self) -> Option<Cow<'a, [u8]>> {
+    /// Like [Self::take_str], but without counting a step as used for dictionary keys
+    fn read_str(&mut self) -> Result<Cow<'a, [u8]>, DecodeError> {
         let len = self.take_usize()?;
         self.take_u8_eq(b':')?;
-        self.take_u8_slice(len).map(Cow::Borrowed)
+        let Some(str_bytes) = self.stats.str_bytes.checked_add(len).filter(|x| *x <= self.max_str_bytes) else {
+            return Err(self.fail(DecodeError::LimitExceeded, DecodeErrorKind::StrLimit));
+        };
+        self.stats.str_bytes = str_bytes;
+        self.take_bytes(len)
     }
 
-    pub fn take_dict(&mut self) -> Option<BTreeMap<Cow<'a, [u8]>, Value<'a>>> {
-        self.take_u8_eq(b'd')?;
-        let mut dict = BTreeMap::new();
-        while let Some(key) = self.take_str() {
-            self.alloc(1)?;
-            let value = self.take_value()?;
-            if dict.insert(key, value).is_some() {
-                return None; // Duplicate keys are forbidden
-            }
+    pub fn take_dict(&mut self) -> Result<BTreeMap<Cow<'a, [u8]>, Value<'a>>, DecodeError> {
+        self.take_dict_with(|d, _| d.take_value())
+    }
+
+    /// Like [Self::take_dict], but decode into `dict` reusing the values of keys seen before
+    ///
+    /// The entries are replaced, but values for keys that are present again are decoded with
+    /// [Self::take_value_into], keeping the capacity of nested lists.
+    pub fn take_dict_into(&mut self, dict: &mut BTreeMap<Cow<'a, [u8]>, Value<'a>>) -> Result<(), DecodeError> {
+        let mut old = std::mem::take(dict);
+        *dict = self.take_dict_with(|d, key| match old.remove(key) {
+            Some(mut v) => d.take_value_into(&mut v).map(|_| v),
+            None => d.take_value(),
+        })?;
+        Ok(())
+    }
+
+    fn take_dict_with(
+        &mut self,
+        mut take_value: impl FnMut(&mut Self, &Str<'a>) -> Result<Value<'a>, DecodeError>,
+    ) -> Result<BTreeMap<Cow<'a, [u8]>, Value<'a>>, DecodeError> {
+        self.begin_dict()?;
+        // Canonical input has ascending keys which are collected and bulk-inserted at the end.
+        // Only unordered input pays for inserting entry by entry.
+        let mut sorted: Vec<(Str<'a>, Value<'a>)> = Vec::new();
+        let mut dict: Option<BTreeMap<_, _>> = None;
+        while let Some(key) = self.next_key()? {
+            self.alloc(DICT_ENTRY_SIZE)?;
+            let value = take_value(self, &key);
+            let value = self.crumb(value, || Segment::Key(key.to_vec()))?;
+            let dict = match &mut dict {
+                Some(d) => d,
+                None => match sorted.last() {
+                    Some((last, _)) if *last >= key => dict.insert(sorted.drain(..).collect()),
+                    _ => {
+                        sorted.push((key, value));
+                        continue;
+                    }
+                },
+            };
+            self.insert_entry(dict, key, value)?;
         }
-        self.take_u8_eq(b'e')?;
-        Some(dict)
+        Ok(dict.unwrap_or_else(|| sorted.into_iter().collect()))
+    }
+
+    pub fn take_value(&mut self) -> Result<Value<'a>, DecodeError> {
+        let top = self.depth == 0;
+        let r = self.take_any();
+        if top {
+            self.finish(r.as_ref().map(Value::kind).map_err(|e| *e));
+        }
+        r
+    }
+
+    /// Like [Self::take_value], but replace byte strings longer than `threshold` by what `sink`
+    /// returns for them as soon as they are decoded (see [Value::decode_with_sink])
+    pub(crate) fn take_value_with_sink(
+        &mut self,
+        threshold: usize,
+        sink: &mut dyn FnMut(&[u8]) -> Value<'a>,
+    ) -> Result<Value<'a>, DecodeError> {
+        let top = self.depth == 0;
+        let r = self.take_any_with_sink(threshold, sink);
+        if top {
+            self.finish(r.as_ref().map(Value::kind).map_err(|e| *e));
+        }
+        r
     }
 
-    pub fn take_value(&mut self) -> Option<Value<'a>> {
-        match self.buf.get(0)? {
+    fn take_any_with_sink(
+        &mut self,
+        threshold: usize,
+        sink: &mut dyn FnMut(&[u8]) -> Value<'a>,
+    ) -> Result<Value<'a>, DecodeError> {
+        match self.peek_u8()? {
             b'i' => self.take_int().map(Value::Int),
-            b'l' => self.take_list().map(Value::List),
-            b'd' => self.take_dict().map(Value::Dict),
-            b'0'..=b'9' => self.take_str().map(Value::Str),
-            _ => None,
+            b'0'..=b'9' => {
+                let s = self.take_str()?;
+                Ok(if s.len() > threshold { sink(&s) } else { Value::Str(s) })
+            }
+            b'l' => {
+                self.begin_list()?;
+                let mut list = Vec::new();
+                while self.peek_u8()? != b'e' {
+                    self.alloc(LIST_ITEM_SIZE)?;
+                    let (n, r) = (list.len(), self.take_any_with_sink(threshold, sink));
+                    list.push(self.crumb(r, || Segment::Index(n))?);
+                }
+                self.take_u8_eq(b'e')?;
+                self.leave();
+                Ok(Value::List(list))
+            }
+            b'd' => self.take_dict_with(|d, _| d.take_any_with_sink(threshold, sink)).map(Value::Dict),
+            _ => Err(DecodeError::Invalid),
+        }
+    }
+
+    /// Like [Self::take_value], but decode into `v` reusing the capacity of its lists
+    ///
+    /// Lists and dictionaries are decoded into the existing ones if the kind matches (see
+    /// [Self::take_list_into] and [Self::take_dict_into]). `v` is left in an unspecified state
+    /// on error.
+    pub fn take_value_into(&mut self, v: &mut Value<'a>) -> Result<(), DecodeError> {
+        let top = self.depth == 0;
+        let r = self.take_any_into(v);
+        if top {
+            self.finish(r.map(|_| v.kind()));
+        }
+        r
+    }
+
+    /// Report the result of decoding a top-level value
+    pub(crate) fn finish(&self, r: Result<Kind, DecodeError>) {
+        telemetry::record_decode(r);
+        if let (Some(hook), Err(error)) = (&self.on_reject, r) {
+            let prefix = &self.head[..self.head.len().min(Rejection::MAX_PREFIX_LEN)];
+            hook.call(&Rejection { offset: self.len - self.remaining(), error, prefix });
+        }
+    }
+
+    pub(crate) fn take_any(&mut self) -> Result<Value<'a>, DecodeError> {
+        self.take_any_with_sink(usize::MAX, &mut |_| unreachable!("no string is longer than usize::MAX"))
+    }
+
+    fn take_any_into(&mut self, v: &mut Value<'a>) -> Result<(), DecodeError> {
+        match (self.peek_u8()?, &mut *v) {
+            (b'l', Value::List(l)) => self.take_list_into(l),
+            (b'd', Value::Dict(d)) => self.take_dict_into(d),
+            (b'i', _) => self.take_int().map(|i| *v = Value::Int(i)),
+            (b'l', _) => self.take_list().map(|l| *v = Value::List(l)),
+            (b'd', _) => self.take_dict().map(|d| *v = Value::Dict(d)),
+            (b'0'..=b'9', _) => self.take_str().map(|s| *v = Value::Str(s)),
+            _ => Err(DecodeError::Invalid),
+        }
+    }
+
+    /// Skip over the next value without building it
+    ///
+    /// The syntax is validated like in [Self::take_value], but nothing is allocated. Duplicate
+    /// and (in [strict](DecodeOptions::strict) mode) unsorted dictionary keys are therefor not
+    /// detected and the alloc and size limits do not apply.
+    pub fn skip_value(&mut self) -> Result<(), DecodeError> {
+        self.step()?;
+        match self.peek_u8()? {
+            b'i' => self.read_int().map(|_| ()),
+            b'l' => {
+                self.take_u8_eq(b'l')?;
+                self.enter()?;
+                while self.peek_u8()? != b'e' {
+                    self.skip_value()?;
+                }
+                self.take_u8_eq(b'e')?;
+                self.leave();
+                Ok(())
+            }
+            b'd' => {
+                self.take_u8_eq(b'd')?;
+                self.enter()?;
+                while self.peek_u8()? != b'e' {
+                    self.skip_str()?;
+                    self.skip_value()?;
+                }
+                self.take_u8_eq(b'e')?;
+                self.leave();
+                Ok(())
+            }
+            b'0'..=b'9' => self.skip_str(),
+            _ => Err(DecodeError::Invalid),
+        }
+    }
+
+    pub fn skip_str(&mut self) -> Result<(), DecodeError> {
+        let len = self.take_usize()?;
+        self.take_u8_eq(b':')?;
+        self.skip_bytes(len)
+    }
+
+    pub fn skip_bytes(&mut self, n: usize) -> Result<(), DecodeError> {
+        if let Some(needed) = n.checked_sub(self.remaining()).filter(|x| *x > 0) {
+            return Err(DecodeError::Incomplete { needed });
+        }
+        let mut n = n;
+        while n > 0 {
+            let (h, t) = self.buf.split_at(self.buf.len().min(n));
+            n -= h.len();
+            self.advance(t);
         }
+        Ok(())
+    }
+
+    pub fn peek_u8(&self) -> Result<u8, DecodeError> {
+        self.buf.first().copied().ok_or(DecodeError::Incomplete { needed: 1 })
+    }
+
+    pub fn take_u8_eq(&mut self, c: u8) -> Result<(), DecodeError> {
+        self.take_u8_if(|x| *x == c).map(|_| ())
     }
 
-    pub fn take_u8_eq(&mut self, c: u8) -> Option<()> {
-        let (_, t) = self.buf.split_first().filter(|x| x.0 == &c)?;
-        self.buf = t;
-        Some(())
+    pub fn take_u8_if(&mut self, f: impl FnOnce(&u8) -> bool) -> Result<u8, DecodeError> {
+        let (h, t) = self.buf.split_first().ok_or(DecodeError::Incomplete { needed: 1 })?;
+        if !f(h) {
+            return Err(DecodeError::Invalid);
+        }
+        self.advance(t);
+        Ok(*h)
     }
 
-    pub fn take_u8_if(&mut self, f: impl FnOnce(&u8) -> bool) -> Option<u8> {
-        let (h, t) = self.buf.split_first().filter(|x| f(x.0))?;
-        self.buf = t;
-        Some(*h)
+    /// Take `n` bytes from the input
+    ///
+    /// Fails with [DecodeError::Invalid] if the bytes span multiple input slices (see [Self::take_bytes]).
+    pub fn take_u8_slice(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        let Some((h, t)) = self.buf.split_at_checked(n) else {
+            return Err(match n.checked_sub(self.remaining()) {
+                Some(needed) if needed > 0 => DecodeError::Incomplete { needed },
+                _ => DecodeError::Invalid,
+            });
+        };
+        self.advance(t);
+        Ok(h)
     }
 
-    pub fn take_u8_slice(&mut self, n: usize) -> Option<&'a [u8]> {
-        let (h, t) = self.buf.split_at_checked(n)?;
-        self.buf = t;
-        Some(h)
+    /// Like [Self::take_u8_slice], but copy the bytes if they span multiple input slices
+    pub fn take_bytes(&mut self, n: usize) -> Result<Cow<'a, [u8]>, DecodeError> {
+        if self.buf.len() >= n {
+            return self.take_u8_slice(n).map(Cow::Borrowed);
+        }
+        if let Some(needed) = n.checked_sub(self.remaining()).filter(|x| *x > 0) {
+            return Err(DecodeError::Incomplete { needed });
+        }
+        let Some(size) = self.stats.size.checked_add(n).filter(|x| *x <= self.max_size) else {
+            return Err(self.fail(DecodeError::LimitExceeded, DecodeErrorKind::SizeLimit));
+        };
+        self.stats.size = size;
+        let mut v = Vec::with_capacity(n);
+        while v.len() < n {
+            let (h, t) = self.buf.split_at(self.buf.len().min(n - v.len()));
+            v.extend_from_slice(h);
+            self.advance(t);
+        }
+        Ok(Cow::Owned(v))
     }
 
-    pub fn take_i64(&mut self) -> Option<i64> {
+    pub fn take_i64(&mut self) -> Result<i64, DecodeError> {
         let s = self.take_u8_eq(b'-');
-        let mut r: i64 = (self.take_u8_if(u8::is_ascii_digit)? - b'0').into();
-        while let Some(x) = self.take_u8_if(u8::is_ascii_digit) {
-            r = r.checked_mul(10)?;
-            r = r.checked_add((x - b'0').into())?;
+        let first = self.take_u8_if(u8::is_ascii_digit)?;
+        if self.strict && first == b'0' && (s.is_ok() || self.peek_u8().is_ok_and(|x| x.is_ascii_digit())) {
+            return Err(self.fail(DecodeError::Invalid, DecodeErrorKind::NonCanonical));
+        }
+        let mut r: i64 = (first - b'0').into();
+        let mut digits = 1;
+        while let Ok(x) = self.take_u8_if(u8::is_ascii_digit) {
+            digits += 1;
+            if digits > I64_DIGITS {
+                self.step()?;
+            }
+            let Some(next) = r.checked_mul(10).and_then(|r| r.checked_add((x - b'0').into())) else {
+                return Err(self.fail(DecodeError::Invalid, DecodeErrorKind::IntegerOverflow));
+            };
+            r = next;
+        }
+        Ok(if s.is_ok() { -r } else { r })
+    }
+
+    /// Take a string length
+    ///
+    /// The digits are parsed as [u64] on all platforms. Lengths beyond [isize::MAX] (the maximum
+    /// size of any slice) fail with [DecodeError::LengthTooLarge] as no input could satisfy them.
+    pub fn take_usize(&mut self) -> Result<usize, DecodeError> {
+        let first = self.take_u8_if(u8::is_ascii_digit)?;
+        if self.strict && first == b'0' && self.peek_u8().is_ok_and(|x| x.is_ascii_digit()) {
+            return Err(self.fail(DecodeError::Invalid, DecodeErrorKind::NonCanonical));
+        }
+        let mut r: u64 = (first - b'0').into();
+        let mut digits = 1;
+        while let Ok(x) = self.take_u8_if(u8::is_ascii_digit) {
+            digits += 1;
+            if digits > U64_DIGITS {
+                self.step()?;
+            }
+            r = r.checked_mul(10).ok_or(DecodeError::LengthTooLarge)?;
+            r = r.checked_add((x - b'0').into()).ok_or(DecodeError::LengthTooLarge)?;
+        }
+        usize::try_from(r).ok().filter(|x| isize::try_from(*x).is_ok()).ok_or(DecodeError::LengthTooLarge)
+    }
+
+    /// Number of bytes left in the input
+    pub fn remaining(&self) -> usize {
+        self.buf.len() + self.rest.iter().map(|x| x.len()).sum::<usize>()
+    }
+
+    /// Remember the current position to go back to it with [Self::restore]
+    ///
+    /// This allows speculative decoding, like trying whether a field is a string and falling back
+    /// to a list of strings otherwise.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// fn strings<'a>(d: &mut Decoder<'a>) -> Result<Vec<Value<'a>>, DecodeError> {
+    ///     let c = d.save();
+    ///     match d.take_str() {
+    ///         Ok(s) => Ok(vec![Value::Str(s)]),
+    ///         Err(_) => {
+    ///             d.restore(c);
+    ///             d.take_list()
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let opts = DecodeOptions::default();
+    /// assert_eq!(strings(&mut Decoder::with_options(b"1:a", &opts)), Ok(vec![str!("a")]));
+    /// assert_eq!(strings(&mut Decoder::with_options(b"l1:a1:be", &opts)), Ok(vec![str!("a"), str!("b")]));
+    /// ```
+    pub fn save(&self) -> Checkpoint<'a> {
+        Checkpoint { buf: self.buf, rest: self.rest, depth: self.depth }
+    }
+
+    /// Go back to a position remembered with [Self::save]
+    ///
+    /// The checkpoint must have been taken from this decoder since the last reset. The input is
+    /// rewound, but the limits are not: steps and allocations of the abandoned attempt still count,
+    /// so backtracking cannot be used to exceed the configured [DecodeOptions]. It is fine to
+    /// restore after a failure.
+    pub fn restore(&mut self, checkpoint: Checkpoint<'a>) {
+        self.buf = checkpoint.buf;
+        self.rest = checkpoint.rest;
+        self.depth = checkpoint.depth;
+        while self.last_keys.last().is_some_and(|(depth, _)| *depth > checkpoint.depth) {
+            self.last_keys.pop();
+        }
+        self.crumbs.clear();
+        self.kind = None;
+    }
+
+    fn next_part(&mut self) -> &'a [u8] {
+        while let Some((part, rest)) = self.rest.split_first() {
+            self.rest = rest;
+            if !part.is_empty() {
+                return part;
+            }
+        }
+        &[]
+    }
+
+    fn advance(&mut self, rem: &'a [u8]) {
+        self.buf = rem;
+        if self.buf.is_empty() {
+            self.buf = self.next_part();
+        }
+    }
+
+    pub(crate) fn step(&mut self) -> Result<(), DecodeError> {
+        let Some(steps) = self.stats.steps.checked_add(1).filter(|x| *x <= self.max_steps) else {
+            return Err(self.fail(DecodeError::LimitExceeded, DecodeErrorKind::StepLimit));
+        };
+        self.stats.steps = steps;
+        if self.stats.steps.is_multiple_of(DecodeOptions::CANCEL_CHECK_INTERVAL) {
+            if let Some(hook) = &self.should_continue {
+                if !hook.call() {
+                    return Err(DecodeError::Cancelled);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Account for an allocation of `size` bytes against the limits
+    ///
+    /// [Decodable] implementations that allocate per item should call this to stay within the
+    /// configured [DecodeOptions].
+    pub fn alloc(&mut self, size: usize) -> Result<(), DecodeError> {
+        let allocs = self.stats.allocs.checked_add(1).filter(|x| *x <= self.max_allocs);
+        let size = self.stats.size.checked_add(size).filter(|x| *x <= self.max_size);
+        let (Some(allocs), Some(size)) = (allocs, size) else {
+            let kind = if allocs.is_none() { DecodeErrorKind::AllocLimit } else { DecodeErrorKind::SizeLimit };
+            return Err(self.fail(DecodeError::LimitExceeded, kind));
+        };
+        self.stats.allocs = allocs;
+        self.stats.size = size;
+        Ok(())
+    }
+
+    fn enter(&mut self) -> Result<(), DecodeError> {
+        if self.depth >= self.max_depth {
+            return Err(self.fail(DecodeError::LimitExceeded, DecodeErrorKind::DepthLimit));
+        }
+        self.depth += 1;
+        self.stats.depth = self.stats.depth.max(self.depth);
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc_count::count_allocs;
+
+    #[test]
+    fn test_no_allocations() {
+        let opts = DecodeOptions::default();
+        assert_eq!(count_allocs(|| Value::decode(b"i1e", 10)), 0);
+        assert_eq!(count_allocs(|| Value::decode_with(b"4:spam", &opts)), 0);
+        assert_eq!(count_allocs(|| Decoder::with_options(b"li1ee", &opts).skip_value()), 0);
+        assert_eq!(count_allocs(|| Value::decode_chained(&[b"4:sp", b"am"], &opts)), 1);
+    }
+
+    #[test]
+    fn test_restore_chained() {
+        let opts = DecodeOptions::default();
+        let mut d = Decoder::chained(&[b"li1e", b"", b"3:a", b"bci2ee"], &opts);
+        d.begin_list().unwrap();
+        assert_eq!(d.take_int(), Ok(1));
+        let c = d.save();
+        assert_eq!(d.take_int(), Err(DecodeError::Invalid));
+        d.restore(c);
+        assert_eq!(d.remaining(), 9);
+        assert_eq!(d.take_str().as_deref(), Ok(b"abc".as_ref()));
+        let c2 = d.save();
+        assert_eq!(d.take_int(), Ok(2));
+        assert!(!d.next_item().unwrap());
+        assert_eq!(d.remaining(), 0);
+        d.restore(c2);
+        assert_eq!(d.remaining(), 4);
+        d.restore(c);
+        assert_eq!(d.take_value(), Ok(Value::Str(Cow::Borrowed(b"abc"))));
+        // Every token read counts, including those of the abandoned attempts
+        assert_eq!(d.stats().steps, 6);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let opts = DecodeOptions::default().with_max_depth(3);
+        let mut d = Decoder::with_options(b"lllleeee", &opts);
+        assert_eq!(d.skip_value(), Err(DecodeError::LimitExceeded));
+        d.reset(b"ld1:alleee", &opts);
+        d.begin_list().unwrap();
+        assert_eq!(d.take_value(), Err(DecodeError::LimitExceeded));
+        assert_eq!(d.error_path().to_string(), "a[0]");
+        d.reset(b"ld1:aleee", &opts);
+        assert!(d.take_value().is_ok());
+        assert_eq!(d.stats().depth, 3);
+    }
+
+    #[test]
+    fn test_leading_zeros() {
+        let zeros = |n: usize| b"0".repeat(n);
+        let opts = DecodeOptions::default().with_max_steps(2);
+        for buf in [
+            [b"i".as_ref(), &zeros(18), b"1e"].concat(),
+            [b"i-".as_ref(), &zeros(19), b"e"].concat(),
+            [b"l".as_ref(), &zeros(19), b"1:ae"].concat(),
+        ] {
+            assert!(Decoder::with_options(&buf, &opts).take_value().is_ok());
+        }
+        for buf in [
+            [b"i".as_ref(), &zeros(1 << 20), b"1e"].concat(),
+            [b"i-".as_ref(), &zeros(21), b"e"].concat(),
+            [b"l".as_ref(), &zeros(1 << 20), b"1:ae"].concat(),
+        ] {
+            let mut d = Decoder::with_options(&buf, &opts);
+            let e = d.take_value().unwrap_err();
+            assert_eq!(d.error_kind(e), DecodeErrorKind::StepLimit);
+            // Decoding stops right after the steps run out
+            assert!(buf.len() - d.remaining() < 32);
         }
-        s.map(|_| -r).or(Some(r))
     }
 
-    pub fn take_usize(&mut self) -> Option<usize> {
-        let mut r: usize = (self.take_u8_if(u8::is_ascii_digit)? - b'0').into();
-        while let Some(x) = self.take_u8_if(u8::is_ascii_digit) {
-            r = r.checked_mul(10)?;
-            r = r.checked_add((x - b'0').into())?;
+    #[test]
+    fn test_strict() {
+        let opts = DecodeOptions::default().with_strict(true);
+        for (buf, path) in [
+            (b"i00e".as_ref(), ""),
+            (b"i-0e", ""),
+            (b"i-01e", ""),
+            (b"l00:e", "[0]"),
+            (b"d01:ai1ee", ""),
+            (b"d1:ad1:ci0e1:bi0eee", "a.b"),
+            (b"d1:bi0e1:ai0ee", "a"),
+        ] {
+            let mut d = Decoder::with_options(buf, &opts);
+            let e = d.take_value().unwrap_err();
+            assert_eq!((e, d.error_kind(e)), (DecodeError::Invalid, DecodeErrorKind::NonCanonical), "{:?}", buf);
+            assert_eq!(d.error_path().to_string(), path);
+            d.reset(buf, &DecodeOptions::default());
+            assert!(d.take_value().is_ok());
+        }
+
+        // Duplicates are still reported as such and canonical input is unaffected
+        let mut d = Decoder::with_options(b"d1:ai0e1:ai0ee", &opts);
+        let e = d.take_value().unwrap_err();
+        assert_eq!(d.error_kind(e), DecodeErrorKind::DuplicateKey);
+        for buf in [b"i0e".as_ref(), b"i-10e", b"i100e", b"0:", b"10:0123456789", b"d0:i0e1:ai0e2:aai0ee"] {
+            d.reset(buf, &opts);
+            let v = d.take_value().unwrap();
+            assert_eq!(v.encode(), buf);
         }
-        Some(r)
     }
 
-    fn alloc(&mut self, n: usize) -> Option<()> {
-        self.rem_allocs = self.rem_allocs.checked_sub(n)?;
-        Some(())
+    #[test]
+    fn test_duplicate_keys() {
+        let buf = b"d1:bi1e1:ad1:xi1e1:xi2ee1:bi3ee";
+        let mut d = Decoder::with_options(buf, &DecodeOptions::default());
+        let e = d.take_value().unwrap_err();
+        assert_eq!((d.error_kind(e), d.error_path().to_string()), (DecodeErrorKind::DuplicateKey, "a.x".into()));
+
+        for (policy, expected) in
+            [(DuplicateKeys::FirstWins, b"d1:ad1:xi1ee1:bi1ee"), (DuplicateKeys::LastWins, b"d1:ad1:xi2ee1:bi3ee")]
+        {
+            let opts = DecodeOptions::default().with_duplicate_keys(policy);
+            d.reset(buf, &opts);
+            assert_eq!(d.take_value().unwrap().encode(), expected);
+            assert_eq!(d.duplicates(), [b"x".as_ref(), b"b"]);
+            let mut v = Value::decode(b"d1:ali1eee", 10).unwrap();
+            d.reset(buf, &opts);
+            d.take_value_into(&mut v).unwrap();
+            assert_eq!(v.encode(), expected);
+
+            d.reset(b"d1:ai1e1:ai2ee", &opts);
+            let m: BTreeMap<Cow<[u8]>, i64> = d.decode().unwrap();
+            assert_eq!(m[b"a".as_ref()], if policy == DuplicateKeys::FirstWins { 1 } else { 2 });
+            let l = crate::LazyDict::decode(b"d1:ai1e1:ai2ee", &opts).unwrap();
+            assert_eq!(l.get_as::<i64>(b"a"), Ok(Some(m[b"a".as_ref()])));
+
+            // Strict mode rejects duplicates regardless
+            d.reset(b"d1:ai1e1:ai2ee", &opts.with_strict(true));
+            let e = d.take_value().unwrap_err();
+            assert_eq!(d.error_kind(e), DecodeErrorKind::DuplicateKey);
+            assert!(d.duplicates().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_hostile_nesting() {
+        let opts = DecodeOptions::default();
+        let buf = b"l".repeat(1 << 20);
+        let mut d = Decoder::with_options(&buf, &opts);
+        let e = d.take_value().unwrap_err();
+        assert_eq!((e, d.error_kind(e)), (DecodeError::LimitExceeded, DecodeErrorKind::DepthLimit));
+        d.reset(&buf, &opts);
+        assert_eq!(d.skip_value(), Err(DecodeError::LimitExceeded));
+
+        // The deepest value allowed by default can be decoded, encoded and dropped on a small stack
+        let n = DecodeOptions::DEFAULT_MAX_DEPTH;
+        let buf = [b"l".repeat(n), b"e".repeat(n)].concat();
+        let t = std::thread::Builder::new().stack_size(1 << 20).spawn(move || {
+            let v = Value::decode_with(&buf, &opts).unwrap();
+            assert_eq!(v.encode(), buf);
+        });
+        t.unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn test_reject_hook_front_ends() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let opts = DecodeOptions::default().with_on_reject(RejectHook::new(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }));
+        type Decode = fn(&[u8], &DecodeOptions) -> bool;
+        let decoders: &[(&str, Decode)] = &[
+            ("Value", |b, o| Value::decode_with(b, o).is_ok()),
+            ("Verbatim", |b, o| crate::Verbatim::decode(b, o).is_ok()),
+            ("LazyDict", |b, o| crate::LazyDict::decode(b, o).is_ok()),
+            ("spans", |b, o| Value::decode_with_spans(b, o).is_ok()),
+            ("Tokens", |b, o| crate::Tokens::new(b, o).all(|t| t.is_ok())),
+            #[cfg(feature = "indexmap")]
+            ("OrderedValue", |b, o| crate::OrderedValue::decode(b, o).is_ok()),
+        ];
+        for (name, decode) in decoders {
+            for (buf, ok) in
+                [(b"d1:ai1ee".as_ref(), true), (b"d1:ai1x", false), (b"i1", false), (b"d1:bi1e1:ai1ee", true)]
+            {
+                calls.store(0, Ordering::Relaxed);
+                assert_eq!(decode(buf, &opts), ok, "{name} {buf:?}");
+                assert_eq!(calls.load(Ordering::Relaxed), usize::from(!ok), "{name} {buf:?}");
+            }
+        }
     }
 }
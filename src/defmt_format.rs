@@ -0,0 +1,60 @@
+use super::path::{is_plain, QuotedKey};
+use super::{Path, Segment, Value};
+use defmt::{write, Display2Format, Format, Formatter};
+
+/// Like [Debug](std::fmt::Debug): strings are shown as text if valid UTF-8 and as hex otherwise
+impl Format for Value<'_> {
+    fn format(&self, f: Formatter<'_>) {
+        match self {
+            Value::Int(i) => write!(f, "{=i64}", i),
+            Value::Str(s) => match std::str::from_utf8(s) {
+                Ok(s) => write!(f, "{=str:?}", s),
+                Err(_) => write!(f, "{=[u8]:x}", s.as_ref()),
+            },
+            Value::Raw(s) => write!(f, "raw {=[u8]:x}", s.as_ref()),
+            Value::List(l) => {
+                write!(f, "[");
+                for (i, v) in l.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ");
+                    }
+                    write!(f, "{}", v);
+                }
+                write!(f, "]");
+            }
+            Value::Dict(d) => {
+                write!(f, "{{");
+                for (i, (k, v)) in d.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ");
+                    }
+                    match std::str::from_utf8(k) {
+                        Ok(k) => write!(f, "{=str:?}: {}", k, v),
+                        Err(_) => write!(f, "{=[u8]:x}: {}", k.as_ref(), v),
+                    }
+                }
+                write!(f, "}}");
+            }
+        }
+    }
+}
+
+/// Like [Display](std::fmt::Display)
+impl Format for Path {
+    fn format(&self, f: Formatter<'_>) {
+        for (i, s) in self.segments().iter().enumerate() {
+            match s {
+                Segment::Index(n) => write!(f, "[{=usize}]", n),
+                Segment::Key(k) => {
+                    if i > 0 {
+                        write!(f, ".");
+                    }
+                    match std::str::from_utf8(k) {
+                        Ok(s) if is_plain(s) => write!(f, "{=str}", s),
+                        _ => write!(f, "{}", Display2Format(&QuotedKey(k))),
+                    }
+                }
+            }
+        }
+    }
+}
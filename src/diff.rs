@@ -0,0 +1,224 @@
+use super::{Path, Value};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt::Write;
+
+/// A single difference between two [Value]s as reported by [diff]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Difference<'v, 'a> {
+    /// The value only exists on the left side
+    Removed(Path, &'v Value<'a>),
+    /// The value only exists on the right side
+    Added(Path, &'v Value<'a>),
+    /// Both sides have a value at this path, but they are not equal
+    Changed(Path, &'v Value<'a>, &'v Value<'a>),
+}
+
+impl Difference<'_, '_> {
+    /// The location of the difference
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Removed(p, _) => p,
+            Self::Added(p, _) => p,
+            Self::Changed(p, _, _) => p,
+        }
+    }
+}
+
+impl std::fmt::Display for Difference<'_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let p = self.path();
+        let p: &dyn std::fmt::Display = if p.is_root() { &"<root>" } else { p };
+        match self {
+            Self::Removed(_, l) => write!(f, "- {}: {:?}", p, l),
+            Self::Added(_, r) => write!(f, "+ {}: {:?}", p, r),
+            Self::Changed(_, l, r) => write!(f, "~ {}: {:?} != {:?}", p, l, r),
+        }
+    }
+}
+
+/// Compute the structural differences between two [Value]s
+///
+/// Dictionaries are compared key by key and lists element by element, so a difference deep
+/// inside a large value is reported only at the path where it occurs. The result is empty if and
+/// only if both values are equal.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let a = dict! { "info" => dict! { "name" => str!("a"), "length" => int!(1) } };
+/// let b = dict! { "info" => dict! { "name" => str!("b") } };
+///
+/// let d = diff(&a, &b);
+/// assert_eq!(d.len(), 2);
+/// assert_eq!(d[0].to_string(), "- info.length: 1");
+/// assert_eq!(d[1].to_string(), "~ info.name: \"a\" != \"b\"");
+/// ```
+pub fn diff<'v, 'a>(left: &'v Value<'a>, right: &'v Value<'a>) -> Vec<Difference<'v, 'a>> {
+    let mut out = Vec::new();
+    walk(&mut Path::new(), left, right, &mut out);
+    out
+}
+
+fn walk<'v, 'a>(path: &mut Path, left: &'v Value<'a>, right: &'v Value<'a>, out: &mut Vec<Difference<'v, 'a>>) {
+    match (left, right) {
+        (Value::List(l), Value::List(r)) => {
+            for i in 0..l.len().max(r.len()) {
+                path.push_index(i);
+                match (l.get(i), r.get(i)) {
+                    (Some(x), Some(y)) => walk(path, x, y, out),
+                    (Some(x), None) => out.push(Difference::Removed(path.clone(), x)),
+                    (None, Some(y)) => out.push(Difference::Added(path.clone(), y)),
+                    (None, None) => (),
+                }
+                path.pop();
+            }
+        }
+        (Value::Dict(l), Value::Dict(r)) => {
+            let mut l = l.iter().peekable();
+            let mut r = r.iter().peekable();
+            loop {
+                let ord = match (l.peek(), r.peek()) {
+                    (Some((x, _)), Some((y, _))) => x.cmp(y),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => break,
+                };
+                match ord {
+                    Ordering::Less => {
+                        let (k, x) = l.next().expect("peeked");
+                        path.push_key(k);
+                        out.push(Difference::Removed(path.clone(), x));
+                    }
+                    Ordering::Greater => {
+                        let (k, y) = r.next().expect("peeked");
+                        path.push_key(k);
+                        out.push(Difference::Added(path.clone(), y));
+                    }
+                    Ordering::Equal => {
+                        let (k, x) = l.next().expect("peeked");
+                        let (_, y) = r.next().expect("peeked");
+                        path.push_key(k);
+                        walk(path, x, y, out);
+                    }
+                }
+                path.pop();
+            }
+        }
+        _ if left != right => out.push(Difference::Changed(path.clone(), left, right)),
+        _ => (),
+    }
+}
+
+/// Anything [assert_bencode_eq!](super::assert_bencode_eq!) accepts: [Value]s and encoded buffers
+#[doc(hidden)]
+pub trait Operand {
+    fn to_value(&self) -> Option<Cow<'_, Value<'_>>>;
+}
+
+impl Operand for Value<'_> {
+    fn to_value(&self) -> Option<Cow<'_, Value<'_>>> {
+        Some(Cow::Borrowed(self))
+    }
+}
+
+impl Operand for [u8] {
+    fn to_value(&self) -> Option<Cow<'_, Value<'_>>> {
+        Value::decode(self, usize::MAX).ok().map(Cow::Owned)
+    }
+}
+
+impl<const N: usize> Operand for [u8; N] {
+    fn to_value(&self) -> Option<Cow<'_, Value<'_>>> {
+        self.as_slice().to_value()
+    }
+}
+
+impl Operand for Vec<u8> {
+    fn to_value(&self) -> Option<Cow<'_, Value<'_>>> {
+        self.as_slice().to_value()
+    }
+}
+
+impl<T: Operand + ?Sized> Operand for &T {
+    fn to_value(&self) -> Option<Cow<'_, Value<'_>>> {
+        (**self).to_value()
+    }
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_eq_diff<L: Operand + ?Sized, R: Operand + ?Sized>(left: &L, right: &R) {
+    let l = left.to_value().expect("left side is not valid Bencode");
+    let r = right.to_value().expect("right side is not valid Bencode");
+    let d = diff(&l, &r);
+    if !d.is_empty() {
+        let mut msg = String::from("assertion `left == right` failed");
+        for x in d {
+            let _ = write!(msg, "\n  {}", x);
+        }
+        panic!("{}", msg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn s(x: &str) -> Value<'_> {
+        Value::Str(Cow::Borrowed(x.as_bytes()))
+    }
+
+    #[test]
+    fn test_diff_equal() {
+        let v = Value::List(vec![Value::Int(1), s("a")]);
+        assert!(diff(&v, &v.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_diff_root() {
+        let l = Value::Int(1);
+        let r = s("1");
+        let d = diff(&l, &r);
+        assert_eq!(d, vec![Difference::Changed(Path::new(), &l, &r)]);
+        assert_eq!(d[0].to_string(), "~ <root>: 1 != \"1\"");
+    }
+
+    #[test]
+    fn test_diff_list() {
+        let l = Value::List(vec![Value::Int(1), Value::Int(2)]);
+        let r = Value::List(vec![Value::Int(1), Value::Int(3), Value::Int(4)]);
+        let d = diff(&l, &r);
+        let d = d.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(d, vec!["~ [1]: 2 != 3", "+ [2]: 4"]);
+    }
+
+    #[test]
+    fn test_diff_nested() {
+        let mut f = BTreeMap::new();
+        f.insert(Cow::Borrowed(b"length".as_ref()), Value::Int(1));
+        let mut g = BTreeMap::new();
+        g.insert(Cow::Borrowed(b"path".as_ref()), s("x"));
+        let mut l = BTreeMap::new();
+        l.insert(Cow::Borrowed(b"files".as_ref()), Value::List(vec![Value::Dict(f)]));
+        let mut r = BTreeMap::new();
+        r.insert(Cow::Borrowed(b"files".as_ref()), Value::List(vec![Value::Dict(g)]));
+        let (l, r) = (Value::Dict(l), Value::Dict(r));
+        let d = diff(&l, &r);
+        let d = d.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(d, vec!["- files[0].length: 1", "+ files[0].path: \"x\""]);
+    }
+
+    #[test]
+    fn test_assert_eq_diff_buffer() {
+        assert_eq_diff(&Value::Int(42), b"i42e");
+        assert_eq_diff(&b"li1ee".to_vec(), &Value::List(vec![Value::Int(1)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "~ [0]: 1 != 2")]
+    fn test_assert_eq_diff_mismatch() {
+        assert_eq_diff(b"li1ee", b"li2ee");
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt::{self, Write};
+
+/// Show an encoded buffer like the [Debug](std::fmt::Debug) output of its [Value](super::Value)
+/// without decoding it
+///
+/// The buffer is walked directly and written to the formatter as it goes, so logging a message
+/// does not allocate. Entries are shown in input order. Invalid input is shown up to the first
+/// invalid byte followed by its offset. [Display](std::fmt::Display) and `Debug` print the same.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let msg = b"d1:ad2:id4:abcde1:q4:ping1:t2:\xff\x011:y1:qe";
+/// assert_eq!(
+///     DisplayBencode(msg).to_string(),
+///     r#"{"a": {"id": "abcd"}, "q": "ping", "t": ff01, "y": "q"}"#
+/// );
+/// assert_eq!(format!("{}", DisplayBencode(b"li1ei2x")), "[1, <invalid at offset 4>");
+/// ```
+#[derive(Clone, Copy)]
+pub struct DisplayBencode<'a>(pub &'a [u8]);
+
+impl DisplayBencode<'_> {
+    /// Nesting beyond this depth is not shown to bound the recursion
+    pub const MAX_DEPTH: usize = 64;
+}
+
+enum Stop {
+    Fmt(fmt::Error),
+    Invalid,
+    TooDeep,
+}
+
+impl From<fmt::Error> for Stop {
+    fn from(e: fmt::Error) -> Self {
+        Self::Fmt(e)
+    }
+}
+
+impl fmt::Display for DisplayBencode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut w = Walker { buf: self.0, pos: 0, f };
+        match w.value(0) {
+            Ok(()) if w.pos < w.buf.len() => write!(w.f, " <{} trailing bytes>", w.buf.len() - w.pos),
+            Ok(()) => Ok(()),
+            Err(Stop::Fmt(e)) => Err(e),
+            Err(Stop::Invalid) => write!(w.f, "<invalid at offset {}>", w.pos),
+            Err(Stop::TooDeep) => write!(w.f, "<nested too deep at offset {}>", w.pos),
+        }
+    }
+}
+
+impl fmt::Debug for DisplayBencode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+struct Walker<'a, 'f, 'g> {
+    buf: &'a [u8],
+    pos: usize,
+    f: &'f mut fmt::Formatter<'g>,
+}
+
+impl<'a> Walker<'a, '_, '_> {
+    fn value(&mut self, depth: usize) -> Result<(), Stop> {
+        match self.peek()? {
+            b'i' => {
+                let start = self.pos + 1;
+                let len = self.buf[start..].iter().position(|c| *c == b'e').ok_or(Stop::Invalid)?;
+                let digits = &self.buf[start..start + len];
+                let unsigned = digits.strip_prefix(b"-").unwrap_or(digits);
+                if unsigned.is_empty() || !unsigned.iter().all(u8::is_ascii_digit) {
+                    return Err(Stop::Invalid);
+                }
+                // Only ASCII was accepted above
+                self.f.write_str(std::str::from_utf8(digits).unwrap_or_default())?;
+                self.pos = start + len + 1;
+            }
+            b'0'..=b'9' => {
+                let s = self.str()?;
+                fmt_str(self.f, s)?;
+            }
+            b'l' | b'd' if depth >= DisplayBencode::MAX_DEPTH => return Err(Stop::TooDeep),
+            b'l' => {
+                self.f.write_char('[')?;
+                self.pos += 1;
+                let mut first = true;
+                while self.peek()? != b'e' {
+                    if !std::mem::take(&mut first) {
+                        self.f.write_str(", ")?;
+                    }
+                    self.value(depth + 1)?;
+                }
+                self.pos += 1;
+                self.f.write_char(']')?;
+            }
+            b'd' => {
+                self.f.write_char('{')?;
+                self.pos += 1;
+                let mut first = true;
+                while self.peek()? != b'e' {
+                    if !std::mem::take(&mut first) {
+                        self.f.write_str(", ")?;
+                    }
+                    if !self.peek()?.is_ascii_digit() {
+                        return Err(Stop::Invalid);
+                    }
+                    let k = self.str()?;
+                    fmt_str(self.f, k)?;
+                    self.f.write_str(": ")?;
+                    self.value(depth + 1)?;
+                }
+                self.pos += 1;
+                self.f.write_char('}')?;
+            }
+            _ => return Err(Stop::Invalid),
+        }
+        Ok(())
+    }
+
+    fn peek(&self) -> Result<u8, Stop> {
+        self.buf.get(self.pos).copied().ok_or(Stop::Invalid)
+    }
+
+    fn str(&mut self) -> Result<&'a [u8], Stop> {
+        let mut len = 0usize;
+        loop {
+            match self.peek()? {
+                b':' => break,
+                c @ b'0'..=b'9' => {
+                    len = len.checked_mul(10).and_then(|x| x.checked_add((c - b'0').into())).ok_or(Stop::Invalid)?;
+                    self.pos += 1;
+                }
+                _ => return Err(Stop::Invalid),
+            }
+        }
+        let start = self.pos + 1;
+        let s = start.checked_add(len).and_then(|end| self.buf.get(start..end)).ok_or(Stop::Invalid)?;
+        self.pos = start + len;
+        Ok(s)
+    }
+}
+
+/// Like the [Debug](std::fmt::Debug) output of [Value::Str](super::Value::Str)
+fn fmt_str(f: &mut fmt::Formatter<'_>, s: &[u8]) -> fmt::Result {
+    match std::str::from_utf8(s) {
+        Ok(s) => write!(f, "{:?}", s),
+        Err(_) => s.iter().try_for_each(|c| write!(f, "{:02x}", c)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+
+    #[test]
+    fn test_same_as_value() {
+        for buf in [b"i-42e".as_ref(), b"0:", b"le", b"de", b"ld1:ai0eee", b"d1:al3:\xfe\x00\x01ee"] {
+            let v = Value::decode(buf, 10).unwrap();
+            assert_eq!(DisplayBencode(buf).to_string(), format!("{:?}", v));
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        for (buf, s) in [
+            (b"".as_ref(), "<invalid at offset 0>"),
+            (b"i-e", "<invalid at offset 0>"),
+            (b"i1", "<invalid at offset 0>"),
+            (b"3:ab", "<invalid at offset 1>"),
+            (b"di1ei2ee", "{<invalid at offset 1>"),
+            (b"l1:ae1:b", "[\"a\"] <3 trailing bytes>"),
+            (b"99999999999999999999999:", "<invalid at offset 19>"),
+        ] {
+            assert_eq!(DisplayBencode(buf).to_string(), s);
+        }
+        let deep = [b'l'; 100];
+        assert!(format!("{:?}", DisplayBencode(&deep)).ends_with("[[<nested too deep at offset 64>"));
+    }
+}
@@ -0,0 +1,145 @@
+use super::decoder::Decoder;
+use super::{DecodeError, DecodeOptions, Value};
+use std::fmt::Write;
+
+/// Render an encoded buffer as an annotated hex listing
+///
+/// Each token (start or end of a container, key, integer or string) is printed on its own line with
+/// its offset, its bytes in hex and ASCII and a description indented by nesting level. Tokens longer
+/// than 16 bytes continue on the following lines. Consecutive values are dumped one after another and
+/// the listing stops with an error line at the first invalid byte.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// assert_eq!(
+///     dump(b"d1:ad2:id3:abce1:y1:qe"),
+///     "\
+/// 00000000  64                                                d                 dict
+/// 00000001  31 3a 61                                          1:a                 key a
+/// 00000004  64                                                d                     dict
+/// 00000005  32 3a 69 64                                       2:id                    key id
+/// 00000009  33 3a 61 62 63                                    3:abc                     str \"abc\"
+/// 0000000e  65                                                e                     end
+/// 0000000f  31 3a 79                                          1:y                 key y
+/// 00000012  31 3a 71                                          1:q                   str \"q\"
+/// 00000015  65                                                e                 end
+/// "
+/// );
+/// ```
+pub fn dump(buf: &[u8]) -> String {
+    let mut out = String::new();
+    let opts = DecodeOptions { max_size_factor: None, ..Default::default() };
+    let mut d = Decoder::with_options(buf, &opts);
+    let mut dumper = Dumper { buf, out: &mut out, depth: 0 };
+    while d.remaining() > 0 {
+        if let Err(e) = dumper.value(&mut d) {
+            let _ = writeln!(dumper.out, "{:08x}  error: {}", buf.len() - d.remaining(), e);
+            break;
+        }
+    }
+    out
+}
+
+struct Dumper<'a, 'o> {
+    buf: &'a [u8],
+    out: &'o mut String,
+    depth: usize,
+}
+
+impl<'a> Dumper<'a, '_> {
+    fn value(&mut self, d: &mut Decoder<'a>) -> Result<(), DecodeError> {
+        let start = self.offset(d);
+        match d.peek_u8()? {
+            b'l' => {
+                d.begin_list()?;
+                self.line(start, d, "list");
+                self.depth += 1;
+                while d.next_item()? {
+                    self.value(d)?;
+                }
+                self.depth -= 1;
+                self.line(self.offset(d) - 1, d, "end");
+            }
+            b'd' => {
+                d.begin_dict()?;
+                self.line(start, d, "dict");
+                self.depth += 1;
+                loop {
+                    let start = self.offset(d);
+                    let Some(k) = d.next_key()? else { break };
+                    self.line(start, d, &format!("key {}", describe_key(&k)));
+                    self.depth += 1;
+                    self.value(d)?;
+                    self.depth -= 1;
+                }
+                self.depth -= 1;
+                self.line(self.offset(d) - 1, d, "end");
+            }
+            _ => match &d.take_any()? {
+                Value::Int(i) => self.line(start, d, &format!("int {}", i)),
+                Value::Str(s) => match std::str::from_utf8(s) {
+                    Ok(x) if x.len() <= 32 && !x.contains(char::is_control) => {
+                        self.line(start, d, &format!("str {:?}", x))
+                    }
+                    _ => self.line(start, d, &format!("str ({} bytes)", s.len())),
+                },
+                _ => unreachable!("containers are handled above"),
+            },
+        }
+        Ok(())
+    }
+
+    fn offset(&self, d: &Decoder) -> usize {
+        self.buf.len() - d.remaining()
+    }
+
+    /// Write the token from `start` to the current position
+    fn line(&mut self, start: usize, d: &Decoder, text: &str) {
+        let token = &self.buf[start..self.offset(d)];
+        for (i, row) in token.chunks(16).enumerate() {
+            let hex: Vec<_> = row.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = row.iter().map(|c| if c.is_ascii_graphic() { *c as char } else { '.' }).collect();
+            let mut line = format!("{:08x}  {:<48}  {:<16}  ", start + 16 * i, hex.join(" "), ascii);
+            if i == 0 {
+                let _ = write!(line, "{:1$}{2}", "", 2 * self.depth, text);
+            }
+            let _ = writeln!(self.out, "{}", line.trim_end());
+        }
+    }
+}
+
+fn describe_key(k: &[u8]) -> String {
+    match std::str::from_utf8(k) {
+        Ok(s) if !s.contains(char::is_control) => s.to_string(),
+        _ => format!("0x{}", k.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_string_and_error() {
+        let mut buf = b"li-7e20:".to_vec();
+        buf.extend(0..20u8);
+        buf.extend(b"ex");
+        let s = dump(&buf);
+        let lines: Vec<_> = s.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[1].starts_with("00000001  69 2d 37 65 ") && lines[1].ends_with("i-7e                int -7"));
+        assert!(lines[2].ends_with("  str (20 bytes)"));
+        assert!(lines[3].starts_with("00000015  0d 0e 0f 10 11 12 13 "));
+        assert!(lines[4].ends_with("e                 end"));
+        assert_eq!(lines[5], "0000001d  error: invalid bencode");
+    }
+
+    #[test]
+    fn test_empty_and_binary_keys() {
+        assert_eq!(dump(b""), "");
+        let s = dump(b"d1:\xffi0ee");
+        assert!(s.lines().nth(1).unwrap().ends_with("key 0xff"));
+        assert_eq!(dump(b"i1").lines().last(), Some("00000002  error: incomplete: need at least 1 more bytes"));
+    }
+}
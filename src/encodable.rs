@@ -0,0 +1,254 @@
+use super::encoder::Encoder;
+use super::Value;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+/// Conversion into encoded output without building a [Value] tree
+///
+/// This is the counterpart to [Decodable](super::Decodable) for hot paths. Structs usually encode
+/// themselves as a dictionary using [Encoder::dict_with] with keys in ascending order:
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// struct Pong<'a> {
+///     t: &'a [u8],
+///     id: [u8; 4],
+/// }
+///
+/// impl Encodable for Pong<'_> {
+///     fn encode(&self, e: &mut Encoder) {
+///         e.dict_with(|d| {
+///             d.key(b"r").dict_with(|r| {
+///                 r.entry(b"id", &self.id);
+///             });
+///             d.entry(b"t", self.t);
+///             d.entry(b"y", "r");
+///         });
+///     }
+/// }
+///
+/// let mut buf = Vec::new();
+/// Encoder::new(&mut buf).encode(&Pong { t: b"aa", id: *b"abcd" });
+/// assert_eq!(buf, b"d1:rd2:id4:abcde1:t2:aa1:y1:re");
+/// ```
+pub trait Encodable {
+    fn encode(&self, e: &mut Encoder);
+}
+
+impl<T: Encodable + ?Sized> Encodable for &T {
+    fn encode(&self, e: &mut Encoder) {
+        (**self).encode(e)
+    }
+}
+
+impl Encodable for i64 {
+    fn encode(&self, e: &mut Encoder) {
+        e.int(*self)
+    }
+}
+
+impl Encodable for i32 {
+    fn encode(&self, e: &mut Encoder) {
+        e.int((*self).into())
+    }
+}
+
+impl Encodable for u32 {
+    fn encode(&self, e: &mut Encoder) {
+        e.int_u64((*self).into())
+    }
+}
+
+impl Encodable for u64 {
+    fn encode(&self, e: &mut Encoder) {
+        e.int_u64(*self)
+    }
+}
+
+impl Encodable for usize {
+    fn encode(&self, e: &mut Encoder) {
+        e.int_usize(*self)
+    }
+}
+
+impl Encodable for [u8] {
+    fn encode(&self, e: &mut Encoder) {
+        e.str(self)
+    }
+}
+
+impl<const N: usize> Encodable for [u8; N] {
+    fn encode(&self, e: &mut Encoder) {
+        e.str(self)
+    }
+}
+
+impl Encodable for Vec<u8> {
+    fn encode(&self, e: &mut Encoder) {
+        e.str(self)
+    }
+}
+
+impl Encodable for Cow<'_, [u8]> {
+    fn encode(&self, e: &mut Encoder) {
+        e.str(self)
+    }
+}
+
+impl Encodable for str {
+    fn encode(&self, e: &mut Encoder) {
+        e.str(self.as_bytes())
+    }
+}
+
+impl Encodable for String {
+    fn encode(&self, e: &mut Encoder) {
+        e.str(self.as_bytes())
+    }
+}
+
+impl Encodable for Value<'_> {
+    fn encode(&self, e: &mut Encoder) {
+        e.value(self)
+    }
+}
+
+impl<T: Encodable> Encodable for [T] {
+    fn encode(&self, e: &mut Encoder) {
+        e.list_with(|e| self.iter().for_each(|x| x.encode(e)))
+    }
+}
+
+impl<T: Encodable> Encodable for Vec<T> {
+    fn encode(&self, e: &mut Encoder) {
+        self.as_slice().encode(e)
+    }
+}
+
+/// The keys are sorted by their bytes, which need not be the order of `K` (see [Encoder::dict_iter])
+impl<K: AsRef<[u8]> + Ord, V: Encodable> Encodable for BTreeMap<K, V> {
+    fn encode(&self, e: &mut Encoder) {
+        // Keys like strings are in byte order already and can be written as they are
+        if !self.keys().zip(self.keys().skip(1)).all(|(a, b)| a.as_ref() < b.as_ref()) {
+            return e.dict_iter(self);
+        }
+        e.raw_u8(b'd');
+        for (k, v) in self {
+            e.str(k.as_ref());
+            v.encode(e);
+        }
+        e.raw_u8(b'e');
+    }
+}
+
+impl<K: AsRef<[u8]>, V: Encodable, S> Encodable for HashMap<K, V, S> {
+    fn encode(&self, e: &mut Encoder) {
+        e.dict_iter(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode<T: Encodable + ?Sized>(v: &T) -> Vec<u8> {
+        let mut buf = Vec::new();
+        Encoder::new(&mut buf).encode(v);
+        buf
+    }
+
+    #[test]
+    fn test_primitives() {
+        assert_eq!(encode(&-3), b"i-3e");
+        assert_eq!(encode(&u64::MAX), b"i18446744073709551615e");
+        assert_eq!(encode(&0usize), b"i0e");
+        assert_eq!(encode("abc"), b"3:abc");
+        assert_eq!(encode(b"abc"), b"3:abc");
+        assert_eq!(encode(&b"abc".to_vec()), b"3:abc");
+    }
+
+    #[test]
+    fn test_int_u128() {
+        let mut buf = Vec::new();
+        let mut e = Encoder::new(&mut buf);
+        assert!(e.int_u128(u64::MAX.into()).is_ok());
+        assert!(e.int_u128(u128::from(u64::MAX) + 1).is_err());
+        assert_eq!(buf, b"i18446744073709551615e");
+    }
+
+    #[test]
+    fn test_containers() {
+        assert_eq!(encode(&vec![1i64, 2]), b"li1ei2ee");
+        assert_eq!(encode(&vec![vec!["a"], vec![]]), b"ll1:aelee");
+        let m: BTreeMap<&str, i64> = [("b", 2), ("a", 1)].into_iter().collect();
+        assert_eq!(encode(&m), b"d1:ai1e1:bi2ee");
+
+        // Keys ordered differently than their bytes
+        #[derive(PartialEq, Eq, PartialOrd, Ord)]
+        struct Rev(std::cmp::Reverse<&'static str>);
+        impl AsRef<[u8]> for Rev {
+            fn as_ref(&self) -> &[u8] {
+                self.0 .0.as_bytes()
+            }
+        }
+        let m: BTreeMap<Rev, i64> = [(Rev(std::cmp::Reverse("a")), 1), (Rev(std::cmp::Reverse("b")), 2)].into();
+        assert_eq!(encode(&m), b"d1:ai1e1:bi2ee");
+    }
+
+    #[test]
+    fn test_sorted_dict_with() {
+        for keys in [["a", "b", "c"], ["c", "b", "a"], ["b", "c", "a"], ["a", "c", "b"]] {
+            let mut buf = Vec::new();
+            Encoder::new(&mut buf).sorted_dict_with(|d| {
+                for k in keys {
+                    d.key(k.as_bytes()).list_with(|l| l.encode(k));
+                }
+            });
+            assert_eq!(buf, b"d1:al1:ae1:bl1:be1:cl1:cee");
+        }
+        let mut buf = Vec::new();
+        Encoder::new(&mut buf).sorted_dict_with(|_| ());
+        assert_eq!(buf, b"de");
+    }
+
+    #[test]
+    #[should_panic(expected = "dictionary keys must be unique")]
+    fn test_sorted_dict_with_duplicate() {
+        let mut buf = Vec::new();
+        Encoder::new(&mut buf).sorted_dict_with(|d| {
+            d.entry(b"b", &1).entry(b"a", &2).entry(b"b", &3);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "dictionary keys must be unique and ascending")]
+    fn test_dict_with_unordered() {
+        let mut buf = Vec::new();
+        Encoder::new(&mut buf).dict_with(|d| {
+            d.entry(b"b", &1).entry(b"a", &2);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "dictionary keys must be unique and ascending")]
+    fn test_dict_with_duplicate() {
+        let mut buf = Vec::new();
+        Encoder::new(&mut buf).dict_with(|d| {
+            d.entry(b"a", &1).entry(b"a", &2);
+        });
+    }
+
+    #[test]
+    fn test_dict_iter() {
+        let m: HashMap<String, Vec<i64>> = (0..20).map(|i| (format!("k{:02}", i), vec![i])).collect();
+        let b: BTreeMap<String, Vec<i64>> = m.clone().into_iter().collect();
+        assert_eq!(encode(&m), encode(&b));
+
+        let mut buf = Vec::new();
+        Encoder::new(&mut buf).dict_iter(vec![(b"b".to_vec(), "x"), (b"a".to_vec(), "y")]);
+        assert_eq!(buf, b"d1:a1:y1:b1:xe");
+        Encoder::new(&mut buf).dict_iter([("z", 1i64), ("y", 2)].iter().filter(|x| x.1 > 1));
+        assert_eq!(buf, b"d1:yi2ee");
+    }
+}
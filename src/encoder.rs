@@ -1,12 +1,56 @@
-use super::Value;
-use std::borrow::Cow;
+use super::value::Piece;
+use super::{Encodable, EncodeError, Value};
+use std::borrow::{Borrow, Cow};
 use std::collections::BTreeMap;
+use std::num::TryFromIntError;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+static DEFAULT_CAPACITY: AtomicUsize = AtomicUsize::new(1500);
+
+/// Set the initial buffer capacity used by [Value::encode] (1500 bytes unless changed)
+///
+/// A DHT node sending small UDP replies may lower it, a tool creating torrents raise it. Use
+/// [Value::encode_with_capacity] where a single call site needs a different size.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// set_default_encode_capacity(300);
+/// assert_eq!(default_encode_capacity(), 300);
+/// assert!(int!(1).encode().capacity() >= 300);
+/// ```
+pub fn set_default_encode_capacity(n: usize) {
+    DEFAULT_CAPACITY.store(n, Ordering::Relaxed);
+}
+
+/// The initial buffer capacity used by [Value::encode] (see [set_default_encode_capacity])
+pub fn default_encode_capacity() -> usize {
+    DEFAULT_CAPACITY.load(Ordering::Relaxed)
+}
+
+/// A low-level encoder appending values to a buffer
+///
+/// Besides encoding [Value]s, the encoder allows to write [Encodable] types directly and to build
+/// lists and dictionaries item by item with [Self::list_with] and [Self::dict_with].
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let mut buf = Vec::new();
+/// let mut e = Encoder::new(&mut buf);
+/// e.dict_with(|d| {
+///     d.entry(b"a", &42);
+///     d.entry(b"b", "hello");
+/// });
+/// assert_eq!(buf, b"d1:ai42e1:b5:helloe");
+/// ```
 pub struct Encoder<'a> {
     buf: &'a mut Vec<u8>,
 }
 
 impl<'a> Encoder<'a> {
+    /// Create an encoder writing into `buf` which is cleared first
     pub fn new(buf: &'a mut Vec<u8>) -> Self {
         buf.clear();
         Self { buf }
@@ -25,7 +69,33 @@ impl<'a> Encoder<'a> {
         self.raw_u8(b'e');
     }
 
+    /// Encode an unsigned integer
+    ///
+    /// The value is written exactly as the format does not limit the size of integers. Note however
+    /// that values above [i64::MAX] are rejected by the [Decoder](super::Decoder) of this crate and by
+    /// many other implementations.
+    pub fn int_u64(&mut self, n: u64) {
+        self.raw_u8(b'i');
+        self.raw_u64(n);
+        self.raw_u8(b'e');
+    }
+
+    /// Encode an unsigned integer (see [Self::int_u64])
+    pub fn int_usize(&mut self, n: usize) {
+        self.int_u64(n as u64);
+    }
+
+    /// Encode an unsigned integer if it does not exceed [u64::MAX] (see [Self::int_u64])
+    ///
+    /// Nothing is written on failure.
+    pub fn int_u128(&mut self, n: u128) -> Result<(), TryFromIntError> {
+        self.int_u64(n.try_into()?);
+        Ok(())
+    }
+
     pub fn str(&mut self, s: &[u8]) {
+        // Reserve once for header and payload
+        self.buf.reserve(str_len(s));
         self.raw_usize(s.len());
         self.raw_u8(b':');
         self.raw_slice(s);
@@ -48,13 +118,203 @@ impl<'a> Encoder<'a> {
         self.raw_u8(b'e');
     }
 
-    pub fn value(&mut self, v: &Value<'_>) {
-        match v {
-            Value::Int(i) => self.int(*i),
-            Value::Str(s) => self.str(s),
-            Value::List(l) => self.list(l),
-            Value::Dict(d) => self.dict(d),
+    /// Encode anything [Encodable]
+    pub fn encode<T: Encodable + ?Sized>(&mut self, v: &T) {
+        v.encode(self);
+    }
+
+    /// Encode a list whose items are written by `f`
+    pub fn list_with(&mut self, f: impl FnOnce(&mut Self)) {
+        self.raw_u8(b'l');
+        f(self);
+        self.raw_u8(b'e');
+    }
+
+    /// Encode a list from the items of an iterator
+    ///
+    /// Items are encoded as they are produced, so large sequences (e.g. from a database cursor) need
+    /// not be collected first. Items may be values or references. See [Self::dict_iter] for
+    /// dictionaries from pair iterators.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let mut buf = Vec::new();
+    /// Encoder::new(&mut buf).dict_with(|d| {
+    ///     d.key(b"peers").list_iter((1..=3).map(|i| format!("10.0.0.{}", i)));
+    ///     d.key(b"sizes").list_iter(&[1i64, 2]);
+    /// });
+    /// assert_eq!(buf, b"d5:peersl8:10.0.0.18:10.0.0.28:10.0.0.3e5:sizesli1ei2eee");
+    /// ```
+    pub fn list_iter<I>(&mut self, iter: I)
+    where
+        I: IntoIterator,
+        I::Item: Encodable,
+    {
+        self.list_with(|e| iter.into_iter().for_each(|x| x.encode(e)));
+    }
+
+    /// Encode a dictionary whose entries are written by `f`
+    ///
+    /// Entries must be written in ascending key order as the format mandates.
+    pub fn dict_with(&mut self, f: impl FnOnce(&mut DictEncoder<'_, 'a>)) {
+        self.raw_u8(b'd');
+        f(&mut DictEncoder { e: self, last_key: None });
+        self.raw_u8(b'e');
+    }
+
+    /// Encode a dictionary whose entries are written by `f` in arbitrary order
+    ///
+    /// Entries are written to the buffer as they come and sorted by key in place afterwards, so the
+    /// output is canonical without building a [BTreeMap] first. Panics on duplicate keys.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let mut buf = Vec::new();
+    /// Encoder::new(&mut buf).sorted_dict_with(|d| {
+    ///     d.entry(b"y", "q");
+    ///     d.entry(b"t", "aa");
+    ///     d.key(b"a").dict_with(|a| {
+    ///         a.entry(b"id", "abcd");
+    ///     });
+    /// });
+    /// assert_eq!(buf, b"d1:ad2:id4:abcde1:t2:aa1:y1:qe");
+    /// ```
+    pub fn sorted_dict_with(&mut self, f: impl FnOnce(&mut SortedDictEncoder<'_, 'a>)) {
+        if let Err(e) = self.try_sorted_dict_with(f) {
+            panic!("dictionary keys must be unique: {}", e);
+        }
+    }
+
+    /// Like [Self::sorted_dict_with], but nothing is written if keys are not unique
+    fn try_sorted_dict_with(&mut self, f: impl FnOnce(&mut SortedDictEncoder<'_, 'a>)) -> Result<(), EncodeError> {
+        let position = self.buf.len();
+        self.raw_u8(b'd');
+        let start = self.buf.len();
+        let mut d = SortedDictEncoder { e: self, entries: Vec::new() };
+        f(&mut d);
+        let mut entries = d.entries;
+        if let Some(last) = entries.last_mut() {
+            last.1.end = self.buf.len();
+        }
+        if let Err(key) = self.sort_entries(start, entries) {
+            let key = self.buf[key].to_vec();
+            self.buf.truncate(position);
+            return Err(EncodeError::DuplicateKey { key });
         }
+        self.raw_u8(b'e');
+        Ok(())
+    }
+
+    /// Sort the dictionary entries written since `start` by key
+    ///
+    /// Each entry is given by the range of its key and the range of the whole entry. Fails with
+    /// the range of a key that is not unique, in which case nothing is moved.
+    pub(crate) fn sort_entries(
+        &mut self,
+        start: usize,
+        mut entries: Vec<(Range<usize>, Range<usize>)>,
+    ) -> Result<(), Range<usize>> {
+        let buf = &mut *self.buf;
+        entries.sort_by(|x, y| buf[x.0.clone()].cmp(&buf[y.0.clone()]));
+        if let Some(w) = entries.windows(2).find(|w| buf[w[0].0.clone()] == buf[w[1].0.clone()]) {
+            return Err(w[0].0.clone());
+        }
+        if !entries.windows(2).all(|w| w[0].1.end == w[1].1.start) {
+            let scratch = buf[start..].to_vec();
+            buf.truncate(start);
+            for (_, r) in entries {
+                buf.extend_from_slice(&scratch[r.start - start..r.end - start]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of bytes in the buffer
+    #[cfg(feature = "serde")]
+    pub(crate) fn position(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Drop everything written after `position`
+    #[cfg(feature = "serde")]
+    pub(crate) fn truncate(&mut self, position: usize) {
+        self.buf.truncate(position);
+    }
+
+    /// Encode a dictionary from (key, value) pairs in arbitrary order
+    ///
+    /// Accepts pairs by value or by reference, e.g. from a [HashMap](std::collections::HashMap),
+    /// a slice of pairs or any iterator. Keys are sorted as needed (see [Self::sorted_dict_with]).
+    /// Panics on duplicate keys, which may come from iterators and from maps whose keys differ but
+    /// have the same bytes. Use [Self::try_dict_iter] where that is possible.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let mut buf = Vec::new();
+    /// Encoder::new(&mut buf).dict_iter(&[("name", "John"), ("age", "42")]);
+    /// assert_eq!(buf, b"d3:age2:424:name4:Johne");
+    ///
+    /// let peers = std::collections::HashMap::from([("b".to_string(), 2), ("a".to_string(), 1)]);
+    /// Encoder::new(&mut buf).dict_iter(&peers);
+    /// assert_eq!(buf, b"d1:ai1e1:bi2ee");
+    /// ```
+    pub fn dict_iter<I, K, V>(&mut self, iter: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(K, V)>,
+        K: AsRef<[u8]>,
+        V: Encodable,
+    {
+        if let Err(e) = self.try_dict_iter(iter) {
+            panic!("dictionary keys must be unique: {}", e);
+        }
+    }
+
+    /// Like [Self::dict_iter], but fail on duplicate keys instead of panicking
+    ///
+    /// Nothing is written to the buffer if it fails.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let mut buf = Vec::new();
+    /// let mut e = Encoder::new(&mut buf);
+    /// let r = e.try_dict_iter([("a", 1), ("b", 2), ("a", 3)]);
+    /// assert_eq!(r, Err(EncodeError::DuplicateKey { key: b"a".to_vec() }));
+    /// e.try_dict_iter([("b", 2), ("a", 1)]).unwrap();
+    /// assert_eq!(buf, b"d1:ai1e1:bi2ee");
+    /// ```
+    pub fn try_dict_iter<I, K, V>(&mut self, iter: I) -> Result<(), EncodeError>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(K, V)>,
+        K: AsRef<[u8]>,
+        V: Encodable,
+    {
+        self.try_sorted_dict_with(|d| {
+            for x in iter {
+                let (k, v) = x.borrow();
+                d.entry(k.as_ref(), v);
+            }
+        })
+    }
+
+    /// Encode a [Value]
+    ///
+    /// This does not recurse, so values nested arbitrarily deep (e.g. built programmatically rather
+    /// than decoded with a depth limit) are fine.
+    pub fn value(&mut self, v: &Value<'_>) {
+        v.walk(|p| match p {
+            Piece::Int(i) => self.int(i),
+            Piece::Str(s) => self.str(s),
+            Piece::List => self.raw_u8(b'l'),
+            Piece::Dict => self.raw_u8(b'd'),
+            Piece::End => self.raw_u8(b'e'),
+            Piece::Raw(s) => self.raw_slice(s),
+        });
     }
 
     pub fn raw_u8(&mut self, n: u8) {
@@ -62,32 +322,98 @@ impl<'a> Encoder<'a> {
     }
 
     pub fn raw_u64(&mut self, n: u64) {
-        let len = n.checked_ilog10().map(|i| i + 1).unwrap_or(1) as usize;
-        let buf = self.alloc(len);
+        // Digits are written into a stack buffer, so the output needs neither zero-filling nor a
+        // second pass
+        let mut tmp = [0u8; 20];
+        let mut i = tmp.len();
         let mut n = n;
-        for b in buf.iter_mut().rev() {
-            *b = b'0' + (n % 10) as u8;
+        loop {
+            i -= 1;
+            tmp[i] = b'0' + (n % 10) as u8;
             n /= 10;
+            if n == 0 {
+                break;
+            }
         }
+        self.buf.extend_from_slice(&tmp[i..]);
     }
 
     pub fn raw_usize(&mut self, n: usize) {
-        let len = n.checked_ilog10().map(|i| i + 1).unwrap_or(1) as usize;
-        let buf = self.alloc(len);
-        let mut n = n;
-        for b in buf.iter_mut().rev() {
-            *b = b'0' + (n % 10) as u8;
-            n /= 10;
-        }
+        self.raw_u64(n as u64);
     }
 
     pub fn raw_slice(&mut self, data: &[u8]) {
-        self.buf.extend(data);
+        self.buf.extend_from_slice(data);
     }
+}
 
-    pub fn alloc(&mut self, len: usize) -> &mut [u8] {
-        let start = self.buf.len();
-        self.buf.resize(start + len, 0);
-        &mut self.buf[start..start + len]
+/// Number of decimal digits of `n`
+pub(crate) fn digits(n: u64) -> usize {
+    n.checked_ilog10().map(|i| i + 1).unwrap_or(1) as usize
+}
+
+/// Encoded length of the byte string `s`
+pub(crate) fn str_len(s: &[u8]) -> usize {
+    digits(s.len() as u64) + 1 + s.len()
+}
+
+/// Writes dictionary entries (see [Encoder::dict_with])
+pub struct DictEncoder<'e, 'a> {
+    e: &'e mut Encoder<'a>,
+    last_key: Option<Range<usize>>,
+}
+
+impl<'a> DictEncoder<'_, 'a> {
+    /// Write an entry with the given key and value
+    ///
+    /// Panics if `key` is not greater than the previous key.
+    pub fn entry<V: Encodable + ?Sized>(&mut self, key: &[u8], value: &V) -> &mut Self {
+        self.key(key);
+        value.encode(self.e);
+        self
+    }
+
+    /// Write a key and return the encoder for the value
+    ///
+    /// The value must be written before the next key. Panics if `key` is not greater than the
+    /// previous key.
+    pub fn key(&mut self, key: &[u8]) -> &mut Encoder<'a> {
+        if let Some(r) = self.last_key.take() {
+            assert!(&self.e.buf[r] < key, "dictionary keys must be unique and ascending");
+        }
+        self.e.str(key);
+        let end = self.e.buf.len();
+        self.last_key = Some(end - key.len()..end);
+        self.e
+    }
+}
+
+/// Writes dictionary entries in arbitrary order (see [Encoder::sorted_dict_with])
+pub struct SortedDictEncoder<'e, 'a> {
+    e: &'e mut Encoder<'a>,
+    // Key and entry range of each entry in the output buffer
+    entries: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl<'a> SortedDictEncoder<'_, 'a> {
+    /// Write an entry with the given key and value
+    pub fn entry<V: Encodable + ?Sized>(&mut self, key: &[u8], value: &V) -> &mut Self {
+        self.key(key);
+        value.encode(self.e);
+        self
+    }
+
+    /// Write a key and return the encoder for the value
+    ///
+    /// The value must be written before the next key.
+    pub fn key(&mut self, key: &[u8]) -> &mut Encoder<'a> {
+        let start = self.e.buf.len();
+        if let Some(last) = self.entries.last_mut() {
+            last.1.end = start;
+        }
+        self.e.str(key);
+        let end = self.e.buf.len();
+        self.entries.push((end - key.len()..end, start..end));
+        self.e
     }
 }
@@ -0,0 +1,246 @@
+use super::{Kind, Path, Segment, TryFromValue, Value};
+
+/// Reason for a failed decode
+///
+/// Stream consumers can tell from [DecodeError::Incomplete] whether waiting for more data may
+/// help. The other variants are final.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecodeError {
+    /// The input ended before the value was complete and at least `needed` more bytes are required
+    Incomplete { needed: usize },
+    /// The input is not valid Bencode
+    Invalid,
+    /// Decoding the input would exceed one of the configured [DecodeOptions](super::DecodeOptions)
+    LimitExceeded,
+    /// A string length is larger than any buffer on this platform can be
+    LengthTooLarge,
+    /// Decoding was aborted by [DecodeOptions::should_continue](super::DecodeOptions::should_continue)
+    Cancelled,
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Incomplete { needed } => write!(f, "incomplete: need at least {} more bytes", needed),
+            Self::Invalid => write!(f, "invalid bencode"),
+            Self::LimitExceeded => write!(f, "decode limit exceeded"),
+            Self::LengthTooLarge => write!(f, "string length too large"),
+            Self::Cancelled => write!(f, "decoding cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Detailed cause of a [DecodeError] (see [Decoder::error_kind](super::Decoder::error_kind))
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecodeErrorKind {
+    /// The input ended before the value was complete
+    Truncated,
+    /// The byte cannot start or continue a value at this position
+    UnexpectedByte(u8),
+    /// A dictionary key appears twice
+    DuplicateKey,
+    /// An integer does not fit into an [i64]
+    IntegerOverflow,
+    /// A leading zero, negative zero or unsorted dictionary key in
+    /// [strict](super::DecodeOptions::strict) mode
+    NonCanonical,
+    /// A string length is larger than any buffer on this platform can be
+    LengthTooLarge,
+    /// Decoding would exceed [DecodeOptions::max_allocs](super::DecodeOptions::max_allocs)
+    AllocLimit,
+    /// Decoding would exceed [DecodeOptions::max_size_factor](super::DecodeOptions::max_size_factor)
+    SizeLimit,
+    /// Decoding would exceed [DecodeOptions::max_steps](super::DecodeOptions::max_steps)
+    StepLimit,
+    /// Decoding would exceed [DecodeOptions::max_depth](super::DecodeOptions::max_depth)
+    DepthLimit,
+    /// Decoding would exceed [DecodeOptions::max_str_bytes](super::DecodeOptions::max_str_bytes)
+    StrLimit,
+    /// Decoding was aborted by [DecodeOptions::should_continue](super::DecodeOptions::should_continue)
+    Cancelled,
+    /// Rejected for another reason, e.g. by a [Decodable](super::Decodable) implementation
+    Other,
+}
+
+impl std::fmt::Display for DecodeErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "truncated input"),
+            Self::UnexpectedByte(b) if b.is_ascii_graphic() => write!(f, "unexpected byte '{}'", *b as char),
+            Self::UnexpectedByte(b) => write!(f, "unexpected byte 0x{:02x}", b),
+            Self::DuplicateKey => write!(f, "duplicate dictionary key"),
+            Self::IntegerOverflow => write!(f, "integer overflow"),
+            Self::NonCanonical => write!(f, "non-canonical encoding"),
+            Self::LengthTooLarge => write!(f, "string length too large"),
+            Self::AllocLimit => write!(f, "allocation limit exceeded"),
+            Self::SizeLimit => write!(f, "size limit exceeded"),
+            Self::StepLimit => write!(f, "step limit exceeded"),
+            Self::DepthLimit => write!(f, "depth limit exceeded"),
+            Self::StrLimit => write!(f, "string limit exceeded"),
+            Self::Cancelled => write!(f, "decoding cancelled"),
+            Self::Other => write!(f, "invalid bencode"),
+        }
+    }
+}
+
+/// Reason for a failed encode
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EncodeError {
+    /// The encoding would take `len` bytes which is more than `max`
+    ///
+    /// `path` points to the innermost value whose encoding crosses the limit.
+    TooLarge { path: Path, len: usize, max: usize },
+    /// A dictionary has two entries with the same `key` (see [Encoder::try_dict_iter](super::Encoder::try_dict_iter))
+    DuplicateKey { key: Vec<u8> },
+}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLarge { path, len, max } if path.is_root() => {
+                write!(f, "encoding takes {} bytes (limit {})", len, max)
+            }
+            Self::TooLarge { path, len, max } => {
+                write!(f, "encoding takes {} bytes (limit {}) at {}", len, max, path)
+            }
+            Self::DuplicateKey { key } => write!(f, "duplicate dictionary key {:?}", String::from_utf8_lossy(key)),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// Reason for a failed [Value::set_path](super::Value::set_path)
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PathError {
+    /// The value at the path is neither a dictionary nor a list matching the next segment
+    NotAContainer(Path),
+    /// The list index at the end of the path is beyond the end of the list
+    OutOfRange(Path),
+}
+
+impl std::fmt::Display for PathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotAContainer(p) => write!(f, "no matching container at {}", p),
+            Self::OutOfRange(p) => write!(f, "index out of range at {}", p),
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/// Reason for a failed conversion from [Value] (see [TryFromValue::convert](super::TryFromValue::convert))
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let v = dict! { "peers" => list![int!(1), str!("x")] };
+/// let e = <Vec<i64> as TryFromValue>::convert(v.get_path("peers").unwrap()).unwrap_err();
+/// assert_eq!(e.to_string(), "cannot convert Str to i64 at [1]");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConversionError {
+    /// Name of the target type (see [TryFromValue::NAME])
+    pub expected: &'static str,
+    /// Type of the converted value (which may match if only its content does not fit, e.g. a non-UTF-8 string)
+    ///
+    /// This is `None` if a required dictionary entry is missing.
+    pub actual: Option<Kind>,
+    /// Location of the offending value relative to the converted one
+    pub path: Path,
+}
+
+impl ConversionError {
+    /// The error for `value` not being convertible to `T`
+    pub fn new<'a, T: TryFromValue<'a>>(value: &Value) -> Self {
+        Self { expected: T::NAME, actual: Some(value.kind()), path: Path::new() }
+    }
+
+    /// The error for a missing dictionary entry `key` that should have been a `T`
+    pub fn missing<'a, T: TryFromValue<'a>>(key: &[u8]) -> Self {
+        Self { expected: T::NAME, actual: None, path: Path::new().key(key) }
+    }
+
+    /// Whether a required dictionary entry is missing (rather than present with the wrong type)
+    pub fn is_missing(&self) -> bool {
+        self.actual.is_none()
+    }
+
+    /// Prepend `key` to the path when passing on the error of a dictionary entry
+    pub fn at_key(mut self, key: &[u8]) -> Self {
+        self.path.push_front(Segment::Key(key.to_vec()));
+        self
+    }
+
+    /// Prepend `index` to the path when passing on the error of a list item
+    pub fn at_index(mut self, index: usize) -> Self {
+        self.path.push_front(Segment::Index(index));
+        self
+    }
+}
+
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.actual {
+            Some(actual) => write!(f, "cannot convert {:?} to {}", actual, self.expected)?,
+            None => write!(f, "missing {}", self.expected)?,
+        }
+        if !self.path.is_root() {
+            write!(f, " at {}", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// Reason for a failed [to_bytes](super::to_bytes) or [from_bytes](super::from_bytes)
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SerdeError {
+    /// The input is not valid Bencode or has bytes after the value
+    Decode(DecodeError),
+    /// The value has no Bencode representation (e.g. floats or a map with non-string keys)
+    Unsupported(&'static str),
+    /// Two entries of a map or struct have the same key
+    DuplicateKey,
+    /// An error reported by a `Serialize` or `Deserialize` implementation (e.g. a type mismatch)
+    Custom(String),
+}
+
+#[cfg(feature = "serde")]
+impl std::fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Decode(e) => e.fmt(f),
+            Self::Unsupported(what) => write!(f, "{} not supported by bencode", what),
+            Self::DuplicateKey => write!(f, "duplicate dictionary key"),
+            Self::Custom(msg) => f.write_str(msg),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::error::Error for SerdeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<DecodeError> for SerdeError {
+    fn from(e: DecodeError) -> Self {
+        Self::Decode(e)
+    }
+}
@@ -0,0 +1,147 @@
+//! Fuzzing harness utilities
+//!
+//! [check] is meant to be called directly from a `cargo fuzz` or AFL target:
+//!
+//! ```rust,ignore
+//! fuzz_target!(|data: &[u8]| bencode_minimal::fuzz::check(data));
+//! ```
+//!
+//! [mutate] derives malformed variants from valid inputs. It can seed a corpus or drive a simple
+//! fuzz loop without any external tooling.
+use super::{peek_len, Value};
+
+/// Exercise decoding and encoding of `buf` and panic if any invariant is violated
+///
+/// Invalid input is fine and simply ignored. For input that decodes, the following must hold:
+///
+/// - [peek_len] reports the length of the value and decoding just that prefix yields the same value.
+/// - Decoding succeeds with exactly as many allocations as the value contains and fails with one less.
+/// - [Value::encoded_len] matches the length of the encoding.
+/// - The value re-encodes to a buffer that decodes to the same value.
+/// - Encoding is canonical: re-encoding the decoded re-encoding yields identical bytes.
+/// - [Value::encode_into] produces the same bytes as [Value::encode] regardless of prior buffer content.
+/// - [Value::into_owned] preserves equality.
+pub fn check(buf: &[u8]) {
+    let Ok(v) = Value::decode(buf, usize::MAX) else {
+        return;
+    };
+    let len = peek_len(buf).expect("peek_len fails on decodable input");
+    assert_eq!(Value::decode(&buf[..len], usize::MAX).as_ref().ok(), Some(&v), "peek_len reports wrong length");
+
+    let allocs = count_allocs(&v);
+    assert_eq!(Value::decode(buf, allocs).as_ref().ok(), Some(&v), "decode fails within exact alloc limit");
+    if let Some(n) = allocs.checked_sub(1) {
+        assert!(Value::decode(buf, n).is_err(), "decode succeeds below alloc limit");
+    }
+
+    let e = v.encode();
+    assert_eq!(v.encoded_len(), e.len(), "encoded_len differs from actual length");
+    let w = Value::decode(&e, allocs).expect("re-encoded value does not decode");
+    assert_eq!(w, v, "re-encoded value decodes to a different value");
+    assert_eq!(w.encode(), e, "encoding is not canonical");
+
+    let mut b = vec![0xff; 3];
+    v.encode_into(&mut b);
+    assert_eq!(b, e, "encode_into differs from encode");
+
+    assert_eq!(v.clone().into_owned(), v, "into_owned changes the value");
+}
+
+/// Derive a (most likely) malformed variant of `buf`
+///
+/// The mutation is chosen deterministically from `seed`: truncation, byte replacement with
+/// structurally relevant characters, insertion, deletion or duplication of a random range.
+///
+/// ```rust
+/// use bencode_minimal::fuzz;
+///
+/// let input = b"d4:infod6:lengthi42e4:name3:fooee";
+/// for seed in 0..1000 {
+///     fuzz::check(&fuzz::mutate(input, seed));
+/// }
+/// ```
+pub fn mutate(buf: &[u8], seed: u64) -> Vec<u8> {
+    const INTERESTING: &[u8] = b"ilde:-0123456789";
+    let mut rng = SplitMix64(seed);
+    let mut v = buf.to_vec();
+    let pos = rng.below(v.len() + 1);
+    let byte = INTERESTING[rng.below(INTERESTING.len())];
+    match rng.below(5) {
+        0 => v.truncate(pos),
+        1 if pos < v.len() => v[pos] = byte,
+        2 if pos < v.len() => {
+            v.remove(pos);
+        }
+        3 => {
+            let end = pos + rng.below(v.len() - pos + 1);
+            let dup = v[pos..end].to_vec();
+            v.splice(pos..pos, dup);
+        }
+        _ => v.insert(pos, byte),
+    }
+    v
+}
+
+fn count_allocs(v: &Value) -> usize {
+    match v {
+        Value::Int(_) | Value::Str(_) | Value::Raw(_) => 0,
+        Value::List(l) => l.len() + l.iter().map(count_allocs).sum::<usize>(),
+        Value::Dict(d) => d.len() + d.values().map(count_allocs).sum::<usize>(),
+    }
+}
+
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CORPUS: &[&[u8]] = &[
+        b"i0e",
+        b"i-42e",
+        b"0:",
+        b"5:hello",
+        b"le",
+        b"li1ei2ee",
+        b"de",
+        b"d3:agei42e4:name4:Johne",
+        b"d4:name4:John3:agei42ee",
+        b"d1:ad1:bl1:cdeleee",
+    ];
+
+    #[test]
+    fn test_check_corpus() {
+        for buf in CORPUS {
+            check(buf);
+        }
+    }
+
+    #[test]
+    fn test_check_mutations() {
+        for buf in CORPUS {
+            for seed in 0..2000 {
+                check(&mutate(buf, seed));
+            }
+        }
+    }
+
+    #[test]
+    fn test_mutate_deterministic() {
+        assert_eq!(mutate(b"li1ei2ee", 7), mutate(b"li1ei2ee", 7));
+        assert_eq!(mutate(b"", 7), mutate(b"", 7));
+    }
+}
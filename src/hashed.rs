@@ -0,0 +1,108 @@
+use super::{Encodable, Encoder, Value};
+use std::hash::{Hash, Hasher};
+
+/// A [Value] that caches its hash for use as a key in hash maps and sets
+///
+/// Hashing a [Value] walks all of its content, so using a decoded `info` dictionary with megabytes of
+/// `pieces` as a `HashMap` key re-hashes all of that on every lookup. [Hashed] computes the hash once
+/// (via [Value::fingerprint64]) on construction and again only when the value is modified through
+/// [Self::update]. Equality still compares the values themselves.
+///
+/// ```rust
+/// use bencode_minimal::*;
+/// use std::collections::HashSet;
+///
+/// let info = dict! { "length" => int!(42), "pieces" => str!(vec![0u8; 20000]) };
+/// let mut seen = HashSet::new();
+/// assert!(seen.insert(Hashed::new(info.clone())));
+/// assert!(!seen.insert(Hashed::new(info)));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Hashed<'a> {
+    value: Value<'a>,
+    hash: u64,
+}
+
+impl<'a> Hashed<'a> {
+    pub fn new(value: Value<'a>) -> Self {
+        let hash = value.fingerprint64();
+        Self { value, hash }
+    }
+
+    pub fn get(&self) -> &Value<'a> {
+        &self.value
+    }
+
+    /// Modify the value with `f` and update the cached hash
+    pub fn update<R>(&mut self, f: impl FnOnce(&mut Value<'a>) -> R) -> R {
+        let r = f(&mut self.value);
+        self.hash = self.value.fingerprint64();
+        r
+    }
+
+    pub fn into_inner(self) -> Value<'a> {
+        self.value
+    }
+
+    /// The cached [Value::fingerprint64]
+    pub fn fingerprint64(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl<'a> From<Value<'a>> for Hashed<'a> {
+    fn from(value: Value<'a>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<'a> std::ops::Deref for Hashed<'a> {
+    type Target = Value<'a>;
+
+    fn deref(&self) -> &Value<'a> {
+        &self.value
+    }
+}
+
+impl PartialEq for Hashed<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        // Differing hashes are a cheap way out
+        self.hash == other.hash && self.value == other.value
+    }
+}
+
+impl Eq for Hashed<'_> {}
+
+impl Hash for Hashed<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl Encodable for Hashed<'_> {
+    fn encode(&self, e: &mut Encoder) {
+        e.value(&self.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_update() {
+        let mut h = Hashed::new(Value::Int(1));
+        assert_eq!(h.fingerprint64(), Value::Int(1).fingerprint64());
+        h.update(|v| *v = Value::Int(2));
+        assert_eq!(h.fingerprint64(), Value::Int(2).fingerprint64());
+        assert_eq!(h, Hashed::new(Value::Int(2)));
+        assert_ne!(h, Hashed::new(Value::Int(1)));
+
+        let mut m = HashMap::new();
+        m.insert(h.clone(), "two");
+        assert_eq!(m.get(&Hashed::from(Value::Int(2))), Some(&"two"));
+        assert_eq!(m.get(&Hashed::from(Value::Int(1))), None);
+        assert_eq!(h.into_inner(), Value::Int(2));
+    }
+}
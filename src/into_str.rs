@@ -1,5 +1,6 @@
 use super::Str;
 use std::borrow::Cow;
+use std::sync::Arc;
 
 /// Conversion into [Str]
 pub trait IntoStr<'a> {
@@ -41,3 +42,60 @@ impl<'a> IntoStr<'a> for String {
         Cow::Owned(self.into_bytes())
     }
 }
+
+impl<'a, 'b: 'a> IntoStr<'a> for &'b String {
+    fn into_str(self) -> Str<'a> {
+        Cow::Borrowed(self.as_bytes())
+    }
+}
+
+impl<'a, 'b: 'a> IntoStr<'a> for &'b Vec<u8> {
+    fn into_str(self) -> Str<'a> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl<'a> IntoStr<'a> for Box<[u8]> {
+    fn into_str(self) -> Str<'a> {
+        Cow::Owned(self.into_vec())
+    }
+}
+
+/// Copies the content as [Str] cannot share ownership
+impl<'a> IntoStr<'a> for Arc<[u8]> {
+    fn into_str(self) -> Str<'a> {
+        Cow::Owned(self.to_vec())
+    }
+}
+
+impl<'a, 'b: 'a> IntoStr<'a> for Cow<'b, [u8]> {
+    fn into_str(self) -> Str<'a> {
+        self
+    }
+}
+
+impl<'a, 'b: 'a> IntoStr<'a> for Cow<'b, str> {
+    fn into_str(self) -> Str<'a> {
+        match self {
+            Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
+            Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_borrow_or_own() {
+        let s = String::from("abc");
+        let v = b"abc".to_vec();
+        assert!(matches!((&s).into_str(), Cow::Borrowed(b"abc")));
+        assert!(matches!((&v).into_str(), Cow::Borrowed(b"abc")));
+        assert!(matches!(Cow::Borrowed("abc").into_str(), Cow::Borrowed(b"abc")));
+        assert!(matches!(Cow::<str>::Owned(s).into_str(), Cow::Owned(x) if x == b"abc"));
+        assert!(matches!(v.clone().into_boxed_slice().into_str(), Cow::Owned(x) if x == b"abc"));
+        assert!(matches!(Arc::<[u8]>::from(v).into_str(), Cow::Owned(x) if x == b"abc"));
+    }
+}
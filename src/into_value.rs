@@ -0,0 +1,110 @@
+use super::{IntoStr, Value};
+use std::collections::BTreeMap;
+
+/// Conversion into [Value]
+///
+/// Integers become [Value::Int], byte strings and text become [Value::Str] (see [IntoStr]), vectors
+/// become [Value::List] and maps become [Value::Dict].
+pub trait IntoValue<'a> {
+    fn into_value(self) -> Value<'a>;
+}
+
+impl<'a> IntoValue<'a> for Value<'a> {
+    fn into_value(self) -> Value<'a> {
+        self
+    }
+}
+
+impl<'a> IntoValue<'a> for i64 {
+    fn into_value(self) -> Value<'a> {
+        Value::Int(self)
+    }
+}
+
+impl<'a> IntoValue<'a> for i32 {
+    fn into_value(self) -> Value<'a> {
+        Value::Int(self.into())
+    }
+}
+
+impl<'a> IntoValue<'a> for u32 {
+    fn into_value(self) -> Value<'a> {
+        Value::Int(self.into())
+    }
+}
+
+impl<'a, 'b: 'a> IntoValue<'a> for &'b [u8] {
+    fn into_value(self) -> Value<'a> {
+        Value::Str(self.into_str())
+    }
+}
+
+impl<'a, 'b: 'a, const N: usize> IntoValue<'a> for &'b [u8; N] {
+    fn into_value(self) -> Value<'a> {
+        Value::Str(self.into_str())
+    }
+}
+
+impl<'a, 'b: 'a> IntoValue<'a> for &'b str {
+    fn into_value(self) -> Value<'a> {
+        Value::Str(self.into_str())
+    }
+}
+
+impl<'a> IntoValue<'a> for Vec<u8> {
+    fn into_value(self) -> Value<'a> {
+        Value::Str(self.into_str())
+    }
+}
+
+impl<'a, const N: usize> IntoValue<'a> for [u8; N] {
+    fn into_value(self) -> Value<'a> {
+        Value::Str(self.into_str())
+    }
+}
+
+impl<'a> IntoValue<'a> for String {
+    fn into_value(self) -> Value<'a> {
+        Value::Str(self.into_str())
+    }
+}
+
+impl<'a, T: IntoValue<'a>> IntoValue<'a> for Vec<T> {
+    fn into_value(self) -> Value<'a> {
+        Value::List(self.into_iter().map(IntoValue::into_value).collect())
+    }
+}
+
+impl<'a, K: IntoStr<'a>, V: IntoValue<'a>> IntoValue<'a> for BTreeMap<K, V> {
+    fn into_value(self) -> Value<'a> {
+        Value::Dict(self.into_iter().map(|(k, v)| (k.into_str(), v.into_value())).collect())
+    }
+}
+
+/// Convert anything [IntoValue] and encode it in one go
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// assert_eq!(encode_value(vec!["a", "b"]), b"l1:a1:be");
+/// assert_eq!(encode_value(42), b"i42e");
+/// ```
+pub fn encode_value<'a>(value: impl IntoValue<'a>) -> Vec<u8> {
+    value.into_value().encode()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_into_value() {
+        let m: BTreeMap<&str, Vec<Vec<u8>>> = [("k", vec![b"x".to_vec()])].into_iter().collect();
+        let v = m.into_value();
+        assert_eq!(v.encode(), b"d1:kl1:xee");
+        assert_eq!(b"ab".into_value(), Value::Str(Cow::Borrowed(b"ab")));
+        assert_eq!(String::from("ab").into_value(), Value::Str(Cow::Owned(b"ab".to_vec())));
+        assert_eq!(u32::MAX.into_value(), Value::Int(u32::MAX.into()));
+    }
+}
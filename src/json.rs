@@ -0,0 +1,79 @@
+use super::de::{finish, Stream};
+use super::{DecodeOptions, Decoder, SerdeError, Serializer, Value};
+
+/// Convert Bencode to JSON without building a [Value](super::Value) in between
+///
+/// Strings that are valid UTF-8 become JSON strings, all others arrays of byte values. The input
+/// is decoded with the [default options](DecodeOptions::default) and must be a single value.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let json = transcode_to_json(b"d1:ali1ei-2ee1:b2:\xff\x00e").unwrap();
+/// assert_eq!(json, r#"{"a":[1,-2],"b":[255,0]}"#);
+/// assert_eq!(transcode_from_json(&json).unwrap(), b"d1:ali1ei-2ee1:bli255ei0eee");
+/// ```
+pub fn transcode_to_json(buf: &[u8]) -> Result<String, SerdeError> {
+    let opts = DecodeOptions::default();
+    let mut d = Decoder::with_options(buf, &opts);
+    let mut json = Vec::new();
+    let r = serde_transcode::transcode(Stream { d: &mut d, text: true }, &mut serde_json::Serializer::new(&mut json));
+    if let Err(e) = r {
+        // The transcoder turns decoding errors into text, so decode again to report them as such
+        return Err(match Value::decode_with(buf, &opts) {
+            Err(e) => SerdeError::Decode(e),
+            Ok(_) => SerdeError::Custom(e.to_string()),
+        });
+    }
+    finish(&mut d, buf, Ok(()))?;
+    Ok(String::from_utf8(json).expect("JSON is UTF-8"))
+}
+
+/// Convert JSON to Bencode with the rules of [to_bytes](super::to_bytes)
+///
+/// Objects become dictionaries with sorted keys and `null` is left out where possible. Floats and
+/// other values Bencode has no representation for are an error.
+pub fn transcode_from_json(json: &str) -> Result<Vec<u8>, SerdeError> {
+    let mut buf = Vec::new();
+    let mut de = serde_json::Deserializer::from_str(json);
+    serde_transcode::transcode(&mut de, &mut Serializer::new(&mut buf))?;
+    de.end().map_err(|e| SerdeError::Custom(e.to_string()))?;
+    if buf.is_empty() {
+        return Err(SerdeError::Unsupported("none or unit as root value"));
+    }
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DecodeError;
+
+    #[test]
+    fn test_round_trip() {
+        for buf in [
+            b"i-42e".as_ref(),
+            b"0:",
+            b"le",
+            b"de",
+            b"d1:ad1:bli1ei2eee1:c3:xyze",
+            b"d8:announce3:foo4:infod6:lengthi42e4:name3:bar12:piece lengthi16384eee",
+        ] {
+            let json = transcode_to_json(buf).unwrap();
+            assert_eq!(transcode_from_json(&json).unwrap(), buf, "{}", json);
+        }
+        assert_eq!(transcode_to_json(b"l2:\xc3\xa4e").unwrap(), r#"["ä"]"#);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(transcode_to_json(b"i1ei2e"), Err(SerdeError::Decode(DecodeError::Invalid)));
+        assert_eq!(transcode_to_json(b"li1e"), Err(SerdeError::Decode(DecodeError::Incomplete { needed: 1 })));
+        assert_eq!(transcode_to_json(b"d1:ai0e1:ai1ee"), Err(SerdeError::Decode(DecodeError::Invalid)));
+        assert!(matches!(transcode_from_json("1.5"), Err(SerdeError::Custom(_))));
+        assert!(matches!(transcode_from_json("[1] x"), Err(SerdeError::Custom(_))));
+        assert_eq!(transcode_from_json("null"), Err(SerdeError::Unsupported("none or unit as root value")));
+        assert_eq!(transcode_from_json(r#"{"a":null,"b":[]}"#).unwrap(), b"d1:blee");
+        assert_eq!(transcode_from_json(r#"{"b":1,"a":2}"#).unwrap(), b"d1:ai2e1:bi1ee");
+    }
+}
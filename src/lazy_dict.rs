@@ -0,0 +1,146 @@
+use super::decoder::{Decoder, DICT_ENTRY_SIZE};
+use super::{Decodable, DecodeError, DecodeOptions, Kind, Str, Value};
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
+
+/// A dictionary whose values are only decoded when accessed
+///
+/// Decoding a [LazyDict] only reads the keys and skips over the values, remembering where each value
+/// is located in the input. Handlers that inspect one or two fields of a large dictionary (like the
+/// `info` of a torrent next to megabytes of `pieces`) thereby skip building the rest entirely.
+/// Values are checked for syntax up front, but duplicate keys within values and the limits of the
+/// [DecodeOptions] only apply when a value is decoded.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let buf = b"d8:announce3:foo4:infod6:lengthi42e6:pieces4:xxxxee";
+/// let d = LazyDict::decode(buf, &DecodeOptions::default()).unwrap();
+/// assert_eq!(d.raw(b"info"), Some(b"d6:lengthi42e6:pieces4:xxxxe".as_ref()));
+/// assert_eq!(d.get(b"announce"), Ok(Some(&str!("foo"))));
+/// assert_eq!(d.get_as::<&str>(b"announce"), Ok(Some("foo")));
+/// ```
+#[derive(Clone, Debug)]
+pub struct LazyDict<'a> {
+    entries: BTreeMap<Cow<'a, [u8]>, Entry<'a>>,
+    opts: DecodeOptions,
+}
+
+#[derive(Clone, Debug)]
+struct Entry<'a> {
+    raw: &'a [u8],
+    value: OnceCell<Value<'a>>,
+}
+
+impl<'a> LazyDict<'a> {
+    /// Read the keys of the dictionary at the start of `buf`
+    pub fn decode(buf: &'a [u8], opts: &DecodeOptions) -> Result<Self, DecodeError> {
+        let mut d = Decoder::with_options(buf, opts);
+        let r = Self::take(&mut d, buf);
+        d.finish(r.as_ref().map(|_| Kind::Dict).map_err(|e| *e));
+        Ok(Self { entries: r?, opts: opts.clone() })
+    }
+
+    fn take(d: &mut Decoder<'a>, buf: &'a [u8]) -> Result<BTreeMap<Str<'a>, Entry<'a>>, DecodeError> {
+        let mut entries = BTreeMap::new();
+        d.begin_dict()?;
+        while let Some(k) = d.next_key()? {
+            d.alloc(DICT_ENTRY_SIZE)?;
+            let start = buf.len() - d.remaining();
+            d.skip_value()?;
+            let raw = &buf[start..buf.len() - d.remaining()];
+            d.insert_entry(&mut entries, k, Entry { raw, value: OnceCell::new() })?;
+        }
+        Ok(entries)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// The keys in ascending order
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> + use<'_, 'a> {
+        self.entries.keys().map(AsRef::as_ref)
+    }
+
+    /// The encoded value for `key` as found in the input
+    pub fn raw(&self, key: &[u8]) -> Option<&'a [u8]> {
+        self.entries.get(key).map(|e| e.raw)
+    }
+
+    /// Decode the value for `key` on first access and return it
+    ///
+    /// Successfully decoded values are cached, so later calls are cheap.
+    pub fn get(&self, key: &[u8]) -> Result<Option<&Value<'a>>, DecodeError> {
+        let Some(e) = self.entries.get(key) else {
+            return Ok(None);
+        };
+        if let Some(v) = e.value.get() {
+            return Ok(Some(v));
+        }
+        let v = Value::decode_with(e.raw, &self.opts)?;
+        Ok(Some(e.value.get_or_init(|| v)))
+    }
+
+    /// Decode the value for `key` as `T` without caching (see [Decodable])
+    pub fn get_as<T: Decodable<'a>>(&self, key: &[u8]) -> Result<Option<T>, DecodeError> {
+        let Some(raw) = self.raw(key) else {
+            return Ok(None);
+        };
+        Decoder::with_options(raw, &self.opts).decode().map(Some)
+    }
+
+    /// Decode all remaining values and convert into a [Value::Dict]
+    pub fn into_value(self) -> Result<Value<'a>, DecodeError> {
+        let mut dict = BTreeMap::new();
+        for (k, e) in self.entries {
+            let v = match e.value.into_inner() {
+                Some(v) => v,
+                None => Value::decode_with(e.raw, &self.opts)?,
+            };
+            dict.insert(k, v);
+        }
+        Ok(Value::Dict(dict))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lazy() {
+        let buf = b"d1:bd1:xi1e1:xi2ee1:ai0ee";
+        let d = LazyDict::decode(buf, &DecodeOptions::default()).unwrap();
+        assert_eq!(d.keys().collect::<Vec<_>>(), vec![b"a", b"b"]);
+        assert_eq!(d.get(b"a"), Ok(Some(&Value::Int(0))));
+        assert_eq!(d.get(b"b"), Err(DecodeError::Invalid));
+        assert_eq!(d.get(b"c"), Ok(None));
+        assert_eq!(d.get_as::<i64>(b"c"), Ok(None));
+        assert_eq!(d.clone().into_value(), Err(DecodeError::Invalid));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let opts = DecodeOptions::default();
+        assert_eq!(LazyDict::decode(b"li1ee", &opts).unwrap_err(), DecodeError::Invalid);
+        assert_eq!(LazyDict::decode(b"d1:ai1e1:ai1ee", &opts).unwrap_err(), DecodeError::Invalid);
+        assert_eq!(LazyDict::decode(b"d1:ai1x", &opts).unwrap_err(), DecodeError::Invalid);
+        assert_eq!(LazyDict::decode(b"d1:ai1e", &opts).unwrap_err(), DecodeError::Incomplete { needed: 1 });
+        let d = LazyDict::decode(b"d1:ali1ei2ee1:bi3ee", &opts).unwrap();
+        assert_eq!(d.into_value(), Value::decode_with(b"d1:ali1ei2ee1:bi3ee", &opts));
+
+        let opts = DecodeOptions::default().with_strict(true);
+        assert_eq!(LazyDict::decode(b"d1:bi1e1:ai1ee", &opts).unwrap_err(), DecodeError::Invalid);
+        assert!(LazyDict::decode(b"d1:ad1:bi1ee1:bi1ee", &opts).is_ok());
+    }
+}
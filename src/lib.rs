@@ -1,12 +1,100 @@
+#[cfg(test)]
+mod alloc_count;
+pub mod config;
+mod ct;
+#[cfg(feature = "serde")]
+mod de;
+mod decodable;
+mod decode_all;
+mod decode_options;
 mod decoder;
+#[cfg(feature = "defmt")]
+mod defmt_format;
+mod diff;
+mod display;
+mod dump;
+mod encodable;
 mod encoder;
+mod error;
+pub mod fuzz;
+mod hashed;
 mod into_str;
+mod into_value;
+#[cfg(feature = "json")]
+mod json;
+mod lazy_dict;
+pub mod metainfo;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "indexmap")]
+mod ordered;
+mod patch;
+mod path;
+mod peek;
+#[doc(hidden)]
+pub mod private;
+mod push_decoder;
+mod report;
+#[cfg(feature = "zeroize")]
+mod secret;
+#[cfg(feature = "serde")]
+mod ser;
+#[cfg(feature = "serde")]
+mod serde_value;
+mod sha1;
+mod shared;
+mod spans;
+mod telemetry;
+mod tokens;
 mod try_from_value;
 mod value;
+mod value_mut;
+mod verbatim;
+mod xxh64;
 
+#[cfg(feature = "derive")]
+pub use bencode_minimal_derive::{FromBencode, ToBencode};
+pub use ct::ct_eq;
+#[cfg(feature = "serde")]
+pub use de::{from_bytes, from_bytes_with, Deserializer};
+pub use decodable::Decodable;
+pub use decode_all::{decode_all, DecodeAll};
+pub use decode_options::{ContinueHook, DecodeOptions, DecodeStats, DuplicateKeys, RejectHook, Rejection};
+pub use decoder::{Checkpoint, Decoder};
+#[doc(hidden)]
+pub use diff::{assert_eq_diff, Operand};
+pub use diff::{diff, Difference};
+pub use display::DisplayBencode;
+pub use dump::dump;
+pub use encodable::Encodable;
+pub use encoder::{default_encode_capacity, set_default_encode_capacity, DictEncoder, Encoder, SortedDictEncoder};
+#[cfg(feature = "serde")]
+pub use error::SerdeError;
+pub use error::{ConversionError, DecodeError, DecodeErrorKind, EncodeError, PathError};
+pub use hashed::Hashed;
 pub use into_str::IntoStr;
-pub use try_from_value::TryFromValue;
-pub use value::{Dict, Int, List, Str, Value};
+pub use into_value::{encode_value, IntoValue};
+#[cfg(feature = "json")]
+pub use json::{transcode_from_json, transcode_to_json};
+pub use lazy_dict::LazyDict;
+#[cfg(feature = "mmap")]
+pub use mmap::MappedValue;
+#[cfg(feature = "indexmap")]
+pub use ordered::{OrderedDict, OrderedValue};
+pub use patch::{Patch, PatchOp};
+pub use path::{Path, Segment};
+pub use peek::{estimate_limits, measure, peek_kind, peek_len};
+pub use push_decoder::{DecodeProgress, PushDecoder};
+pub use report::DecodeReport;
+#[cfg(feature = "serde")]
+pub use ser::{to_bytes, to_text, Serializer};
+pub use shared::SharedValue;
+pub use spans::Spans;
+pub use tokens::{Token, Tokens};
+pub use try_from_value::{decode_as, TryFromValue};
+pub use value::{Dict, Int, Kind, List, Str, Value};
+pub use value_mut::ValueMut;
+pub use verbatim::Verbatim;
 
 /// Create a [Value::Int] from [i64]
 ///
@@ -50,6 +138,24 @@ macro_rules! str {
     };
 }
 
+/// Create an owned [Value::Str] from format arguments like [format!]
+///
+/// Dictionary keys can be formatted with [format!] directly as [String] is [IntoStr].
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let (idx, port) = (3, 6881);
+/// let v = dict! { format!("peer-{}", idx) => strf!("{}:{port}", "10.0.0.1") };
+/// assert_eq!(v.encode(), b"d6:peer-313:10.0.0.1:6881e");
+/// ```
+#[macro_export]
+macro_rules! strf {
+    ($($arg:tt)*) => {
+        bencode_minimal::Value::Str(std::borrow::Cow::Owned(format!($($arg)*).into_bytes()))
+    };
+}
+
 /// Create a [Value::List] from a list of [Value]s
 ///
 /// ```rust
@@ -71,6 +177,9 @@ macro_rules! list {
 
 /// Create a [Value::Dict] from key-value pairs (keys like [str!], values as [Value]s)
 ///
+/// If all keys are literals, duplicate keys are rejected at compile time. Otherwise, later entries
+/// replace earlier ones with the same key.
+///
 /// ```rust
 /// use bencode_minimal::*;
 /// use std::borrow::Cow;
@@ -83,8 +192,21 @@ macro_rules! list {
 ///     (Cow::Borrowed(b"name".as_ref()), Value::Str(Cow::Borrowed(b"John"))),
 /// ].into_iter().collect()));
 /// ```
+///
+/// ```rust,compile_fail
+/// use bencode_minimal::*;
+///
+/// let v = dict! { "a" => int!(1), b"a" => int!(2) };
+/// ```
 #[macro_export]
 macro_rules! dict {
+    ($($k:literal => $v:expr),* $(,)?) => {{
+        const _: () = assert!(
+            !bencode_minimal::private::has_duplicate_keys(&[$(bencode_minimal::private::KeyLit($k).bytes()),*]),
+            "duplicate key in dict!"
+        );
+        bencode_minimal::Value::Dict([$((bencode_minimal::IntoStr::into_str($k), $v)),*].into_iter().collect())
+    }};
     ($($k:expr => $v:expr),* $(,)?) => {
         bencode_minimal::Value::Dict([$((bencode_minimal::IntoStr::into_str($k), $v)),*].into_iter().collect())
     };
@@ -92,3 +214,31 @@ macro_rules! dict {
         bencode_minimal::Value::Dict(std::collections::BTreeMap::new())
     };
 }
+
+/// Assert that two [Value]s are equal and print a structural [diff] otherwise
+///
+/// Either side may also be an encoded buffer (`&[u8]`, `&[u8; N]` or [Vec]<[u8]>) which is decoded
+/// before comparison. On mismatch, only the differing paths are printed instead of both values.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let v = dict! { "age" => int!(42) };
+/// assert_bencode_eq!(v, b"d3:agei42ee");
+/// assert_bencode_eq!(v.encode(), v);
+/// ```
+///
+/// ```rust,should_panic
+/// use bencode_minimal::*;
+///
+/// // panics with:
+/// //   assertion `left == right` failed
+/// //     ~ age: 42 != 43
+/// assert_bencode_eq!(dict! { "age" => int!(42) }, dict! { "age" => int!(43) });
+/// ```
+#[macro_export]
+macro_rules! assert_bencode_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        bencode_minimal::assert_eq_diff(&$left, &$right)
+    };
+}
@@ -0,0 +1,822 @@
+//! Helpers for `.torrent` metainfo files (BEP 3 and extensions)
+//!
+//! The metainfo is handled as a plain [Value] tree. The functions in this module check and rewrite
+//! the well-known keys and leave everything else to the caller. [TorrentEditor] edits a file without
+//! touching the encoding of its info dictionary.
+use super::sha1::Sha1;
+use super::{DecodeError, DecodeOptions, Kind, List, Path, PathError, TryFromValue, Value, Verbatim};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+/// Top-level keys kept by [sanitize] by default
+pub const DEFAULT_KEYS: &[&[u8]] = &[
+    b"announce",
+    b"announce-list",
+    b"comment",
+    b"created by",
+    b"creation date",
+    b"encoding",
+    b"httpseeds",
+    b"info",
+    b"nodes",
+    b"piece layers",
+    b"signatures",
+    b"url-list",
+];
+
+/// Info dictionary keys kept by [sanitize] if it is allowed to touch the info dictionary
+pub const DEFAULT_INFO_KEYS: &[&[u8]] = &[
+    b"collections",
+    b"file tree",
+    b"files",
+    b"length",
+    b"meta version",
+    b"name",
+    b"originator",
+    b"piece length",
+    b"pieces",
+    b"private",
+    b"root hash",
+    b"similar",
+    b"update-url",
+];
+
+/// Keys of `files` entries kept by [sanitize] if it is allowed to touch the info dictionary
+pub const DEFAULT_FILE_KEYS: &[&[u8]] = &[b"length", b"md5sum", b"path"];
+
+/// Options for [sanitize]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SanitizeOptions {
+    /// Top-level keys to keep
+    pub keys: BTreeSet<Vec<u8>>,
+    /// Info dictionary keys to keep
+    ///
+    /// Stripping keys from the info dictionary changes the info hash and thereby the identity of the
+    /// torrent. With [None] (the default), the info dictionary is validated, but left untouched.
+    pub info_keys: Option<BTreeSet<Vec<u8>>>,
+    /// Keys of `files` entries to keep (see [Self::info_keys])
+    pub file_keys: Option<BTreeSet<Vec<u8>>>,
+}
+
+impl SanitizeOptions {
+    /// Also strip unknown keys from the info dictionary using [DEFAULT_INFO_KEYS] and [DEFAULT_FILE_KEYS]
+    pub fn strip_info(mut self) -> Self {
+        let set = |x: &[&[u8]]| x.iter().map(|k| k.to_vec()).collect();
+        self.info_keys = Some(set(DEFAULT_INFO_KEYS));
+        self.file_keys = Some(set(DEFAULT_FILE_KEYS));
+        self
+    }
+}
+
+impl Default for SanitizeOptions {
+    fn default() -> Self {
+        Self { keys: DEFAULT_KEYS.iter().map(|k| k.to_vec()).collect(), info_keys: None, file_keys: None }
+    }
+}
+
+/// Reason for a failed [sanitize]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SanitizeError {
+    /// A required key is missing
+    Missing(Path),
+    /// The value has an unexpected type
+    Invalid(Path),
+    /// A file name or path component is empty, `.` or `..`, or contains a path separator or NUL
+    UnsafePath(Path),
+}
+
+impl std::fmt::Display for SanitizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing(p) => write!(f, "missing key {}", p),
+            Self::Invalid(p) => write!(f, "invalid value at {}", p),
+            Self::UnsafePath(p) => write!(f, "unsafe path component at {}", p),
+        }
+    }
+}
+
+impl std::error::Error for SanitizeError {}
+
+/// Return a cleaned copy of a metainfo suitable for re-publication
+///
+/// - Top-level keys not in [SanitizeOptions::keys] are removed (and info dictionary keys if configured).
+/// - `announce-list` is normalized: URLs are trimmed, empty and duplicate URLs as well as empty tiers
+///   are removed and an empty list is dropped. A missing `announce` is set to the first tracker.
+/// - The `name`, all `files` paths and the `file tree` (BEP 52) are checked to not escape the
+///   download directory.
+///
+/// ```rust
+/// use bencode_minimal::*;
+/// use bencode_minimal::metainfo::{sanitize, SanitizeOptions};
+///
+/// let v = dict! {
+///     "announce-list" => list![list![str!(" udp://a "), str!("udp://b")], list![str!("udp://a")]],
+///     "info" => dict! { "name" => str!("x"), "piece length" => int!(16384), "pieces" => str!("") },
+///     "x-tracking-id" => str!("1234"),
+/// };
+/// let s = sanitize(&v, &SanitizeOptions::default()).unwrap();
+/// assert_eq!(s, dict! {
+///     "announce" => str!("udp://a"),
+///     "announce-list" => list![list![str!("udp://a"), str!("udp://b")]],
+///     "info" => dict! { "name" => str!("x"), "piece length" => int!(16384), "pieces" => str!("") },
+/// });
+/// ```
+pub fn sanitize<'a>(metainfo: &Value<'a>, opts: &SanitizeOptions) -> Result<Value<'a>, SanitizeError> {
+    let root = Path::new();
+    let Value::Dict(m) = metainfo else {
+        return Err(SanitizeError::Invalid(root));
+    };
+    let mut m = m.clone();
+    m.retain(|k, _| opts.keys.contains(k.as_ref()));
+
+    let path = root.key(b"info");
+    let info = m.get_mut(b"info".as_ref()).ok_or_else(|| SanitizeError::Missing(path.clone()))?;
+    let Value::Dict(info) = info else {
+        return Err(SanitizeError::Invalid(path));
+    };
+    if let Some(keys) = &opts.info_keys {
+        info.retain(|k, _| keys.contains(k.as_ref()));
+    }
+    check_component(info.get(b"name".as_ref()), &path.key(b"name"))?;
+    if let Some(files) = info.get_mut(b"files".as_ref()) {
+        let path = path.key(b"files");
+        let Value::List(files) = files else {
+            return Err(SanitizeError::Invalid(path));
+        };
+        for (i, file) in files.iter_mut().enumerate() {
+            let path = path.index(i);
+            let Value::Dict(file) = file else {
+                return Err(SanitizeError::Invalid(path));
+            };
+            if let Some(keys) = &opts.file_keys {
+                file.retain(|k, _| keys.contains(k.as_ref()));
+            }
+            let path = path.key(b"path");
+            match file.get(b"path".as_ref()) {
+                Some(Value::List(l)) if !l.is_empty() => {
+                    for (j, c) in l.iter().enumerate() {
+                        check_component(Some(c), &path.index(j))?;
+                    }
+                }
+                Some(_) => return Err(SanitizeError::Invalid(path)),
+                None => return Err(SanitizeError::Missing(path)),
+            }
+        }
+    }
+    if let Some(tree) = info.get(b"file tree".as_ref()) {
+        check_file_tree(tree, path.key(b"file tree"))?;
+    }
+
+    if let Some(list) = m.get(b"announce-list".as_ref()) {
+        let tiers = normalize_announce_list(list, &root.key(b"announce-list"))?;
+        if let Some(url) = tiers.first().and_then(|t| t.first()) {
+            m.entry(Cow::Borrowed(b"announce".as_ref())).or_insert_with(|| Value::Str(url.clone()));
+        }
+        if tiers.is_empty() {
+            m.remove(b"announce-list".as_ref());
+        } else {
+            let tiers = tiers.into_iter().map(|t| Value::List(t.into_iter().map(Value::Str).collect()));
+            m.insert(Cow::Borrowed(b"announce-list"), Value::List(tiers.collect()));
+        }
+    }
+    if let Some(url) = m.get_mut(b"announce".as_ref()) {
+        let Value::Str(url) = url else {
+            return Err(SanitizeError::Invalid(root.key(b"announce")));
+        };
+        *url = trim(url);
+    }
+    Ok(Value::Dict(m))
+}
+
+/// How the pieces of a torrent are verified
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PieceHashes<'v> {
+    /// The SHA-1 hashes of all pieces (`pieces`, BEP 3)
+    Pieces(&'v [[u8; 20]]),
+    /// The root of a Merkle hash tree over the pieces (`root hash`, BEP 30)
+    ///
+    /// The piece hashes are not part of the metainfo, but sent along with the pieces.
+    MerkleRoot(&'v [u8; 20]),
+}
+
+/// Get the piece hashes from the info dictionary of a metainfo
+///
+/// Returns [None] if the info dictionary contains neither `pieces` nor `root hash`, both, or a
+/// value of the wrong size.
+///
+/// ```rust
+/// use bencode_minimal::*;
+/// use bencode_minimal::metainfo::{piece_hashes, PieceHashes};
+///
+/// let v = dict! { "info" => dict! { "root hash" => str!(&[1; 20]) } };
+/// assert_eq!(piece_hashes(&v), Some(PieceHashes::MerkleRoot(&[1; 20])));
+///
+/// let v = dict! { "info" => dict! { "pieces" => str!(&[2; 40]) } };
+/// assert_eq!(piece_hashes(&v), Some(PieceHashes::Pieces(&[[2; 20], [2; 20]])));
+/// ```
+pub fn piece_hashes<'v>(metainfo: &'v Value<'_>) -> Option<PieceHashes<'v>> {
+    let info = metainfo.get::<&Value>("info")?;
+    match (info.get::<&[u8]>("pieces"), info.get::<&[u8]>("root hash")) {
+        (Some(p), None) => {
+            let (chunks, rest) = p.as_chunks();
+            rest.is_empty().then_some(PieceHashes::Pieces(chunks))
+        }
+        (None, Some(r)) => r.try_into().ok().map(PieceHashes::MerkleRoot),
+        _ => None,
+    }
+}
+
+/// A publisher's signature of a torrent (BEP 35)
+///
+/// Signatures are stored by signer in the top-level `signatures` dictionary, so they do not
+/// affect the info hash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Signature<'v> {
+    /// Identifies the signer, like a domain name
+    pub signer: &'v [u8],
+    pub signature: &'v [u8],
+    /// DER-encoded X.509 certificate of the signer
+    pub certificate: Option<&'v [u8]>,
+    /// Additional signed data
+    pub info: Option<&'v Value<'v>>,
+}
+
+impl Signature<'_> {
+    /// The data covered by the signature given the encoded info dictionary of the torrent
+    ///
+    /// This is the info dictionary followed by the encoded [Self::info] if present. Pass the info
+    /// dictionary as found in the file (e.g. via [LazyDict::raw](super::LazyDict::raw)) if it may not be
+    /// canonical.
+    pub fn signed_data(&self, info: &[u8]) -> Vec<u8> {
+        let mut data = info.to_vec();
+        if let Some(i) = self.info {
+            data.extend(i.encode());
+        }
+        data
+    }
+}
+
+/// Get all signatures of a metainfo (see [Signature])
+///
+/// ```rust
+/// use bencode_minimal::*;
+/// use bencode_minimal::metainfo::{add_signature, signatures, verify_signatures, Signature};
+///
+/// let mut v = dict! { "info" => dict! { "name" => str!("a") } };
+/// let sign = |data: &[u8]| data.iter().rev().copied().collect::<Vec<_>>(); // Not a real signature scheme
+/// let signature = sign(b"d4:name1:ae");
+/// add_signature(&mut v, &Signature { signer: b"example.com", signature: &signature, certificate: None, info: None })
+///     .unwrap();
+///
+/// assert_eq!(signatures(&v).unwrap()[0].signer, b"example.com");
+/// let valid = verify_signatures(&v, |s, data| s.signature == sign(data)).unwrap();
+/// assert_eq!(valid, [b"example.com"]);
+/// ```
+pub fn signatures<'v>(metainfo: &'v Value<'_>) -> Result<Vec<Signature<'v>>, SanitizeError> {
+    let path = Path::new().key(b"signatures");
+    let Some(sigs) = metainfo.get_path(&path) else {
+        return Ok(Vec::new());
+    };
+    let Value::Dict(sigs) = sigs else {
+        return Err(SanitizeError::Invalid(path));
+    };
+    let mut r = Vec::with_capacity(sigs.len());
+    for (signer, sig) in sigs {
+        let path = path.key(signer);
+        let invalid = |k: &[u8]| SanitizeError::Invalid(path.key(k));
+        let Value::Dict(_) = sig else {
+            return Err(SanitizeError::Invalid(path));
+        };
+        let signature = match sig.get_path("signature") {
+            Some(s) => s.try_into::<&[u8]>().ok_or_else(|| invalid(b"signature"))?,
+            None => return Err(SanitizeError::Missing(path.key(b"signature"))),
+        };
+        let certificate = match sig.get_path("certificate") {
+            Some(c) => Some(c.try_into::<&[u8]>().ok_or_else(|| invalid(b"certificate"))?),
+            None => None,
+        };
+        let info = match sig.get_path("info") {
+            Some(i @ Value::Dict(_)) => Some(i),
+            Some(_) => return Err(invalid(b"info")),
+            None => None,
+        };
+        r.push(Signature { signer, signature, certificate, info });
+    }
+    Ok(r)
+}
+
+/// Add or replace the signature of `sig.signer`
+pub fn add_signature(metainfo: &mut Value<'_>, sig: &Signature<'_>) -> Result<(), PathError> {
+    let owned = |x: &[u8]| Value::Str(Cow::Owned(x.to_vec()));
+    let mut d = Value::empty_dict();
+    d.insert(b"signature".as_ref(), owned(sig.signature));
+    if let Some(c) = sig.certificate {
+        d.insert(b"certificate".as_ref(), owned(c));
+    }
+    if let Some(i) = sig.info {
+        d.insert(b"info".as_ref(), i.clone().into_owned());
+    }
+    metainfo.set_path(Path::new().key(b"signatures").key(sig.signer), d).map(|_| ())
+}
+
+/// Check all signatures with `verify` and return the signers whose signatures are valid
+///
+/// `verify` is called with each signature and the data it covers (see [Signature::signed_data]),
+/// based on the canonical encoding of the info dictionary. Fails if the info dictionary is missing
+/// or a signature is malformed.
+pub fn verify_signatures<'v>(
+    metainfo: &'v Value<'_>,
+    mut verify: impl FnMut(&Signature<'v>, &[u8]) -> bool,
+) -> Result<Vec<&'v [u8]>, SanitizeError> {
+    let sigs = signatures(metainfo)?;
+    let info = metainfo.get_path("info").ok_or_else(|| SanitizeError::Missing(Path::new().key(b"info")))?;
+    let info = info.encode();
+    Ok(sigs.into_iter().filter(|s| verify(s, &s.signed_data(&info))).map(|s| s.signer).collect())
+}
+
+/// The feed URL announcing updated versions of this torrent (BEP 39 `update-url`)
+///
+/// The key lives in the info dictionary, so only the original publisher can set it without changing
+/// the info hash. Returns [None] if it is missing or not UTF-8.
+///
+/// ```rust
+/// use bencode_minimal::*;
+/// use bencode_minimal::metainfo::{originator, set_originator, set_update_url, update_url};
+///
+/// let mut v = dict! { "info" => dict! { "name" => str!("a") } };
+/// set_update_url(&mut v, "https://example.com/feed.xml").unwrap();
+/// set_originator(&mut v, "example.com").unwrap();
+/// assert_eq!(update_url(&v), Some("https://example.com/feed.xml"));
+/// assert_eq!(originator(&v), Some("example.com"));
+/// ```
+pub fn update_url<'v>(metainfo: &'v Value<'_>) -> Option<&'v str> {
+    metainfo.get_path("info.update-url")?.try_into()
+}
+
+/// Set the BEP 39 `update-url` (this changes the info hash)
+pub fn set_update_url(metainfo: &mut Value<'_>, url: &str) -> Result<(), PathError> {
+    metainfo.set_path("info.update-url", Value::Str(Cow::Owned(url.as_bytes().to_vec()))).map(|_| ())
+}
+
+/// The publisher whose feed may provide updates (BEP 39 `originator`, see [update_url])
+pub fn originator<'v>(metainfo: &'v Value<'_>) -> Option<&'v str> {
+    metainfo.get_path("info.originator")?.try_into()
+}
+
+/// Set the BEP 39 `originator` (this changes the info hash)
+pub fn set_originator(metainfo: &mut Value<'_>, originator: &str) -> Result<(), PathError> {
+    metainfo.set_path("info.originator", Value::Str(Cow::Owned(originator.as_bytes().to_vec()))).map(|_| ())
+}
+
+/// Where a key that may appear both inside and outside the info dictionary is placed
+///
+/// Keys in the info dictionary are covered by the info hash, so they are as trustworthy as the
+/// torrent itself, but changing them changes its identity. Keys at the top level can be added or
+/// changed by anyone passing the file on and should only be treated as hints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Placement {
+    Info,
+    TopLevel,
+}
+
+impl Placement {
+    fn path(self, key: &[u8]) -> Path {
+        match self {
+            Self::Info => Path::new().key(b"info").key(key),
+            Self::TopLevel => Path::new().key(key),
+        }
+    }
+}
+
+/// The info hashes of similar torrents (BEP 38 `similar`) at the given placement
+///
+/// Returns [None] if the key is missing or not a list of 20 byte strings.
+///
+/// ```rust
+/// use bencode_minimal::*;
+/// use bencode_minimal::metainfo::{collections, set_similar, similar, Placement};
+///
+/// let mut v = dict! { "info" => dict! { "collections" => list![str!("linux")] } };
+/// set_similar(&mut v, Placement::TopLevel, &[[1; 20]]).unwrap();
+/// assert_eq!(similar(&v, Placement::TopLevel), Some(vec![[1; 20]]));
+/// assert_eq!(similar(&v, Placement::Info), None);
+/// assert_eq!(collections(&v, Placement::Info), Some(vec!["linux"]));
+/// ```
+pub fn similar(metainfo: &Value<'_>, placement: Placement) -> Option<Vec<[u8; 20]>> {
+    let list = metainfo.get_path(placement.path(b"similar"))?.try_into::<&List>()?;
+    list.iter().map(<[u8; 20] as TryFromValue>::try_from).collect()
+}
+
+/// Set the BEP 38 `similar` info hashes at the given placement
+///
+/// Note that [Placement::Info] changes the info hash. Fails if `info` is not a dictionary.
+pub fn set_similar(metainfo: &mut Value<'_>, placement: Placement, hashes: &[[u8; 20]]) -> Result<(), PathError> {
+    let list = hashes.iter().map(|h| Value::Str(Cow::Owned(h.to_vec()))).collect();
+    metainfo.set_path(placement.path(b"similar"), Value::List(list)).map(|_| ())
+}
+
+/// The names of collections this torrent belongs to (BEP 38 `collections`) at the given placement
+///
+/// Returns [None] if the key is missing or not a list of UTF-8 strings.
+pub fn collections<'v>(metainfo: &'v Value<'_>, placement: Placement) -> Option<Vec<&'v str>> {
+    let list = metainfo.get_path(placement.path(b"collections"))?.try_into::<&List>()?;
+    list.iter().map(<&str as TryFromValue>::try_from).collect()
+}
+
+/// Set the BEP 38 `collections` at the given placement (see [set_similar])
+pub fn set_collections(metainfo: &mut Value<'_>, placement: Placement, names: &[&str]) -> Result<(), PathError> {
+    let list = names.iter().map(|n| Value::Str(Cow::Owned(n.as_bytes().to_vec()))).collect();
+    metainfo.set_path(placement.path(b"collections"), Value::List(list)).map(|_| ())
+}
+
+/// An editor for the top-level keys of a `.torrent` file that never changes its info hash
+///
+/// The file is decoded as [Verbatim], so the `info` dictionary is copied byte for byte on encoding,
+/// even if it was not canonically encoded in the first place. Only [Self::set_private] modifies the
+/// info dictionary and thereby the info hash.
+///
+/// ```rust
+/// use bencode_minimal::*;
+/// use bencode_minimal::metainfo::TorrentEditor;
+///
+/// let buf = b"d8:announce5:udp:a4:infod6:lengthi01e4:name1:aee";
+/// let mut t = TorrentEditor::decode(buf, &DecodeOptions::default()).unwrap();
+/// let hash = t.info_hash();
+/// t.set_trackers(&[&["udp:b"], &["udp:c", "udp:d"]]);
+/// t.set_comment(Some("edited"));
+/// t.set_web_seeds(&["https://example.com/a"]);
+/// assert_eq!(t.trackers(), [vec!["udp:b"], vec!["udp:c", "udp:d"]]);
+/// assert_eq!(t.info_hash(), hash);
+/// assert_eq!(
+///     t.encode(),
+///     b"d8:announce5:udp:b13:announce-listll5:udp:bel5:udp:c5:udp:dee7:comment6:edited\
+///       4:infod6:lengthi01e4:name1:ae8:url-listl21:https://example.com/aee"
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct TorrentEditor<'a> {
+    root: Verbatim<'a>,
+}
+
+impl<'a> TorrentEditor<'a> {
+    /// Decode a metainfo file
+    ///
+    /// Fails with [DecodeError::Invalid] if it is not a dictionary with an `info` dictionary.
+    pub fn decode(buf: &'a [u8], opts: &DecodeOptions) -> Result<Self, DecodeError> {
+        let root = Verbatim::decode(buf, opts)?;
+        match root.get(b"info").map(Verbatim::kind) {
+            Some(Kind::Dict) => Ok(Self { root }),
+            _ => Err(DecodeError::Invalid),
+        }
+    }
+
+    /// The SHA-1 hash of the original `info` dictionary bytes (BEP 3 info hash)
+    pub fn info_hash(&self) -> [u8; 20] {
+        let mut h = Sha1::new();
+        h.update(&self.info().encode());
+        h.finish()
+    }
+
+    /// The tracker tiers from `announce-list` or else `announce` (BEP 12)
+    ///
+    /// Entries that are not UTF-8 strings are skipped.
+    pub fn trackers(&self) -> Vec<Vec<String>> {
+        if let Some(Value::List(tiers)) = &self.top(b"announce-list") {
+            let tier = |t: &Value| match t {
+                Value::List(t) => t.iter().filter_map(string).collect(),
+                _ => Vec::new(),
+            };
+            return tiers.iter().map(tier).filter(|t: &Vec<_>| !t.is_empty()).collect();
+        }
+        self.top(b"announce").as_ref().and_then(string).map(|url| vec![vec![url]]).unwrap_or_default()
+    }
+
+    /// Replace all trackers, setting `announce` to the first one
+    ///
+    /// Empty tiers are dropped. Without any trackers, both `announce` and `announce-list` are removed.
+    pub fn set_trackers(&mut self, tiers: &[&[&str]]) {
+        let tiers: Vec<_> = tiers.iter().filter(|t| !t.is_empty()).collect();
+        let Some(first) = tiers.first() else {
+            self.root.remove(b"announce");
+            self.root.remove(b"announce-list");
+            return;
+        };
+        self.root.insert(b"announce", owned(first[0]));
+        let list = tiers.iter().map(|t| Value::List(t.iter().map(|url| owned(url)).collect())).collect();
+        self.root.insert(b"announce-list", Value::List(list));
+    }
+
+    pub fn comment(&self) -> Option<String> {
+        self.top(b"comment").as_ref().and_then(string)
+    }
+
+    /// Set or remove the `comment`
+    pub fn set_comment(&mut self, comment: Option<&str>) {
+        match comment {
+            Some(c) => self.root.insert(b"comment", owned(c)),
+            None => self.root.remove(b"comment"),
+        };
+    }
+
+    /// The web seed URLs from `url-list` (BEP 19), which may be a single string or a list
+    pub fn web_seeds(&self) -> Vec<String> {
+        match &self.top(b"url-list") {
+            Some(Value::List(l)) => l.iter().filter_map(string).collect(),
+            Some(s) => string(s).into_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Replace the web seeds, removing `url-list` if `urls` is empty
+    pub fn set_web_seeds(&mut self, urls: &[&str]) {
+        if urls.is_empty() {
+            self.root.remove(b"url-list");
+        } else {
+            self.root.insert(b"url-list", Value::List(urls.iter().map(|url| owned(url)).collect()));
+        }
+    }
+
+    /// Whether the `private` flag is set in the info dictionary (BEP 27)
+    pub fn is_private(&self) -> bool {
+        self.info().get(b"private").map(Verbatim::to_value) == Some(Value::Int(1))
+    }
+
+    /// Set or clear the `private` flag
+    ///
+    /// This is the only modification that changes the info hash. The other info dictionary entries
+    /// are still copied verbatim. Nothing is changed if the flag already has the requested state.
+    pub fn set_private(&mut self, private: bool) {
+        if self.is_private() == private {
+            return;
+        }
+        let info = self.root.get_mut(b"info").expect("checked on decode");
+        if private {
+            info.insert(b"private", Value::Int(1));
+        } else {
+            info.remove(b"private");
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        self.root.encode()
+    }
+
+    /// The underlying [Verbatim] value for modifications not covered here
+    pub fn into_inner(self) -> Verbatim<'a> {
+        self.root
+    }
+
+    fn info(&self) -> &Verbatim<'a> {
+        self.root.get(b"info").expect("checked on decode")
+    }
+
+    fn top(&self, key: &[u8]) -> Option<Value<'a>> {
+        self.root.get(key).map(Verbatim::to_value)
+    }
+}
+
+fn string(v: &Value) -> Option<String> {
+    v.try_into::<&str>().map(str::to_owned)
+}
+
+fn owned(s: &str) -> Value<'static> {
+    Value::Str(Cow::Owned(s.as_bytes().to_vec()))
+}
+
+fn check_component(v: Option<&Value>, path: &Path) -> Result<(), SanitizeError> {
+    let Some(v) = v else {
+        return Err(SanitizeError::Missing(path.clone()));
+    };
+    let Value::Str(s) = v else {
+        return Err(SanitizeError::Invalid(path.clone()));
+    };
+    check_name(s, path)
+}
+
+fn check_name(s: &[u8], path: &Path) -> Result<(), SanitizeError> {
+    if s.is_empty() || s == b"." || s == b".." || s.iter().any(|c| matches!(c, b'/' | b'\\' | 0)) {
+        return Err(SanitizeError::UnsafePath(path.clone()));
+    }
+    Ok(())
+}
+
+/// Check the names in a BEP 52 `file tree`, whose directories map names to directories or files
+/// and whose files are dictionaries with an empty key for the file's properties
+fn check_file_tree(tree: &Value, path: Path) -> Result<(), SanitizeError> {
+    let mut stack = vec![(tree, path)];
+    while let Some((v, path)) = stack.pop() {
+        let Value::Dict(d) = v else {
+            return Err(SanitizeError::Invalid(path));
+        };
+        for (k, v) in d.iter().filter(|(k, _)| !k.is_empty()) {
+            let path = path.key(k);
+            check_name(k, &path)?;
+            stack.push((v, path));
+        }
+    }
+    Ok(())
+}
+
+fn normalize_announce_list<'a>(list: &Value<'a>, path: &Path) -> Result<Vec<Vec<Cow<'a, [u8]>>>, SanitizeError> {
+    let Value::List(list) = list else {
+        return Err(SanitizeError::Invalid(path.clone()));
+    };
+    let mut seen = BTreeSet::new();
+    let mut tiers = Vec::new();
+    for (i, tier) in list.iter().enumerate() {
+        let Value::List(tier) = tier else {
+            return Err(SanitizeError::Invalid(path.index(i)));
+        };
+        let mut urls = Vec::new();
+        for (j, url) in tier.iter().enumerate() {
+            let Value::Str(url) = url else {
+                return Err(SanitizeError::Invalid(path.index(i).index(j)));
+            };
+            let trimmed = url.trim_ascii();
+            if trimmed.is_empty() || !seen.insert(trimmed) {
+                continue;
+            }
+            urls.push(trim(url));
+        }
+        if !urls.is_empty() {
+            tiers.push(urls);
+        }
+    }
+    Ok(tiers)
+}
+
+fn trim<'a>(s: &Cow<'a, [u8]>) -> Cow<'a, [u8]> {
+    match s {
+        Cow::Borrowed(x) => Cow::Borrowed(x.trim_ascii()),
+        Cow::Owned(x) => Cow::Owned(x.trim_ascii().to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(x: &str) -> Value<'_> {
+        Value::Str(Cow::Borrowed(x.as_bytes()))
+    }
+
+    fn d<'a>(entries: Vec<(&'a str, Value<'a>)>) -> Value<'a> {
+        Value::Dict(entries.into_iter().map(|(k, v)| (Cow::Borrowed(k.as_bytes()), v)).collect())
+    }
+
+    fn files(path: Vec<Value<'_>>) -> Value<'_> {
+        d(vec![(
+            "info",
+            d(vec![
+                ("name", s("dir")),
+                ("x-extra", Value::Int(1)),
+                ("files", Value::List(vec![d(vec![("length", Value::Int(1)), ("path", Value::List(path))])])),
+            ]),
+        )])
+    }
+
+    #[test]
+    fn test_unsafe_paths() {
+        let opts = SanitizeOptions::default();
+        assert!(sanitize(&files(vec![s("a"), s("b")]), &opts).is_ok());
+        for c in ["", ".", "..", "a/b", "a\\b", "a\0"] {
+            let e = sanitize(&files(vec![s("a"), s(c)]), &opts).unwrap_err();
+            assert_eq!(e.to_string(), "unsafe path component at info.files[0].path[1]");
+        }
+        let e = sanitize(&files(vec![]), &opts).unwrap_err();
+        assert_eq!(e, SanitizeError::Invalid(Path::new().key(b"info").key(b"files").index(0).key(b"path")));
+        let e = sanitize(&d(vec![("info", d(vec![]))]), &opts).unwrap_err();
+        assert_eq!(e.to_string(), "missing key info.name");
+    }
+
+    #[test]
+    fn test_unsafe_file_tree() {
+        let file = || d(vec![("", d(vec![("length", Value::Int(1))]))]);
+        let tree = |name, f| {
+            let info = d(vec![("name", s("dir")), ("file tree", d(vec![("a", d(vec![(name, f)]))]))]);
+            d(vec![("info", info)])
+        };
+        let opts = SanitizeOptions::default();
+        assert!(sanitize(&tree("b", file()), &opts).is_ok());
+        let path = Path::new().key(b"info").key(b"file tree").key(b"a");
+        for c in [".", "..", "a/b", "a\\b", "a\0"] {
+            let e = sanitize(&tree(c, file()), &opts).unwrap_err();
+            assert_eq!(e, SanitizeError::UnsafePath(path.key(c.as_bytes())));
+        }
+        let e = sanitize(&tree("b", Value::Int(1)), &opts).unwrap_err();
+        assert_eq!(e, SanitizeError::Invalid(path.key(b"b")));
+    }
+
+    #[test]
+    fn test_strip_info() {
+        let v = files(vec![s("a")]);
+        let r = sanitize(&v, &SanitizeOptions::default()).unwrap();
+        assert_eq!(r, v);
+        let r = sanitize(&v, &SanitizeOptions::default().strip_info()).unwrap();
+        assert_eq!(r.get::<&Value>("info").unwrap().get::<i64>("x-extra"), None);
+    }
+
+    #[test]
+    fn test_announce_list() {
+        let v = d(vec![
+            ("announce", s(" udp://x ")),
+            ("announce-list", Value::List(vec![Value::List(vec![s(""), s(" ")]), Value::List(vec![])])),
+            ("info", d(vec![("name", s("a"))])),
+        ]);
+        let r = sanitize(&v, &SanitizeOptions::default()).unwrap();
+        assert_eq!(r, d(vec![("announce", s("udp://x")), ("info", d(vec![("name", s("a"))]))]));
+        let v = d(vec![("announce-list", Value::List(vec![s("udp://x")])), ("info", d(vec![("name", s("a"))]))]);
+        let e = sanitize(&v, &SanitizeOptions::default()).unwrap_err();
+        assert_eq!(e.to_string(), "invalid value at announce-list[0]");
+    }
+
+    #[test]
+    fn test_similar_and_collections() {
+        let mut v = d(vec![("info", d(vec![("name", s("a"))])), ("similar", Value::List(vec![s("short")]))]);
+        assert_eq!(similar(&v, Placement::TopLevel), None);
+        set_similar(&mut v, Placement::Info, &[[7; 20], [8; 20]]).unwrap();
+        assert_eq!(similar(&v, Placement::Info), Some(vec![[7; 20], [8; 20]]));
+        set_collections(&mut v, Placement::TopLevel, &["x", "y"]).unwrap();
+        assert_eq!(collections(&v, Placement::TopLevel), Some(vec!["x", "y"]));
+        assert_eq!(collections(&v, Placement::Info), None);
+        let mut v = d(vec![("info", s("a"))]);
+        assert!(set_similar(&mut v, Placement::Info, &[]).is_err());
+    }
+
+    #[test]
+    fn test_piece_hashes() {
+        let info = |e| d(vec![("info", d(e))]);
+        assert_eq!(piece_hashes(&info(vec![("pieces", s(""))])), Some(PieceHashes::Pieces(&[])));
+        assert_eq!(piece_hashes(&info(vec![("pieces", s("x"))])), None);
+        assert_eq!(piece_hashes(&info(vec![("root hash", s("x"))])), None);
+        assert_eq!(piece_hashes(&info(vec![("pieces", s("")), ("root hash", s("01234567890123456789"))])), None);
+        assert_eq!(piece_hashes(&info(vec![])), None);
+        let v = info(vec![("name", s("a")), ("root hash", s("01234567890123456789"))]);
+        let r = sanitize(&v, &SanitizeOptions::default().strip_info()).unwrap();
+        assert_eq!(piece_hashes(&r), Some(PieceHashes::MerkleRoot(b"01234567890123456789")));
+    }
+
+    #[test]
+    fn test_signatures() {
+        let extra = d(vec![("expires", Value::Int(1))]);
+        let mut v = d(vec![("info", d(vec![("name", s("a"))]))]);
+        assert_eq!(signatures(&v), Ok(vec![]));
+        let sig = Signature { signer: b"a", signature: b"x", certificate: Some(b"cert"), info: Some(&extra) };
+        add_signature(&mut v, &sig).unwrap();
+        add_signature(&mut v, &Signature { signer: b"b", signature: b"y", certificate: None, info: None }).unwrap();
+        let sigs = signatures(&v).unwrap();
+        assert_eq!(sigs[0], sig);
+        assert_eq!(sigs[0].signed_data(b"d4:name1:ae"), b"d4:name1:aed7:expiresi1ee");
+        let mut seen = Vec::new();
+        let valid = verify_signatures(&v, |s, data| {
+            seen.push(data.to_vec());
+            s.signer == b"b"
+        });
+        assert_eq!(valid, Ok(vec![b"b".as_ref()]));
+        assert_eq!(seen, [b"d4:name1:aed7:expiresi1ee".to_vec(), b"d4:name1:ae".to_vec()]);
+
+        v.set_path("signatures.b.certificate", Value::Int(1)).unwrap();
+        assert_eq!(signatures(&v).unwrap_err().to_string(), "invalid value at signatures.b.certificate");
+        v.remove_path("signatures.b.signature");
+        assert_eq!(signatures(&v).unwrap_err().to_string(), "missing key signatures.b.signature");
+    }
+
+    #[test]
+    fn test_update_feed() {
+        let mut v = d(vec![("info", d(vec![("name", s("a")), ("update-url", Value::Int(1))]))]);
+        assert_eq!(update_url(&v), None);
+        set_update_url(&mut v, "http://x").unwrap();
+        let r = sanitize(&v, &SanitizeOptions::default().strip_info()).unwrap();
+        assert_eq!(update_url(&r), Some("http://x"));
+        assert_eq!(originator(&r), None);
+        assert!(set_originator(&mut d(vec![]), "x").is_ok());
+        assert!(set_originator(&mut d(vec![("info", Value::Int(1))]), "x").is_err());
+    }
+
+    #[test]
+    fn test_torrent_editor() {
+        let buf = b"d8:url-list3:x:y4:infod1:bi0e1:a0:ee";
+        let mut t = TorrentEditor::decode(buf, &DecodeOptions::default()).unwrap();
+        let hash = t.info_hash();
+        assert_eq!(t.trackers(), Vec::<Vec<String>>::new());
+        assert_eq!(t.web_seeds(), ["x:y"]);
+        assert_eq!(t.comment(), None);
+        assert!(!t.is_private());
+        t.set_private(false);
+        t.set_web_seeds(&[]);
+        t.set_trackers(&[&[], &["udp:a"]]);
+        assert_eq!(t.trackers(), [["udp:a"]]);
+        assert_eq!(t.info_hash(), hash);
+        assert_eq!(t.encode(), b"d8:announce5:udp:a13:announce-listll5:udp:aee4:infod1:bi0e1:a0:ee");
+        t.set_trackers(&[]);
+        t.set_private(true);
+        assert!(t.is_private());
+        assert_ne!(t.info_hash(), hash);
+        assert_eq!(t.encode(), b"d4:infod1:a0:1:bi0e7:privatei1eee");
+
+        for buf in [b"le".as_ref(), b"d4:infoi0ee", b"d4:name1:ae"] {
+            assert_eq!(TorrentEditor::decode(buf, &DecodeOptions::default()).unwrap_err(), DecodeError::Invalid);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use super::{DecodeOptions, Value};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io;
+
+/// A [Value] borrowing from a memory-mapped file (see [Value::decode_file])
+///
+/// The mapping lives as long as this guard. The value can only be borrowed from the guard. Use
+/// [Value::into_owned] on a clone if it needs to outlive the mapping.
+pub struct MappedValue {
+    // Declared before `map` so that it is dropped first.
+    value: Value<'static>,
+    map: Mmap,
+}
+
+impl MappedValue {
+    /// The raw content of the mapped file
+    pub fn bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    pub fn value(&self) -> &Value<'_> {
+        &self.value
+    }
+}
+
+impl std::fmt::Debug for MappedValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl Value<'_> {
+    /// Memory-map the file at `path` and decode a [Value] borrowing from the mapping
+    ///
+    /// This avoids reading and copying large files (e.g. torrent archives) into memory. Fails with
+    /// [io::ErrorKind::InvalidData] if the file content cannot be decoded with the given options.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated (by this or any other process) while the
+    /// returned [MappedValue] exists. Otherwise reading the value is undefined behaviour, e.g. a
+    /// crash or strings that are no longer valid UTF-8 after having been checked.
+    pub unsafe fn decode_file(path: impl AsRef<std::path::Path>, opts: &DecodeOptions) -> io::Result<MappedValue> {
+        let file = File::open(path)?;
+        // SAFETY: The caller guarantees that the file is not modified while it is mapped.
+        let map = unsafe { Mmap::map(&file)? };
+        // SAFETY: The mapped memory does not move when the guard is moved and outlives the value
+        // since the value is dropped first. The value is only handed out with the guard's lifetime.
+        let buf: &'static [u8] = unsafe { std::slice::from_raw_parts(map.as_ptr(), map.len()) };
+        let value = Value::decode_with(buf, opts).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(MappedValue { value, map })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_file() {
+        let path = std::env::temp_dir().join(format!("bencode-minimal-mmap-{}", std::process::id()));
+        std::fs::write(&path, b"d3:agei42ee").unwrap();
+        // SAFETY: The file is private to this test and not modified while mapped.
+        let v = unsafe { Value::decode_file(&path, &DecodeOptions::default()) };
+        std::fs::remove_file(&path).unwrap();
+        let v = v.unwrap();
+        assert_eq!(v.value().get::<i64>("age"), Some(42));
+        assert_eq!(v.bytes(), b"d3:agei42ee");
+    }
+
+    #[test]
+    fn test_decode_file_invalid() {
+        let path = std::env::temp_dir().join(format!("bencode-minimal-mmap-invalid-{}", std::process::id()));
+        std::fs::write(&path, b"d3:agei42e").unwrap();
+        // SAFETY: The file is private to this test and not modified while mapped.
+        let v = unsafe { Value::decode_file(&path, &DecodeOptions::default()) };
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(v.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,202 @@
+use super::decoder::{Decoder, DICT_ENTRY_SIZE, LIST_ITEM_SIZE};
+use super::encoder::Encoder;
+use super::value::Unpacked;
+use super::{Decodable, DecodeError, DecodeOptions, Encodable, Int, Kind, Str, Value};
+use indexmap::IndexMap;
+use std::borrow::Cow;
+
+/// A dictionary keeping its keys in the order they were inserted or decoded
+pub type OrderedDict<'a> = IndexMap<Str<'a>, OrderedValue<'a>>;
+
+/// Like [Value], but dictionaries keep the key order of the input
+///
+/// [Value] sorts dictionary keys, so tools that show documents as a peer sent them cannot tell
+/// whether the keys were in order. [OrderedValue] decodes dictionaries into an [OrderedDict] and
+/// encodes them in that order again, so unsorted input re-encodes with its keys as they were.
+/// Integers and string lengths are still normalized (see [Verbatim](super::Verbatim) for a
+/// byte-exact copy). Equality ignores the key order like that of [IndexMap].
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let buf = b"d1:yi1e1:ad1:ci0e1:bi0eee";
+/// let v = OrderedValue::decode(buf, &DecodeOptions::default()).unwrap();
+/// assert_eq!(v.encode(), buf);
+/// assert_eq!(format!("{:?}", v), r#"{"y": 1, "a": {"c": 0, "b": 0}}"#);
+///
+/// let v = Value::from(v);
+/// assert_eq!(v.encode(), b"d1:ad1:bi0e1:ci0ee1:yi1ee");
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub enum OrderedValue<'a> {
+    Int(Int),
+    Str(Str<'a>),
+    List(Vec<OrderedValue<'a>>),
+    Dict(OrderedDict<'a>),
+}
+
+impl<'a> OrderedValue<'a> {
+    /// Decode a value keeping the key order of its dictionaries
+    ///
+    /// Limits and errors are the same as with [Value::decode_with], with items accounted at the
+    /// size they take in a [Value]. Duplicate keys keep the position of their first occurrence.
+    pub fn decode(buf: &'a [u8], opts: &DecodeOptions) -> Result<Self, DecodeError> {
+        let mut d = Decoder::with_options(buf, opts);
+        let r = d.decode();
+        d.finish(r.as_ref().map(Self::kind).map_err(|e| *e));
+        r
+    }
+
+    /// Encode with the keys of each dictionary in their current order
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        Encoder::new(&mut buf).encode(self);
+        buf
+    }
+
+    pub fn kind(&self) -> Kind {
+        match self {
+            Self::Int(_) => Kind::Int,
+            Self::Str(_) => Kind::Str,
+            Self::List(_) => Kind::List,
+            Self::Dict(_) => Kind::Dict,
+        }
+    }
+}
+
+impl<'a> Decodable<'a> for OrderedValue<'a> {
+    fn decode(d: &mut Decoder<'a>) -> Result<Self, DecodeError> {
+        match d.peek_u8()? {
+            b'l' => {
+                d.begin_list()?;
+                let mut list = Vec::new();
+                while d.next_item()? {
+                    d.alloc(LIST_ITEM_SIZE)?;
+                    list.push(d.decode()?);
+                }
+                Ok(Self::List(list))
+            }
+            b'd' => {
+                d.begin_dict()?;
+                let mut dict = IndexMap::new();
+                while let Some(k) = d.next_key()? {
+                    d.alloc(DICT_ENTRY_SIZE)?;
+                    let v = d.decode()?;
+                    d.insert_entry(&mut dict, k, v)?;
+                }
+                Ok(Self::Dict(dict))
+            }
+            _ => match d.take_any()?.unpack() {
+                Unpacked::Int(i) => Ok(Self::Int(i)),
+                Unpacked::Str(s) => Ok(Self::Str(s)),
+                _ => unreachable!("containers are handled above"),
+            },
+        }
+    }
+}
+
+impl Encodable for OrderedValue<'_> {
+    fn encode(&self, e: &mut Encoder) {
+        match self {
+            Self::Int(i) => e.int(*i),
+            Self::Str(s) => e.str(s),
+            Self::List(l) => l.encode(e),
+            Self::Dict(d) => {
+                e.raw_u8(b'd');
+                for (k, v) in d {
+                    e.str(k);
+                    Encodable::encode(v, e);
+                }
+                e.raw_u8(b'e');
+            }
+        }
+    }
+}
+
+/// Sorts the keys of all dictionaries
+impl<'a> From<OrderedValue<'a>> for Value<'a> {
+    fn from(v: OrderedValue<'a>) -> Self {
+        match v {
+            OrderedValue::Int(i) => Value::Int(i),
+            OrderedValue::Str(s) => Value::Str(s),
+            OrderedValue::List(l) => Value::List(l.into_iter().map(Value::from).collect()),
+            OrderedValue::Dict(d) => Value::Dict(d.into_iter().map(|(k, v)| (k, v.into())).collect()),
+        }
+    }
+}
+
+/// Dictionaries are in sorted order and raw values are decoded
+impl<'a> From<Value<'a>> for OrderedValue<'a> {
+    fn from(v: Value<'a>) -> Self {
+        match v.unpack() {
+            Unpacked::Int(i) => Self::Int(i),
+            Unpacked::Str(s) => Self::Str(s),
+            Unpacked::List(l) => Self::List(l.into_iter().map(Self::from).collect()),
+            Unpacked::Dict(d) => Self::Dict(d.into_iter().map(|(k, v)| (k, v.into())).collect()),
+            Unpacked::Raw(s) => Value::unraw_lossy(&s).into(),
+        }
+    }
+}
+
+/// Shown like a [Value] with the entries of dictionaries in order
+impl std::fmt::Debug for OrderedValue<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(i) => std::fmt::Debug::fmt(&Value::Int(*i), f),
+            Self::Str(s) => std::fmt::Debug::fmt(&Value::Str(Cow::Borrowed(s)), f),
+            Self::List(l) => f.debug_list().entries(l).finish(),
+            Self::Dict(d) => f
+                .debug_map()
+                .entries(d.iter().map(|(k, v)| {
+                    let k = match std::str::from_utf8(k) {
+                        Ok(s) => s.to_string(),
+                        Err(_) => format!("{:?}", k),
+                    };
+                    (k, v)
+                }))
+                .finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DuplicateKeys;
+
+    #[test]
+    fn test_round_trip() {
+        let opts = DecodeOptions::default();
+        for buf in [b"i-3e".as_ref(), b"1:\xff", b"le", b"de", b"ld1:bi0e1:a0:ei1ee", b"d1:bd1:zi0e1:yi0ee1:a0:e"] {
+            let v = OrderedValue::decode(buf, &opts).unwrap();
+            assert_eq!(v.encode(), buf);
+            assert_eq!(Value::from(v.clone()), Value::decode_with(buf, &opts).unwrap());
+            assert_eq!(OrderedValue::from(Value::from(v.clone())), v);
+        }
+        let v = OrderedValue::decode(b"d1:bi0e1:ai0ee", &opts).unwrap();
+        let OrderedValue::Dict(d) = &v else { panic!("{:?}", v) };
+        assert_eq!(d.keys().map(|k| k.as_ref()).collect::<Vec<_>>(), [b"b", b"a"]);
+        assert_eq!(v.kind(), Kind::Dict);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let opts = DecodeOptions::default();
+        assert_eq!(OrderedValue::decode(b"d1:ai1e1:ai2ee", &opts), Err(DecodeError::Invalid));
+        assert_eq!(OrderedValue::decode(b"li1e", &opts), Err(DecodeError::Incomplete { needed: 1 }));
+        assert_eq!(
+            OrderedValue::decode(b"li1ei2ee", &opts.clone().with_max_allocs(1)),
+            Err(DecodeError::LimitExceeded)
+        );
+        assert_eq!(OrderedValue::decode(b"llee", &opts.clone().with_max_depth(1)), Err(DecodeError::LimitExceeded));
+
+        let opts = opts.with_duplicate_keys(DuplicateKeys::LastWins);
+        let v = OrderedValue::decode(b"d1:bi1e1:ai2e1:bi3ee", &opts).unwrap();
+        assert_eq!(v.encode(), b"d1:bi3e1:ai2ee");
+
+        let opts = DecodeOptions::default().with_strict(true);
+        assert_eq!(OrderedValue::decode(b"d1:bi1e1:ai1ee", &opts), Err(DecodeError::Invalid));
+        assert_eq!(OrderedValue::decode(b"d1:ad1:bi1e1:ai1eee", &opts), Err(DecodeError::Invalid));
+        assert!(OrderedValue::decode(b"d1:ad1:bi1ee1:bi1ee", &opts).is_ok());
+    }
+}
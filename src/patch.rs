@@ -0,0 +1,204 @@
+use super::path::Segment;
+use super::{diff, Difference, Encodable, Encoder, Path, PathError, Value};
+use std::borrow::Cow;
+
+/// A single step of a [Patch]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PatchOp<'a> {
+    /// Insert or replace the value at the path (see [Value::set_path])
+    Set(Path, Value<'a>),
+    /// Remove the value at the path (see [Value::remove_path])
+    Remove(Path),
+}
+
+/// A delta between two [Value]s that can be applied to the first to get the second
+///
+/// Patches are built from a [diff] and can themselves be encoded as Bencode, so a replica or an
+/// undo log can store and ship deltas instead of full documents. The encoding is a list of
+/// operations, each a dictionary with `op` (`set` or `remove`), `path` (a list of keys and
+/// indices) and for `set` the new `value`.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let a = dict! { "comment" => str!("old"), "nodes" => list![int!(1), int!(2)] };
+/// let b = dict! { "nodes" => list![int!(1)], "url-list" => str!("https://x") };
+///
+/// let p = Patch::new(&a, &b);
+/// let buf = p.encode();
+/// let p = Patch::from_value(&Value::decode(&buf, 100).unwrap()).unwrap();
+///
+/// let mut v = a.clone();
+/// p.apply(&mut v).unwrap();
+/// assert_eq!(v, b);
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Patch<'a> {
+    ops: Vec<PatchOp<'a>>,
+}
+
+impl<'a> Patch<'a> {
+    /// The patch turning `from` into `to`
+    pub fn new(from: &Value<'a>, to: &Value<'a>) -> Self {
+        let mut ops = Vec::new();
+        let mut removes = Vec::new();
+        for d in diff(from, to) {
+            match d {
+                Difference::Added(p, v) | Difference::Changed(p, _, v) => ops.push(PatchOp::Set(p, v.clone())),
+                Difference::Removed(p, _) => removes.push(PatchOp::Remove(p)),
+            }
+        }
+        // Trailing list items are reported in ascending order but must be removed from the back
+        ops.extend(removes.into_iter().rev());
+        Self { ops }
+    }
+
+    /// Create a patch from a list of operations
+    pub fn from_ops(ops: Vec<PatchOp<'a>>) -> Self {
+        Self { ops }
+    }
+
+    pub fn ops(&self) -> &[PatchOp<'a>] {
+        &self.ops
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Apply all operations to `target` in order
+    ///
+    /// Removing a value that does not exist is not an error. Fails if a value cannot be set, in
+    /// which case `target` may have been modified partially.
+    pub fn apply(&self, target: &mut Value<'_>) -> Result<(), PathError> {
+        for op in &self.ops {
+            match op {
+                PatchOp::Set(p, v) => {
+                    target.set_path(p, v.clone().into_owned())?;
+                }
+                PatchOp::Remove(p) => {
+                    target.remove_path(p);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse a patch from its Bencode representation
+    ///
+    /// Returns [None] if `value` is not a list of valid operations.
+    pub fn from_value(value: &Value<'a>) -> Option<Self> {
+        let Value::List(l) = value else {
+            return None;
+        };
+        let ops = l.iter().map(|op| {
+            let Value::Dict(d) = op else {
+                return None;
+            };
+            let path = path_from_value(d.get(b"path".as_ref())?)?;
+            match d.get(b"op".as_ref())?.try_into::<&[u8]>()? {
+                b"set" => Some(PatchOp::Set(path, d.get(b"value".as_ref())?.clone())),
+                b"remove" => Some(PatchOp::Remove(path)),
+                _ => None,
+            }
+        });
+        Some(Self { ops: ops.collect::<Option<_>>()? })
+    }
+
+    /// The Bencode representation of the patch (see [Self::from_value])
+    pub fn to_value(&self) -> Value<'_> {
+        let ops = self.ops.iter().map(|o| match o {
+            PatchOp::Set(p, v) => {
+                let mut d = op("set", p);
+                d.insert("value", v.clone());
+                d
+            }
+            PatchOp::Remove(p) => op("remove", p),
+        });
+        Value::List(ops.collect())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        self.to_value().encode()
+    }
+}
+
+impl Encodable for Patch<'_> {
+    fn encode(&self, e: &mut Encoder) {
+        e.value(&self.to_value());
+    }
+}
+
+fn op<'p>(name: &'static str, path: &'p Path) -> Value<'p> {
+    let mut d = Value::empty_dict();
+    d.insert("op", Value::from_str_static(name));
+    d.insert("path", path_to_value(path));
+    d
+}
+
+fn path_to_value(path: &Path) -> Value<'_> {
+    let segments = path.segments().iter().map(|s| match s {
+        Segment::Key(k) => Value::Str(Cow::Borrowed(k)),
+        Segment::Index(i) => Value::Int(*i as i64),
+    });
+    Value::List(segments.collect())
+}
+
+fn path_from_value(value: &Value<'_>) -> Option<Path> {
+    let Value::List(l) = value else {
+        return None;
+    };
+    let mut path = Path::new();
+    for s in l {
+        match s {
+            Value::Str(k) => path.push_key(k),
+            Value::Int(i) => path.push_index((*i).try_into().ok()?),
+            _ => return None,
+        }
+    }
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(items: &[i64]) -> Value<'static> {
+        Value::List(items.iter().map(|i| Value::Int(*i)).collect())
+    }
+
+    #[test]
+    fn test_list_shrink_and_grow() {
+        for (a, b) in [(&[1, 2, 3, 4][..], &[1][..]), (&[1], &[2, 3, 4]), (&[], &[1]), (&[1, 2], &[])] {
+            let (a, b) = (list(a), list(b));
+            let mut v = a.clone();
+            Patch::new(&a, &b).apply(&mut v).unwrap();
+            assert_eq!(v, b);
+        }
+    }
+
+    #[test]
+    fn test_encoding() {
+        let (a, b) = (list(&[1, 2]), Value::Int(3));
+        let p = Patch::new(&a, &b);
+        assert_eq!(p.encode(), b"ld2:op3:set4:pathle5:valuei3eee");
+        let mut root = Value::empty_dict();
+        root.insert(b"x".as_ref(), list(&[]));
+        let p = Patch::new(&root, &Value::empty_dict());
+        assert_eq!(p.ops(), [PatchOp::Remove(Path::new().key(b"x"))]);
+        assert_eq!(p.encode(), b"ld2:op6:remove4:pathl1:xeee");
+        assert_eq!(Patch::from_value(&Value::decode(&p.encode(), 100).unwrap()), Some(p));
+
+        for bad in
+            [b"i1e".as_ref(), b"ld2:op3:set4:pathleee", b"ld2:op4:move4:pathleee", b"ld2:op6:remove4:pathli-1eeee"]
+        {
+            assert_eq!(Patch::from_value(&Value::decode(bad, 100).unwrap()), None);
+        }
+    }
+
+    #[test]
+    fn test_apply_error() {
+        let p = Patch::from_ops(vec![PatchOp::Remove("a.b".into()), PatchOp::Set("[0]".into(), Value::Int(1))]);
+        assert_eq!(p.apply(&mut Value::Int(0)), Err(PathError::NotAContainer(Path::new())));
+    }
+}
@@ -0,0 +1,424 @@
+use super::{PathError, Value};
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// A single step of a [Path]: either a dictionary key or a list index
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Segment {
+    Key(Vec<u8>),
+    Index(usize),
+}
+
+/// A location within a [Value](super::Value) like `info.files[3].length`
+///
+/// Keys are separated by dots and list indices are written in brackets. The root is the empty path.
+/// Keys that are empty, not valid UTF-8 or contain any of `.[]"` are rendered in double quotes,
+/// where `"` and `\` are escaped with a backslash and bytes that are not UTF-8 are written as `\xff`.
+///
+/// Paths can be parsed from the same notation with [From<&str>](#impl-From%3C%26str%3E-for-Path),
+/// so rendering and parsing round trip. Unquoted keys are taken literally up to the next dot.
+///
+/// ```rust
+/// use bencode_minimal::Path;
+///
+/// let p = Path::new().key(b"a.b").index(1).key(b"\xff").key(b"c");
+/// assert_eq!(p.to_string(), r#""a.b"[1]."\xff".c"#);
+/// assert_eq!(Path::from(p.to_string().as_str()), p);
+/// ```
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Path(Vec<Segment>);
+
+impl Path {
+    /// The empty path pointing at the root value
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.0
+    }
+
+    pub fn push_key(&mut self, key: &[u8]) {
+        self.0.push(Segment::Key(key.to_vec()));
+    }
+
+    pub fn push_index(&mut self, index: usize) {
+        self.0.push(Segment::Index(index));
+    }
+
+    pub fn pop(&mut self) -> Option<Segment> {
+        self.0.pop()
+    }
+
+    /// Prepend a segment for errors that collect their path while propagating outwards
+    pub(crate) fn push_front(&mut self, segment: Segment) {
+        self.0.insert(0, segment);
+    }
+
+    /// Return a copy of this path extended by the given key
+    pub fn key(&self, key: &[u8]) -> Self {
+        let mut p = self.clone();
+        p.push_key(key);
+        p
+    }
+
+    /// Return a copy of this path extended by the given index
+    pub fn index(&self, index: usize) -> Self {
+        let mut p = self.clone();
+        p.push_index(index);
+        p
+    }
+}
+
+impl std::fmt::Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, s) in self.0.iter().enumerate() {
+            match s {
+                Segment::Index(n) => write!(f, "[{}]", n)?,
+                Segment::Key(k) => {
+                    if i > 0 {
+                        f.write_char('.')?;
+                    }
+                    match std::str::from_utf8(k) {
+                        Ok(s) if is_plain(s) => f.write_str(s)?,
+                        _ => write!(f, "{}", QuotedKey(k))?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<&str> for Path {
+    fn from(s: &str) -> Self {
+        let mut path = Self::new();
+        if s.is_empty() {
+            return path;
+        }
+        let mut rest = Some(s);
+        while let Some(part) = rest {
+            // A quoted key may contain dots, so the part only ends after the closing quote
+            let (quoted, tail) = match unquote(part) {
+                Some((key, tail)) => (Some(key), tail),
+                None => (None, part),
+            };
+            let part = match tail.split_once('.') {
+                Some((part, next)) => {
+                    rest = Some(next);
+                    part
+                }
+                None => {
+                    rest = None;
+                    tail
+                }
+            };
+            // Trailing `[n]` groups are indices, anything else is part of the key
+            let mut key = part;
+            let mut indices = Vec::new();
+            while let Some(rest) = key.strip_suffix(']') {
+                let Some((k, n)) = rest.rsplit_once('[') else { break };
+                let Ok(n) = n.parse() else { break };
+                indices.push(n);
+                key = k;
+            }
+            match quoted {
+                Some(q) => path.push_key(&[q.as_slice(), key.as_bytes()].concat()),
+                None if !key.is_empty() || indices.is_empty() => path.push_key(key.as_bytes()),
+                None => (),
+            }
+            for n in indices.into_iter().rev() {
+                path.push_index(n);
+            }
+        }
+        path
+    }
+}
+
+/// Whether a key can be rendered without quotes
+pub(crate) fn is_plain(key: &str) -> bool {
+    !key.is_empty() && !key.contains(['.', '[', ']', '"'])
+}
+
+/// Renders a key in the quoted form of [Path]
+pub(crate) struct QuotedKey<'k>(pub(crate) &'k [u8]);
+
+impl std::fmt::Display for QuotedKey<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_char('"')?;
+        for chunk in self.0.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                if matches!(c, '"' | '\\') {
+                    f.write_char('\\')?;
+                }
+                f.write_char(c)?;
+            }
+            for b in chunk.invalid() {
+                write!(f, "\\x{:02x}", b)?;
+            }
+        }
+        f.write_char('"')
+    }
+}
+
+/// Split a quoted key off the start of `s` and return it with the rest
+///
+/// Returns `None` if `s` does not start with a well-formed quoted key.
+fn unquote(s: &str) -> Option<(Vec<u8>, &str)> {
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    let mut key = Vec::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((key, &s[i + 2..])),
+            '\\' => match chars.next()?.1 {
+                'x' => {
+                    let hi = chars.next()?.1.to_digit(16)?;
+                    let lo = chars.next()?.1.to_digit(16)?;
+                    key.push((hi * 16 + lo) as u8);
+                }
+                c @ ('"' | '\\') => key.push(c as u8),
+                _ => return None,
+            },
+            c => key.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    None
+}
+
+impl From<&Path> for Path {
+    fn from(path: &Path) -> Self {
+        path.clone()
+    }
+}
+
+/// Where a [Segment] leads from a node of a [PathTree]
+pub(crate) enum Step<'t, T> {
+    Child(&'t T),
+    /// A dictionary key that is not present or the index just past the end of a list
+    Missing,
+    OutOfRange,
+    NotAContainer,
+}
+
+/// A tree that [set_path] can modify
+pub(crate) trait PathTree: Sized {
+    /// An empty container that `segment` can be added to
+    fn container_for(segment: &Segment) -> Self;
+    fn step(&self, segment: &Segment) -> Step<'_, Self>;
+    /// The child at `segment`, adding a [container](Self::container_for) for `next` if missing
+    ///
+    /// Only called after [Self::step] did not fail for `segment`.
+    fn child_mut(&mut self, segment: &Segment, next: &Segment) -> &mut Self;
+    /// Set the child at `segment` and return the previous one
+    ///
+    /// Only called after [Self::step] did not fail for `segment`.
+    fn replace(&mut self, segment: &Segment, value: Self) -> Option<Self>;
+}
+
+/// Set the value at `path` below `root`, checking the whole path before modifying anything
+pub(crate) fn set_path<T: PathTree>(root: &mut T, path: &Path, value: T) -> Result<Option<T>, PathError> {
+    let segments = path.segments();
+    let Some((last, parents)) = segments.split_last() else {
+        return Ok(Some(std::mem::replace(root, value)));
+    };
+    let mut v = Some(&*root);
+    for (i, s) in segments.iter().enumerate() {
+        // Below a missing node, everything is created empty
+        let step = match (v, s) {
+            (Some(v), _) => v.step(s),
+            (None, Segment::Index(n)) if *n > 0 => Step::OutOfRange,
+            (None, _) => Step::Missing,
+        };
+        v = match step {
+            Step::Child(child) => Some(child),
+            Step::Missing => None,
+            Step::OutOfRange => return Err(PathError::OutOfRange(Path(segments[..=i].to_vec()))),
+            Step::NotAContainer => return Err(PathError::NotAContainer(Path(segments[..i].to_vec()))),
+        };
+    }
+    let mut v = root;
+    for (s, next) in parents.iter().zip(&segments[1..]) {
+        v = v.child_mut(s, next);
+    }
+    Ok(v.replace(last, value))
+}
+
+impl<'a> PathTree for Value<'a> {
+    fn container_for(segment: &Segment) -> Self {
+        match segment {
+            Segment::Key(_) => Value::empty_dict(),
+            Segment::Index(_) => Value::empty_list(),
+        }
+    }
+
+    fn step(&self, segment: &Segment) -> Step<'_, Self> {
+        match (self, segment) {
+            (Value::Dict(d), Segment::Key(k)) => d.get(k.as_slice()).map_or(Step::Missing, Step::Child),
+            (Value::List(l), Segment::Index(n)) => match l.get(*n) {
+                Some(x) => Step::Child(x),
+                None if *n == l.len() => Step::Missing,
+                None => Step::OutOfRange,
+            },
+            _ => Step::NotAContainer,
+        }
+    }
+
+    fn child_mut(&mut self, segment: &Segment, next: &Segment) -> &mut Self {
+        match (self, segment) {
+            (Value::Dict(d), Segment::Key(k)) => {
+                d.entry(Cow::Owned(k.clone())).or_insert_with(|| Self::container_for(next))
+            }
+            (Value::List(l), Segment::Index(n)) => {
+                if *n == l.len() {
+                    l.push(Self::container_for(next));
+                }
+                &mut l[*n]
+            }
+            _ => unreachable!("the path is checked before"),
+        }
+    }
+
+    fn replace(&mut self, segment: &Segment, value: Self) -> Option<Self> {
+        match (self, segment) {
+            (Value::Dict(d), Segment::Key(k)) => d.insert(Cow::Owned(k.clone()), value),
+            (Value::List(l), Segment::Index(n)) if *n == l.len() => {
+                l.push(value);
+                None
+            }
+            (Value::List(l), Segment::Index(n)) => Some(std::mem::replace(&mut l[*n], value)),
+            _ => unreachable!("the path is checked before"),
+        }
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Get the value at `path`
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let mut v = dict! {};
+    /// v.set_path("info.files[0].length", int!(42)).unwrap();
+    /// v.set_path("info.name", str!("foo")).unwrap();
+    /// assert_eq!(v.encode(), b"d4:infod5:filesld6:lengthi42eee4:name3:fooee");
+    /// assert_eq!(v.get_path("info.files[0].length"), Some(&int!(42)));
+    ///
+    /// assert_eq!(v.remove_path("info.files[0]"), Some(dict! { "length" => int!(42) }));
+    /// assert_eq!(v.encode(), b"d4:infod5:filesle4:name3:fooee");
+    /// ```
+    pub fn get_path(&self, path: impl Into<Path>) -> Option<&Value<'a>> {
+        let path = path.into();
+        let mut v = self;
+        for s in path.segments() {
+            v = match (v, s) {
+                (Value::Dict(d), Segment::Key(k)) => d.get(k.as_slice())?,
+                (Value::List(l), Segment::Index(i)) => l.get(*i)?,
+                _ => return None,
+            };
+        }
+        Some(v)
+    }
+
+    pub fn get_path_mut(&mut self, path: impl Into<Path>) -> Option<&mut Value<'a>> {
+        let path = path.into();
+        let mut v = self;
+        for s in path.segments() {
+            v = match (v, s) {
+                (Value::Dict(d), Segment::Key(k)) => d.get_mut(k.as_slice())?,
+                (Value::List(l), Segment::Index(i)) => l.get_mut(*i)?,
+                _ => return None,
+            };
+        }
+        Some(v)
+    }
+
+    /// Set the value at `path` and return the previous one
+    ///
+    /// Missing intermediate containers are created: a dictionary if the next segment is a key and a
+    /// list if it is an index. An index equal to the length of a list appends to it. Fails if an
+    /// existing value on the way is not a container of the right kind or an index is out of range,
+    /// in which case the value is left unchanged.
+    pub fn set_path(&mut self, path: impl Into<Path>, value: Value<'a>) -> Result<Option<Value<'a>>, PathError> {
+        set_path(self, &path.into(), value)
+    }
+
+    /// Remove the value at `path` and return it
+    ///
+    /// Removing a list item shifts the following items. The root cannot be removed.
+    pub fn remove_path(&mut self, path: impl Into<Path>) -> Option<Value<'a>> {
+        let mut path = path.into();
+        let last = path.pop()?;
+        match (self.get_path_mut(&path)?, last) {
+            (Value::Dict(d), Segment::Key(k)) => d.remove(k.as_slice()),
+            (Value::List(l), Segment::Index(i)) if i < l.len() => Some(l.remove(i)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let p = Path::new().key(b"info").key(b"files").index(3).index(0).key(b"length");
+        assert_eq!(Path::from("info.files[3][0].length"), p);
+        assert_eq!(Path::from(p.to_string().as_str()), p);
+        assert_eq!(Path::from("[1].a"), Path::new().index(1).key(b"a"));
+        assert_eq!(Path::from(""), Path::new());
+        assert_eq!(Path::from("a[x]..b"), Path::new().key(b"a[x]").key(b"").key(b"b"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for p in [
+            Path::new().key(b"a.b").key(b"c"),
+            Path::new().key(b"a[1]").index(2),
+            Path::new().key(b"x]").key(b"[0]"),
+            Path::new().key(b"").key(b"").index(0).key(b""),
+            Path::new().key(b"\xff\x00").key(b"0xff"),
+            Path::new().key(b"\"q\\\"").key(b"caf\xc3\xa9\xc3"),
+        ] {
+            assert_eq!(Path::from(p.to_string().as_str()), p, "{}", p);
+        }
+        assert_eq!(Path::new().key(b"0xff").key(b"\xff").to_string(), r#"0xff."\xff""#);
+        assert_eq!(Path::new().key(b"a.b").index(1).to_string(), r#""a.b"[1]"#);
+        assert_eq!(Path::new().key(b"").to_string(), r#""""#);
+        // Malformed quotes are taken literally
+        assert_eq!(Path::from(r#""a.b"#), Path::new().key(b"\"a").key(b"b"));
+        assert_eq!(Path::from(r#""\q""#), Path::new().key(br#""\q""#));
+    }
+
+    #[test]
+    fn test_set_path_errors() {
+        let mut v = Value::decode(b"d1:ai1e1:bli0eee", 10).unwrap();
+        assert_eq!(v.set_path("a.x", Value::Int(1)), Err(PathError::NotAContainer(Path::from("a"))));
+        assert_eq!(v.set_path("b[2]", Value::Int(1)), Err(PathError::OutOfRange(Path::from("b[2]"))));
+        assert_eq!(v.set_path("b[2].c", Value::Int(1)), Err(PathError::OutOfRange(Path::from("b[2]"))));
+        assert_eq!(v.set_path("b.c", Value::Int(1)), Err(PathError::NotAContainer(Path::from("b"))));
+        assert_eq!(v.set_path("b[0]", Value::Int(5)), Ok(Some(Value::Int(0))));
+        assert_eq!(v.set_path("", Value::Int(7)).unwrap().unwrap().encode(), b"d1:ai1e1:bli5eee");
+        assert_eq!(v, Value::Int(7));
+        assert_eq!(v.remove_path(""), None);
+        assert_eq!(v.get_path(""), Some(&Value::Int(7)));
+    }
+
+    #[test]
+    fn test_set_path_unchanged_on_error() {
+        let mut v = Value::decode(b"d1:ali0eee", 10).unwrap();
+        let before = v.clone();
+        assert_eq!(v.set_path("x.y[3]", Value::Int(1)), Err(PathError::OutOfRange(Path::from("x.y[3]"))));
+        assert_eq!(v.set_path("x.y[1].z", Value::Int(1)), Err(PathError::OutOfRange(Path::from("x.y[1]"))));
+        assert_eq!(v.set_path("a[0].b", Value::Int(1)), Err(PathError::NotAContainer(Path::from("a[0]"))));
+        assert_eq!(v.set_path("a[2][0]", Value::Int(1)), Err(PathError::OutOfRange(Path::from("a[2]"))));
+        assert_eq!(v, before);
+        assert_eq!(v.set_path("x.y[0][0]", Value::Int(1)), Ok(None));
+        assert_eq!(v.encode(), b"d1:ali0ee1:xd1:ylli1eeeee");
+    }
+}
@@ -0,0 +1,165 @@
+use super::decoder::{Decoder, DICT_ENTRY_SIZE, LIST_ITEM_SIZE};
+use super::{DecodeError, DecodeOptions, DuplicateKeys, Kind};
+
+/// Classify the next value in `buf` by its first byte
+///
+/// This neither validates nor consumes anything. `None` is returned if `buf` is empty or does not
+/// start with a valid type marker.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// assert_eq!(peek_kind(b"d1:y1:qe"), Some(Kind::Dict));
+/// assert_eq!(peek_kind(b"4:spam"), Some(Kind::Str));
+/// assert_eq!(peek_kind(b"x"), None);
+/// ```
+pub fn peek_kind(buf: &[u8]) -> Option<Kind> {
+    match buf.first()? {
+        b'i' => Some(Kind::Int),
+        b'l' => Some(Kind::List),
+        b'd' => Some(Kind::Dict),
+        b'0'..=b'9' => Some(Kind::Str),
+        _ => None,
+    }
+}
+
+/// Determine the encoded length of the next value in `buf` without building it
+///
+/// For byte strings, the length is known from the header alone and reported even if `buf` does
+/// not contain the whole string yet. All other values must be complete. Nothing is allocated, so
+/// this is cheap enough to route or reject messages before decoding them. Note that the syntax is
+/// validated, but duplicate dictionary keys are not detected.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// assert_eq!(peek_len(b"li1ei2eetrailer"), Ok(8));
+/// assert_eq!(peek_len(b"10:hel"), Ok(13));
+/// assert_eq!(peek_len(b"li1ei2"), Err(DecodeError::Incomplete { needed: 1 }));
+/// ```
+pub fn peek_len(buf: &[u8]) -> Result<usize, DecodeError> {
+    let mut d = Decoder::with_options(buf, &DecodeOptions::default());
+    if peek_kind(buf) == Some(Kind::Str) {
+        let len = d.take_usize()?;
+        d.take_u8_eq(b':')?;
+        return (buf.len() - d.remaining()).checked_add(len).ok_or(DecodeError::LengthTooLarge);
+    }
+    d.skip_value()?;
+    Ok(buf.len() - d.remaining())
+}
+
+/// Determine the encoded length of the first value in `buf` without building it
+///
+/// Unlike [peek_len], this requires the value to be complete and valid (apart from duplicate
+/// keys, see [peek_len]). Trailing bytes after the value are ignored.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// assert_eq!(measure(b"d1:ai1ee\x00\x01"), Some(8));
+/// assert_eq!(measure(b"10:hel"), None);
+/// ```
+pub fn measure(buf: &[u8]) -> Option<usize> {
+    let mut d = Decoder::with_options(buf, &DecodeOptions::default());
+    d.skip_value().ok()?;
+    Some(buf.len() - d.remaining())
+}
+
+/// Derive [DecodeOptions] from the input length and a scan of the first value in `buf`
+///
+/// The scan validates the syntax like [measure] and counts list items and dictionary entries
+/// without allocating. The returned limits are exactly what decoding `buf` takes, so they can be
+/// derived from a representative message of a class (and given some headroom) instead of guessing
+/// numbers. If `buf` does not start with a complete value, limits are derived from its length
+/// alone: every item takes at least two bytes of input.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let buf = b"d1:ad2:id20:abcdefghij0123456789e1:q4:ping1:t2:aa1:y1:qe";
+/// let opts = estimate_limits(buf);
+/// assert_eq!((opts.max_allocs, opts.max_steps), (5, 6));
+/// assert!(Value::decode_with(buf, &opts).is_ok());
+/// assert_eq!(estimate_limits(b"li1ei2e").max_allocs, 3);
+/// ```
+pub fn estimate_limits(buf: &[u8]) -> DecodeOptions {
+    let mut d = Decoder::with_options(buf, &DecodeOptions { max_size_factor: None, ..Default::default() });
+    let (mut items, mut entries) = (0, 0);
+    let (items, entries, depth, str_bytes) = match count(&mut d, &mut items, &mut entries) {
+        Ok(()) => (items, entries, d.stats().depth, d.stats().str_bytes),
+        Err(_) => (buf.len() / 2, 0, buf.len().min(DecodeOptions::DEFAULT_MAX_DEPTH), buf.len()),
+    };
+    let size = items * LIST_ITEM_SIZE + entries * DICT_ENTRY_SIZE;
+    DecodeOptions {
+        max_allocs: items + entries,
+        max_size_factor: Some(size.div_ceil(buf.len().max(1)).max(1)),
+        max_steps: items + entries + 1,
+        max_depth: depth,
+        max_str_bytes: str_bytes,
+        strict: false,
+        duplicate_keys: DuplicateKeys::Reject,
+        on_reject: None,
+        should_continue: None,
+    }
+}
+
+fn count(d: &mut Decoder, items: &mut usize, entries: &mut usize) -> Result<(), DecodeError> {
+    match d.peek_u8()? {
+        b'l' => {
+            d.begin_list()?;
+            while d.next_item()? {
+                *items += 1;
+                count(d, items, entries)?;
+            }
+            Ok(())
+        }
+        b'd' => {
+            d.begin_dict()?;
+            while d.next_key()?.is_some() {
+                *entries += 1;
+                count(d, items, entries)?;
+            }
+            Ok(())
+        }
+        // Taken rather than skipped to count their length
+        b'0'..=b'9' => d.take_str().map(|_| ()),
+        _ => d.skip_value(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc_count::count_allocs;
+    use crate::Value;
+
+    #[test]
+    fn test_no_allocations() {
+        let buf = b"d1:ad2:id20:abcdefghij0123456789e1:q4:ping1:t2:aa1:y1:qe";
+        assert_eq!(count_allocs(|| peek_len(buf)), 0);
+        assert_eq!(count_allocs(|| peek_len(b"10:hel")), 0);
+        assert_eq!(count_allocs(|| peek_len(b"li1ei2")), 0);
+        assert_eq!(count_allocs(|| measure(buf)), 0);
+    }
+
+    #[test]
+    fn test_estimate_limits_are_tight() {
+        for buf in [b"i1e".as_ref(), b"le", b"ld1:ali1eee1:xe", b"d4:infod6:lengthi42e4:name3:fooee"] {
+            let opts = estimate_limits(buf);
+            assert!(Value::decode_with(buf, &opts).is_ok());
+            let fewer_steps = DecodeOptions { max_steps: opts.max_steps - 1, ..opts.clone() };
+            assert_eq!(Value::decode_with(buf, &fewer_steps), Err(DecodeError::LimitExceeded));
+        }
+        let opts = estimate_limits(b"ld1:ali1eee1:xe");
+        assert_eq!(opts.max_allocs, 4);
+        let fewer_allocs = DecodeOptions { max_allocs: 3, ..opts };
+        assert_eq!(Value::decode_with(b"ld1:ali1eee1:xe", &fewer_allocs), Err(DecodeError::LimitExceeded));
+
+        let buf = b"d4:infod6:lengthi42e4:name3:fooee";
+        let opts = estimate_limits(buf);
+        assert_eq!((opts.max_depth, opts.max_str_bytes), (2, 17));
+        for opts in [opts.clone().with_max_depth(1), opts.with_max_str_bytes(16)] {
+            assert_eq!(Value::decode_with(buf, &opts), Err(DecodeError::LimitExceeded));
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! Support for the macros, not part of the public API
+
+use super::SortedDictEncoder;
+
+/// Writes the fields of a struct deriving `ToBencode` as entries of an enclosing dictionary
+///
+/// This is how `#[bencode(flatten)]` fields are merged into their parent.
+pub trait EncodeEntries {
+    fn encode_entries(&self, d: &mut SortedDictEncoder<'_, '_>);
+}
+
+/// A literal dictionary key usable in const contexts
+///
+/// Inherent methods on the individual instantiations accept both `"str"` and `b"bytes"` literals,
+/// which a trait could not do in a `const` item.
+pub struct KeyLit<T>(pub T);
+
+impl KeyLit<&'static str> {
+    pub const fn bytes(self) -> &'static [u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl<const N: usize> KeyLit<&'static [u8; N]> {
+    pub const fn bytes(self) -> &'static [u8] {
+        self.0
+    }
+}
+
+/// Whether any two of `keys` are equal
+pub const fn has_duplicate_keys(keys: &[&[u8]]) -> bool {
+    let mut i = 0;
+    while i < keys.len() {
+        let mut j = i + 1;
+        while j < keys.len() {
+            if bytes_eq(keys[i], keys[j]) {
+                return true;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    false
+}
+
+const fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
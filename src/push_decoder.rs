@@ -0,0 +1,309 @@
+use super::decoder::{Decoder, DICT_ENTRY_SIZE, LIST_ITEM_SIZE};
+use super::{DecodeError, DecodeOptions, DecodeStats, Dict, List, Str, Value};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// The outcome of feeding a chunk to a [PushDecoder]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeProgress {
+    /// The whole chunk was consumed, but the value is not complete yet
+    NeedMore,
+    /// The value ended after the first `used` bytes of the chunk
+    Done { value: Value<'static>, used: usize },
+    /// The input is invalid or exceeds the limits
+    ///
+    /// This is never [DecodeError::Incomplete]. Feeding more returns the same error until
+    /// [reset](PushDecoder::reset).
+    Failed(DecodeError),
+}
+
+/// A decoder that is fed the input in chunks as it arrives
+///
+/// Messages received over a stream (like the extension messages of the peer wire protocol) arrive
+/// in arbitrary pieces. Instead of buffering and decoding them from the start on each arrival,
+/// this decoder builds the value while the chunks come in and only keeps the start of a string or
+/// integer that is split between chunks. Malformed input is reported as soon as the offending
+/// byte arrives.
+///
+/// The limits of the [DecodeOptions] apply to each value, with the size limit relative to the
+/// number of bytes fed for it. As strings cannot be borrowed from the chunks, values are owned.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let mut d = PushDecoder::new(&DecodeOptions::default());
+/// assert_eq!(d.feed(b"d1:ai1"), DecodeProgress::NeedMore);
+/// assert_eq!(d.feed(b"2e1:b3:ab"), DecodeProgress::NeedMore);
+/// let value = dict! { "a" => int!(12), "b" => str!("abc") };
+/// assert_eq!(d.feed(b"ce\x00"), DecodeProgress::Done { value, used: 2 });
+///
+/// assert_eq!(d.feed(b"li1e"), DecodeProgress::NeedMore);
+/// assert_eq!(d.feed(b"x"), DecodeProgress::Failed(DecodeError::Invalid));
+/// ```
+pub struct PushDecoder {
+    opts: DecodeOptions,
+    // Open containers innermost last
+    stack: Vec<Open>,
+    // The start of a token that continues in the next chunk
+    pending: Vec<u8>,
+    stats: DecodeStats,
+    // Bytes fed since the start of the current value
+    len: usize,
+    error: Option<DecodeError>,
+}
+
+enum Open {
+    List(List<'static>),
+    // A dictionary and the key of the value to come
+    Dict(Dict<'static>, Option<Str<'static>>),
+}
+
+impl PushDecoder {
+    pub fn new(opts: &DecodeOptions) -> Self {
+        Self {
+            opts: opts.clone(),
+            stack: Vec::new(),
+            pending: Vec::new(),
+            stats: DecodeStats::default(),
+            len: 0,
+            error: None,
+        }
+    }
+
+    /// Discard the current value (or error) and start over with the next chunk
+    pub fn reset(&mut self) {
+        self.stack.clear();
+        self.pending.clear();
+        self.stats = DecodeStats::default();
+        self.len = 0;
+        self.error = None;
+    }
+
+    /// Continue decoding with the next chunk of input
+    ///
+    /// After [DecodeProgress::Done], the decoder starts over with the next value, so the rest of
+    /// the chunk may be fed again if it holds the next message.
+    pub fn feed(&mut self, chunk: &[u8]) -> DecodeProgress {
+        if let Some(error) = self.error {
+            return DecodeProgress::Failed(error);
+        }
+        let carried = self.pending.len();
+        let mut pending = std::mem::take(&mut self.pending);
+        let buf = match carried {
+            0 => chunk,
+            _ => {
+                pending.extend_from_slice(chunk);
+                &pending
+            }
+        };
+        self.len = self.len.saturating_add(chunk.len());
+        let max_size = self.opts.max_size_factor.map_or(usize::MAX, |k| k.saturating_mul(self.len));
+        let mut d = Decoder::with_options(buf, &self.opts);
+        d.resume(self.stats, self.stack.len(), max_size);
+        let r = self.run(&mut d);
+        let rest = d.remaining();
+        match r {
+            Ok(Some(value)) => {
+                self.reset();
+                DecodeProgress::Done { value, used: buf.len() - rest - carried }
+            }
+            Ok(None) => {
+                if carried == 0 {
+                    pending.extend_from_slice(&chunk[chunk.len() - rest..]);
+                } else {
+                    pending.drain(..pending.len() - rest);
+                }
+                self.pending = pending;
+                DecodeProgress::NeedMore
+            }
+            Err(error) => {
+                self.error = Some(error);
+                DecodeProgress::Failed(error)
+            }
+        }
+    }
+
+    /// Decode tokens until the value is complete or the input ends
+    ///
+    /// A token cut off by the end of the input is rewound so that it is decoded again as a whole
+    /// from the next chunk, and so is the budget it used.
+    fn run(&mut self, d: &mut Decoder<'_>) -> Result<Option<Value<'static>>, DecodeError> {
+        loop {
+            let (checkpoint, stats) = (d.save(), d.stats());
+            match self.token(d) {
+                Ok(Some(value)) => return Ok(Some(value)),
+                Ok(None) => (),
+                Err(DecodeError::Incomplete { .. }) => {
+                    d.restore(checkpoint);
+                    self.stats = stats;
+                    return Ok(None);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Decode the next token and return the value if it is complete
+    fn token(&mut self, d: &mut Decoder<'_>) -> Result<Option<Value<'static>>, DecodeError> {
+        let end = match self.stack.last_mut() {
+            Some(Open::Dict(dict, key @ None)) => {
+                let Some(k) = d.next_key()? else {
+                    return self.close(d);
+                };
+                // Each chunk is read by a fresh decoder which does not know the previous keys
+                if self.opts.strict && dict.last_key_value().is_some_and(|(last, _)| **last > *k) {
+                    return d.unsorted_key(&k);
+                }
+                d.alloc(DICT_ENTRY_SIZE)?;
+                *key = Some(Cow::Owned(k.into_owned()));
+                return Ok(None);
+            }
+            Some(Open::List(_)) => !d.next_item()?,
+            Some(Open::Dict(_, Some(_))) | None => false,
+        };
+        if end {
+            return self.close(d);
+        }
+        if let Some(Open::List(_)) = self.stack.last() {
+            d.alloc(LIST_ITEM_SIZE)?;
+        }
+        let value = match d.peek_u8()? {
+            b'i' => Value::Int(d.take_int()?),
+            b'0'..=b'9' => Value::Str(Cow::Owned(d.take_str()?.into_owned())),
+            b'l' => {
+                d.begin_list()?;
+                self.stack.push(Open::List(Vec::new()));
+                return Ok(None);
+            }
+            b'd' => {
+                d.begin_dict()?;
+                self.stack.push(Open::Dict(BTreeMap::new(), None));
+                return Ok(None);
+            }
+            _ => return Err(DecodeError::Invalid),
+        };
+        self.add(d, value)
+    }
+
+    /// Finish the innermost container whose end was just consumed
+    fn close(&mut self, d: &mut Decoder<'_>) -> Result<Option<Value<'static>>, DecodeError> {
+        let value = match self.stack.pop() {
+            Some(Open::List(l)) => Value::List(l),
+            Some(Open::Dict(dict, _)) => Value::Dict(dict),
+            None => unreachable!("only open containers are closed"),
+        };
+        self.add(d, value)
+    }
+
+    /// Add a complete value to the innermost container or return it at the top level
+    fn add(&mut self, d: &mut Decoder<'_>, value: Value<'static>) -> Result<Option<Value<'static>>, DecodeError> {
+        match self.stack.last_mut() {
+            None => return Ok(Some(value)),
+            Some(Open::List(l)) => l.push(value),
+            Some(Open::Dict(dict, key)) => {
+                d.insert_entry(dict, key.take().expect("values follow their key"), value)?;
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DuplicateKeys;
+
+    fn feed_bytewise(d: &mut PushDecoder, buf: &[u8]) -> DecodeProgress {
+        for (i, b) in buf.iter().enumerate() {
+            match d.feed(std::slice::from_ref(b)) {
+                DecodeProgress::NeedMore => (),
+                DecodeProgress::Done { value, used } => {
+                    assert_eq!((i, used), (buf.len() - 1, 1));
+                    return DecodeProgress::Done { value, used: buf.len() };
+                }
+                p => return p,
+            }
+        }
+        DecodeProgress::NeedMore
+    }
+
+    #[test]
+    fn test_push_decoder() {
+        let opts = DecodeOptions::default();
+        let mut d = PushDecoder::new(&opts);
+        for buf in [
+            b"i-42e".as_ref(),
+            b"0:",
+            b"10:0123456789",
+            b"le",
+            b"de",
+            b"d1:ad1:bli1ei2eee1:c3:xyze",
+            b"d8:announce3:foo4:infod6:lengthi42e4:name3:bar6:pieces20:aaaaaaaaaaaaaaaaaaaaee",
+        ] {
+            let value = Value::decode_with(buf, &opts).unwrap().into_owned();
+            assert_eq!(feed_bytewise(&mut d, buf), DecodeProgress::Done { value: value.clone(), used: buf.len() });
+            assert_eq!(d.feed(buf), DecodeProgress::Done { value, used: buf.len() });
+        }
+
+        // Values may end and start within a chunk
+        let mut values = Vec::new();
+        for chunk in [b"i1ei".as_ref(), b"2eli3", b"eel", b"e"] {
+            let mut chunk = chunk;
+            while let DecodeProgress::Done { value, used } = d.feed(chunk) {
+                values.push(value);
+                chunk = &chunk[used..];
+            }
+        }
+        assert_eq!(values, [Value::Int(1), Value::Int(2), Value::List(vec![Value::Int(3)]), Value::List(vec![])]);
+    }
+
+    #[test]
+    fn test_push_decoder_errors() {
+        let opts = DecodeOptions::default();
+        let mut d = PushDecoder::new(&opts);
+        for (buf, error) in [
+            (b"x".as_ref(), DecodeError::Invalid),
+            (b"li1e1x", DecodeError::Invalid),
+            (b"d1:ai1e1:ai2ee", DecodeError::Invalid),
+            (b"i9223372036854775808e", DecodeError::Invalid),
+            (b"99999999999999999999999:", DecodeError::LengthTooLarge),
+        ] {
+            assert_eq!(feed_bytewise(&mut d, buf), DecodeProgress::Failed(error), "{:?}", buf);
+            assert_eq!(d.feed(b"i0e"), DecodeProgress::Failed(error));
+            d.reset();
+            assert_eq!(d.feed(buf), DecodeProgress::Failed(error), "{:?}", buf);
+            d.reset();
+        }
+
+        // Limits span all chunks of a value
+        for opts in [
+            opts.clone().with_max_allocs(2),
+            opts.clone().with_max_depth(1),
+            opts.clone().with_max_steps(3),
+            opts.clone().with_max_str_bytes(1),
+        ] {
+            let buf = b"l1:al1:bee";
+            assert_eq!(Value::decode_with(buf, &opts), Err(DecodeError::LimitExceeded));
+            let mut d = PushDecoder::new(&opts);
+            assert_eq!(feed_bytewise(&mut d, buf), DecodeProgress::Failed(DecodeError::LimitExceeded), "{:?}", opts);
+            d.reset();
+            let value = Value::decode_with(b"l1:ae", &opts).unwrap().into_owned();
+            assert_eq!(feed_bytewise(&mut d, b"l1:ae"), DecodeProgress::Done { value, used: 5 });
+        }
+    }
+
+    #[test]
+    fn test_push_decoder_options() {
+        let opts = DecodeOptions::default().with_strict(true);
+        for buf in [b"i-0e".as_ref(), b"i01e", b"01:a", b"d1:bi0e1:ai0ee"] {
+            assert_eq!(feed_bytewise(&mut PushDecoder::new(&opts), buf), DecodeProgress::Failed(DecodeError::Invalid));
+        }
+
+        let opts = DecodeOptions::default().with_duplicate_keys(DuplicateKeys::LastWins);
+        let p = feed_bytewise(&mut PushDecoder::new(&opts), b"d1:ai1e1:ai2ee");
+        assert_eq!(
+            p,
+            DecodeProgress::Done { value: Value::decode_with(b"d1:ai2ee", &opts).unwrap().into_owned(), used: 14 }
+        );
+    }
+}
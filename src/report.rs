@@ -0,0 +1,187 @@
+use super::decoder::Decoder;
+use super::{DecodeError, DecodeErrorKind, DecodeOptions, Path, Value};
+
+/// A decode error with its location for human consumption
+///
+/// The [Display](std::fmt::Display) implementation prints the cause, its offset and the path of the
+/// value being decoded followed by the surrounding input with a caret pointing at the offending byte.
+/// Non-printable bytes are shown as `.`. With the `miette` feature, this also implements
+/// `miette::Diagnostic`.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let buf = b"d4:infod5:filesld6:lengthi4x2eeeee";
+/// let e = Value::decode_with_report(buf, &DecodeOptions::default()).unwrap_err();
+/// assert_eq!(e.error, DecodeError::Invalid);
+/// assert_eq!(e.kind, DecodeErrorKind::UnexpectedByte(b'x'));
+/// assert_eq!(e.offset, 27);
+/// assert_eq!(e.path.to_string(), "info.files[0].length");
+/// assert_eq!(
+///     e.to_string(),
+///     "unexpected byte 'x' at offset 27 (info.files[0].length)\n\
+///      | d4:infod5:filesld6:lengthi4x2eeeee\n\
+///      |                            ^"
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodeReport {
+    pub error: DecodeError,
+    /// What exactly went wrong
+    pub kind: DecodeErrorKind,
+    /// Position in the input at which decoding failed
+    pub offset: usize,
+    /// The innermost value being decoded when the error occurred
+    pub path: Path,
+    window: Vec<u8>,
+    window_start: usize,
+    truncated: bool,
+}
+
+impl DecodeReport {
+    /// Number of input bytes shown before and after the offending offset
+    pub const CONTEXT: usize = 32;
+
+    /// Decode `buf` and return a report if decoding fails
+    pub fn new(buf: &[u8], opts: &DecodeOptions) -> Option<Self> {
+        let mut d = Decoder::with_options(buf, opts);
+        let error = d.take_value().err()?;
+        Some(Self::from_decoder(buf, &d, error))
+    }
+
+    /// Build the report for `error` returned by a decoder that failed decoding `buf`
+    pub(crate) fn from_decoder(buf: &[u8], d: &Decoder, error: DecodeError) -> Self {
+        let offset = buf.len() - d.remaining();
+        let (kind, path) = (d.error_kind(error), d.error_path());
+        let window_start = offset.saturating_sub(Self::CONTEXT);
+        let window_end = buf.len().min(offset + Self::CONTEXT);
+        let window = buf[window_start..window_end].to_vec();
+        Self { error, kind, offset, path, window, window_start, truncated: window_end < buf.len() }
+    }
+}
+
+impl std::fmt::Display for DecodeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)?;
+        if !self.path.is_root() {
+            write!(f, " ({})", self.path)?;
+        }
+        let prefix = if self.window_start > 0 { "..." } else { "" };
+        let suffix = if self.truncated { "..." } else { "" };
+        let text: String = self.window.iter().map(|c| if c.is_ascii_graphic() { *c as char } else { '.' }).collect();
+        write!(f, "\n| {}{}{}", prefix, text, suffix)?;
+        write!(f, "\n| {:>1$}", "^", prefix.len() + self.offset - self.window_start + 1)
+    }
+}
+
+impl std::error::Error for DecodeReport {}
+
+#[cfg(feature = "miette")]
+impl miette::Diagnostic for DecodeReport {
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        Some(&self.window)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        let span = miette::LabeledSpan::at_offset(self.offset - self.window_start, self.path.to_string());
+        Some(Box::new(std::iter::once(span)))
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Like [Self::decode_with], but report where decoding failed (see [DecodeReport])
+    pub fn decode_with_report(buf: &'a [u8], opts: &DecodeOptions) -> Result<Self, DecodeReport> {
+        let mut d = Decoder::with_options(buf, opts);
+        d.take_value().map_err(|e| DecodeReport::from_decoder(buf, &d, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzz::mutate;
+
+    #[test]
+    fn test_same_error_as_decode() {
+        let opts = DecodeOptions { max_allocs: 6, ..Default::default() };
+        for buf in [b"d4:infod6:lengthi42e4:name3:fooee".as_ref(), b"ld1:ai1eeli1ei2ei3eee"] {
+            for seed in 0..2000 {
+                let m = mutate(buf, seed);
+                let r = DecodeReport::new(&m, &opts);
+                assert_eq!(Value::decode_with(&m, &opts).err(), r.as_ref().map(|r| r.error));
+                if let Some(r) = r {
+                    assert!(r.to_string().lines().count() == 3);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_paths() {
+        let opts = DecodeOptions::default();
+        for (buf, path) in [
+            (b"d1:ai1e1:ai2ee".as_ref(), "a"),
+            (b"d1:bi1e1:ai2e1:ai3ee", "a"),
+            (b"ld1:xli1ei2x", "[0].x[1]"),
+            (b"ld1:xi12", "[0].x"),
+            (b"ld1:", "[0]"),
+            (b"l", ""),
+        ] {
+            let r = Value::decode_with_report(buf, &opts).unwrap_err();
+            assert_eq!(r.path.to_string(), path);
+            assert_eq!(Some(r), DecodeReport::new(buf, &opts));
+        }
+        let mut v = Value::decode(b"ld1:ai1eee", 10).unwrap();
+        let mut d = Decoder::with_options(b"ld1:ai1x", &opts);
+        assert_eq!(d.take_value_into(&mut v), Err(DecodeError::Invalid));
+        assert_eq!(d.error_path().to_string(), "[0].a");
+    }
+
+    #[test]
+    fn test_kinds() {
+        use DecodeErrorKind::*;
+        let opts = DecodeOptions::default();
+        for (buf, opts, kind) in [
+            (b"5:hel".as_ref(), opts.clone(), Truncated),
+            (b"li1e:e", opts.clone(), UnexpectedByte(b':')),
+            (b"d1:ai1e1:ai2ee", opts.clone(), DuplicateKey),
+            (b"i9223372036854775808e", opts.clone(), IntegerOverflow),
+            (b"d1:bi1e1:ai2ee", opts.clone().with_strict(true), NonCanonical),
+            (b"99999999999999999999:", opts.clone(), LengthTooLarge),
+            (b"li1ei2ee", opts.clone().with_max_allocs(1), AllocLimit),
+            (b"li1ei2ee", opts.clone().with_max_steps(2), StepLimit),
+            (b"llleee", opts.clone().with_max_depth(2), DepthLimit),
+            (b"l1:a2:bce", opts.clone().with_max_str_bytes(2), StrLimit),
+        ] {
+            let r = Value::decode_with_report(buf, &opts).unwrap_err();
+            assert_eq!(r.kind, kind, "{:?}", buf);
+        }
+        let opts = opts.with_max_size_factor(Some(1));
+        assert_eq!(DecodeReport::new(b"li1ee", &opts).unwrap().kind, SizeLimit);
+
+        // A failed attempt does not leak into the next error
+        let mut d = Decoder::with_options(b"d1:ai1e1:ai2eex", &DecodeOptions::default());
+        let c = d.save();
+        assert_eq!(d.take_dict().map_err(|e| d.error_kind(e)), Err(DuplicateKey));
+        d.restore(c);
+        d.skip_value().unwrap();
+        assert_eq!(d.take_value().map_err(|e| d.error_kind(e)), Err(UnexpectedByte(b'x')));
+    }
+
+    #[test]
+    fn test_window() {
+        let mut buf = b"l".to_vec();
+        buf.extend(std::iter::repeat_n(b"i1e".as_ref(), 30).flatten());
+        buf.extend(b"x");
+        buf.extend(std::iter::repeat_n(b"i1e".as_ref(), 30).flatten());
+        let r = DecodeReport::new(&buf, &DecodeOptions::default()).unwrap();
+        assert_eq!(r.offset, 91);
+        let s = r.to_string();
+        let lines: Vec<_> = s.lines().collect();
+        assert_eq!(lines[0], "unexpected byte 'x' at offset 91 ([30])");
+        assert_eq!(lines[1], "| ...1ei1ei1ei1ei1ei1ei1ei1ei1ei1ei1exi1ei1ei1ei1ei1ei1ei1ei1ei1ei1ei...");
+        assert_eq!(lines[2].find('^'), lines[1].find('x'));
+        let r = DecodeReport::new(b"d1:a\xff", &DecodeOptions::default()).unwrap();
+        assert_eq!(r.to_string(), "unexpected byte 0xff at offset 4 (a)\n| d1:a.\n|     ^");
+    }
+}
@@ -0,0 +1,60 @@
+use super::Value;
+use std::borrow::Cow;
+use zeroize::Zeroize;
+
+/// Overwrite all owned byte strings, keys and integers with zeros
+///
+/// Everything is wiped where it is stored rather than moved out first, and containers are emptied
+/// afterwards. Copies that earlier reallocations of growing lists or strings left behind are out
+/// of reach.
+///
+/// Wrap a value in [Zeroizing](zeroize::Zeroizing) to have this done on drop. Borrowed strings are
+/// left alone as they belong to the input buffer which must be zeroized by the caller (e.g. by
+/// decoding from a `Zeroizing<Vec<u8>>`) or use [Value::into_owned] first.
+///
+/// ```rust
+/// use bencode_minimal::*;
+/// use zeroize::Zeroizing;
+///
+/// let buf = Zeroizing::new(b"d1:k32:0123456789abcdef0123456789abcdefe".to_vec());
+/// let v = Zeroizing::new(Value::decode(&buf, 10).unwrap().into_owned());
+/// assert_eq!(v.get::<&[u8]>("k").map(|x| x.len()), Some(32));
+/// ```
+impl Zeroize for Value<'_> {
+    fn zeroize(&mut self) {
+        match self {
+            Value::Int(i) => i.zeroize(),
+            Value::Str(Cow::Owned(s)) | Value::Raw(Cow::Owned(s)) => s.zeroize(),
+            Value::Str(Cow::Borrowed(_)) | Value::Raw(Cow::Borrowed(_)) => (),
+            Value::List(l) => l.zeroize(),
+            Value::Dict(d) => {
+                // Values are wiped where they are stored before the entries are moved out. The
+                // keys cannot be modified in the map, but their bytes live in separate buffers.
+                d.values_mut().for_each(Zeroize::zeroize);
+                for (k, _) in std::mem::take(d) {
+                    if let Cow::Owned(mut k) = k {
+                        k.zeroize();
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zeroize() {
+        let mut v = Value::decode(b"d1:ali7e3:abce1:b1:ce", 10).unwrap().into_owned();
+        v.zeroize();
+        assert_eq!(v, Value::Dict(Default::default()));
+        let mut v = Value::List(vec![Value::Int(7), Value::Str(Cow::Owned(b"abc".to_vec()))]);
+        v.zeroize();
+        assert_eq!(v, Value::List(vec![]));
+        let mut v = Value::Str(Cow::Borrowed(b"abc"));
+        v.zeroize();
+        assert_eq!(v, Value::Str(Cow::Borrowed(b"abc")));
+    }
+}
@@ -0,0 +1,695 @@
+use super::{default_encode_capacity, DisplayBencode, Encoder, SerdeError};
+use serde::ser::{self, Impossible, Serialize};
+use std::ops::Range;
+
+/// Serialize any [Serialize] type to Bencode
+///
+/// Structs and maps become dictionaries with sorted keys, sequences and tuples become lists, and
+/// `bool` becomes `0` or `1`. Byte arrays are written as strings if they are serialized as bytes
+/// (e.g. with `serde_bytes`), otherwise as lists of integers. Enum variants without data are
+/// written as their name, all others as a dictionary with the name as the only key.
+///
+/// Bencode has no null, so fields and map entries that are `None` or `()` are left out. The same
+/// in a list or as the root value is an error, as are floats and map keys that are not strings,
+/// characters or integers (which are written in decimal).
+///
+/// ```rust
+/// use bencode_minimal::*;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Ping<'a> {
+///     t: &'a str,
+///     y: char,
+///     q: &'a str,
+///     id: Option<u32>,
+///     tags: Vec<&'a str>,
+/// }
+///
+/// let ping = Ping { t: "aa", y: 'q', q: "ping", id: None, tags: vec!["x"] };
+/// assert_eq!(to_bytes(&ping).unwrap(), b"d1:q4:ping1:t2:aa4:tagsl1:xe1:y1:qe");
+/// assert_eq!(to_bytes(&1.5), Err(SerdeError::Unsupported("floating point numbers")));
+/// ```
+pub fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, SerdeError> {
+    let mut buf = Vec::with_capacity(default_encode_capacity());
+    value.serialize(&mut Serializer::new(&mut buf))?;
+    if buf.is_empty() {
+        return Err(SerdeError::Unsupported("none or unit as root value"));
+    }
+    Ok(buf)
+}
+
+/// Serialize any [Serialize] type to the text syntax of [DisplayBencode]
+///
+/// Meant for logs and debugging output. The value is serialized like by [to_bytes], except that
+/// the serializer is [human-readable](Serializer::with_human_readable), so types like IP addresses
+/// or timestamps choose their text form.
+///
+/// ```rust
+/// use bencode_minimal::*;
+/// use std::net::{IpAddr, Ipv4Addr};
+///
+/// let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+/// assert_eq!(to_text(&("ip", ip)).unwrap(), r#"["ip", "127.0.0.1"]"#);
+/// assert_eq!(to_bytes(&ip).unwrap(), b"d2:V4li127ei0ei0ei1eee");
+/// ```
+pub fn to_text<T: Serialize + ?Sized>(value: &T) -> Result<String, SerdeError> {
+    let mut buf = Vec::with_capacity(default_encode_capacity());
+    value.serialize(&mut Serializer::new(&mut buf).with_human_readable(true))?;
+    if buf.is_empty() {
+        return Err(SerdeError::Unsupported("none or unit as root value"));
+    }
+    Ok(DisplayBencode(&buf).to_string())
+}
+
+/// A [serde::Serializer] appending Bencode to a buffer (see [to_bytes])
+///
+/// Nothing is written for a root value that is `None` or `()`.
+pub struct Serializer<'a> {
+    e: Encoder<'a>,
+    human_readable: bool,
+}
+
+impl<'a> Serializer<'a> {
+    /// Create a serializer writing into `buf` which is cleared first
+    pub fn new(buf: &'a mut Vec<u8>) -> Self {
+        Self { e: Encoder::new(buf), human_readable: false }
+    }
+
+    /// Report the format as human-readable to the serialized types (see [to_text])
+    ///
+    /// Off by default, so types that branch on it (like IP addresses) use their compact binary
+    /// form.
+    pub fn with_human_readable(mut self, human_readable: bool) -> Self {
+        self.human_readable = human_readable;
+        self
+    }
+
+    /// Serialize a value that must not be left out (i.e. a list item or the content of a variant)
+    fn required<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let start = self.e.position();
+        value.serialize(&mut *self)?;
+        if self.e.position() == start {
+            return Err(SerdeError::Unsupported("none or unit in a list or variant"));
+        }
+        Ok(())
+    }
+
+    /// Start a dictionary with the variant name as only key
+    fn variant(&mut self, name: &str) {
+        self.e.raw_u8(b'd');
+        self.e.str(name.as_bytes());
+    }
+
+    fn dict(&mut self, variant: bool) -> Dict<'_, 'a> {
+        self.e.raw_u8(b'd');
+        Dict { start: self.e.position(), s: self, entries: Vec::new(), variant }
+    }
+}
+
+impl<'s, 'a> ser::Serializer for &'s mut Serializer<'a> {
+    type Ok = ();
+    type Error = SerdeError;
+    type SerializeSeq = List<'s, 'a>;
+    type SerializeTuple = List<'s, 'a>;
+    type SerializeTupleStruct = List<'s, 'a>;
+    type SerializeTupleVariant = List<'s, 'a>;
+    type SerializeMap = Dict<'s, 'a>;
+    type SerializeStruct = Dict<'s, 'a>;
+    type SerializeStructVariant = Dict<'s, 'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), SerdeError> {
+        self.e.int(v.into());
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), SerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), SerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), SerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), SerdeError> {
+        self.e.int(v);
+        Ok(())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), SerdeError> {
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            Err(_) => {
+                self.serialize_u128(v.try_into().map_err(|_| SerdeError::Unsupported("integers below i64::MIN"))?)
+            }
+        }
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), SerdeError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), SerdeError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), SerdeError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), SerdeError> {
+        self.e.int_u64(v);
+        Ok(())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), SerdeError> {
+        self.e.int_u128(v).map_err(|_| SerdeError::Unsupported("integers above u64::MAX"))
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<(), SerdeError> {
+        Err(SerdeError::Unsupported("floating point numbers"))
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<(), SerdeError> {
+        Err(SerdeError::Unsupported("floating point numbers"))
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), SerdeError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), SerdeError> {
+        self.e.str(v.as_bytes());
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), SerdeError> {
+        self.e.str(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), SerdeError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, _: u32, variant: &'static str) -> Result<(), SerdeError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _: &'static str, value: &T) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.variant(variant);
+        self.required(value)?;
+        self.e.raw_u8(b'e');
+        Ok(())
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<List<'s, 'a>, SerdeError> {
+        self.e.raw_u8(b'l');
+        Ok(List { s: self, variant: false })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<List<'s, 'a>, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, len: usize) -> Result<List<'s, 'a>, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<List<'s, 'a>, SerdeError> {
+        self.variant(variant);
+        self.e.raw_u8(b'l');
+        Ok(List { s: self, variant: true })
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Dict<'s, 'a>, SerdeError> {
+        Ok(self.dict(false))
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Dict<'s, 'a>, SerdeError> {
+        Ok(self.dict(false))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<Dict<'s, 'a>, SerdeError> {
+        self.variant(variant);
+        Ok(self.dict(true))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.human_readable
+    }
+}
+
+/// Writes list items (see [Serializer])
+pub struct List<'s, 'a> {
+    s: &'s mut Serializer<'a>,
+    // Whether the list is wrapped in a variant dictionary
+    variant: bool,
+}
+
+impl List<'_, '_> {
+    fn finish(self) -> Result<(), SerdeError> {
+        self.s.e.raw_u8(b'e');
+        if self.variant {
+            self.s.e.raw_u8(b'e');
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for List<'_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.s.required(value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for List<'_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.s.required(value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for List<'_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.s.required(value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for List<'_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.s.required(value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+/// Writes dictionary entries in arbitrary order and sorts them at the end (see [Serializer])
+pub struct Dict<'s, 'a> {
+    s: &'s mut Serializer<'a>,
+    start: usize,
+    // Key and entry range of each entry in the output buffer
+    entries: Vec<(Range<usize>, Range<usize>)>,
+    // Whether the dictionary is wrapped in a variant dictionary
+    variant: bool,
+}
+
+impl Dict<'_, '_> {
+    fn key<K: Serialize + ?Sized>(&mut self, key: &K) -> Result<(), SerdeError> {
+        let start = self.s.e.position();
+        let len = key.serialize(MapKey(&mut self.s.e, self.s.human_readable))?;
+        let end = self.s.e.position();
+        self.entries.push((end - len..end, start..end));
+        Ok(())
+    }
+
+    fn value<V: Serialize + ?Sized>(&mut self, value: &V) -> Result<(), SerdeError> {
+        let start = self.s.e.position();
+        value.serialize(&mut *self.s)?;
+        let end = self.s.e.position();
+        let Some(entry) = self.entries.last_mut() else {
+            return Err(SerdeError::Custom("map value without key".into()));
+        };
+        if end == start {
+            // `None` and `()` are left out together with their key
+            self.s.e.truncate(entry.1.start);
+            self.entries.pop();
+        } else {
+            entry.1.end = end;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), SerdeError> {
+        if self.s.e.sort_entries(self.start, self.entries).is_err() {
+            return Err(SerdeError::DuplicateKey);
+        }
+        self.s.e.raw_u8(b'e');
+        if self.variant {
+            self.s.e.raw_u8(b'e');
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for Dict<'_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        self.key(key)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.value(value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for Dict<'_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError> {
+        self.key(key)?;
+        self.value(value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for Dict<'_, '_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError> {
+        self.key(key)?;
+        self.value(value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+/// Writes a dictionary key as string and returns its length, and whether it is human-readable
+struct MapKey<'e, 'a>(&'e mut Encoder<'a>, bool);
+
+impl MapKey<'_, '_> {
+    fn str(self, s: &[u8]) -> Result<usize, SerdeError> {
+        self.0.str(s);
+        Ok(s.len())
+    }
+}
+
+fn unsupported_key() -> SerdeError {
+    SerdeError::Unsupported("map keys other than strings and integers")
+}
+
+impl ser::Serializer for MapKey<'_, '_> {
+    type Ok = usize;
+    type Error = SerdeError;
+    type SerializeSeq = Impossible<usize, SerdeError>;
+    type SerializeTuple = Impossible<usize, SerdeError>;
+    type SerializeTupleStruct = Impossible<usize, SerdeError>;
+    type SerializeTupleVariant = Impossible<usize, SerdeError>;
+    type SerializeMap = Impossible<usize, SerdeError>;
+    type SerializeStruct = Impossible<usize, SerdeError>;
+    type SerializeStructVariant = Impossible<usize, SerdeError>;
+
+    fn serialize_bool(self, _: bool) -> Result<usize, SerdeError> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<usize, SerdeError> {
+        self.str(v.to_string().as_bytes())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<usize, SerdeError> {
+        self.str(v.to_string().as_bytes())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<usize, SerdeError> {
+        self.str(v.to_string().as_bytes())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<usize, SerdeError> {
+        self.str(v.to_string().as_bytes())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<usize, SerdeError> {
+        self.str(v.to_string().as_bytes())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<usize, SerdeError> {
+        self.str(v.to_string().as_bytes())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<usize, SerdeError> {
+        self.str(v.to_string().as_bytes())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<usize, SerdeError> {
+        self.str(v.to_string().as_bytes())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<usize, SerdeError> {
+        self.str(v.to_string().as_bytes())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<usize, SerdeError> {
+        self.str(v.to_string().as_bytes())
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<usize, SerdeError> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<usize, SerdeError> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_char(self, v: char) -> Result<usize, SerdeError> {
+        self.str(v.encode_utf8(&mut [0; 4]).as_bytes())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<usize, SerdeError> {
+        self.str(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<usize, SerdeError> {
+        self.str(v)
+    }
+
+    fn serialize_none(self) -> Result<usize, SerdeError> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _: &T) -> Result<usize, SerdeError> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_unit(self) -> Result<usize, SerdeError> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<usize, SerdeError> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, _: u32, variant: &'static str) -> Result<usize, SerdeError> {
+        self.str(variant.as_bytes())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _: &'static str, value: &T) -> Result<usize, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<usize, SerdeError> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, SerdeError> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, SerdeError> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeTupleStruct, SerdeError> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, SerdeError> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, SerdeError> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, SerdeError> {
+        Err(unsupported_key())
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.1
+    }
+}
+
+impl ser::Error for SerdeError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+    use std::collections::{BTreeMap, HashMap};
+
+    #[derive(Serialize)]
+    enum Message {
+        Ping,
+        Error(i64, String),
+        Announce { port: u16, token: Option<Vec<u8>> },
+        Id(#[serde(with = "Bytes")] [u8; 2]),
+    }
+
+    // Minimal stand-in for `serde_bytes`
+    struct Bytes;
+
+    impl Bytes {
+        fn serialize<S: ser::Serializer>(v: &[u8; 2], s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(v)
+        }
+    }
+
+    #[test]
+    fn test_primitives() {
+        assert_eq!(to_bytes(&true).unwrap(), b"i1e");
+        assert_eq!(to_bytes(&-7i8).unwrap(), b"i-7e");
+        assert_eq!(to_bytes(&u64::MAX).unwrap(), b"i18446744073709551615e");
+        assert_eq!(to_bytes(&i128::MIN), Err(SerdeError::Unsupported("integers below i64::MIN")));
+        assert_eq!(to_bytes(&(u128::from(u64::MAX) + 1)), Err(SerdeError::Unsupported("integers above u64::MAX")));
+        assert_eq!(to_bytes("ä").unwrap(), b"2:\xc3\xa4");
+        assert_eq!(to_bytes(&Some(1)).unwrap(), b"i1e");
+        assert_eq!(to_bytes(&None::<i64>), Err(SerdeError::Unsupported("none or unit as root value")));
+        assert_eq!(to_bytes(&(1, "a")).unwrap(), b"li1e1:ae");
+        assert_eq!(to_bytes(&[1u8, 2]).unwrap(), b"li1ei2ee");
+    }
+
+    #[test]
+    fn test_enums() {
+        assert_eq!(to_bytes(&Message::Ping).unwrap(), b"4:Ping");
+        assert_eq!(to_bytes(&Message::Error(201, "x".into())).unwrap(), b"d5:Errorli201e1:xee");
+        let announce = Message::Announce { port: 6881, token: Some(b"ab".to_vec()) };
+        assert_eq!(to_bytes(&announce).unwrap(), b"d8:Announced4:porti6881e5:tokenli97ei98eeee");
+        let announce = Message::Announce { port: 6881, token: None };
+        assert_eq!(to_bytes(&announce).unwrap(), b"d8:Announced4:porti6881eee");
+        assert_eq!(to_bytes(&Message::Id(*b"xy")).unwrap(), b"d2:Id2:xye");
+        assert_eq!(to_bytes(&vec![Some(1), None]), Err(SerdeError::Unsupported("none or unit in a list or variant")));
+    }
+
+    #[test]
+    fn test_human_readable() {
+        use std::net::{IpAddr, Ipv6Addr};
+        let m = BTreeMap::from([(IpAddr::V6(Ipv6Addr::LOCALHOST), 1)]);
+        assert_eq!(to_text(&m).unwrap(), r#"{"::1": 1}"#);
+        assert_eq!(to_bytes(&m), Err(SerdeError::Unsupported("map keys other than strings and integers")));
+        assert_eq!(to_text(&Message::Id(*b"\x00\xff")).unwrap(), r#"{"Id": 00ff}"#);
+        assert_eq!(to_text(&()), Err(SerdeError::Unsupported("none or unit as root value")));
+    }
+
+    #[test]
+    fn test_maps() {
+        let m = HashMap::from([(3u32, "c"), (10, "a"), (2, "b")]);
+        assert_eq!(to_bytes(&m).unwrap(), b"d2:101:a1:21:b1:31:ce");
+        let m = BTreeMap::from([("b", None), ("a", Some(1)), ("c", None)]);
+        assert_eq!(to_bytes(&m).unwrap(), b"d1:ai1ee");
+        let m = BTreeMap::from([((), 1)]);
+        assert_eq!(to_bytes(&m), Err(SerdeError::Unsupported("map keys other than strings and integers")));
+
+        #[derive(Serialize)]
+        struct Clash {
+            #[serde(rename = "1")]
+            a: i64,
+            #[serde(flatten)]
+            b: BTreeMap<u8, i64>,
+        }
+        let c = Clash { a: 1, b: BTreeMap::from([(1, 2)]) };
+        assert_eq!(to_bytes(&c), Err(SerdeError::DuplicateKey));
+    }
+}
@@ -0,0 +1,288 @@
+use super::{Str, Value};
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+/// Strings are written as bytes, except in human-readable formats where valid UTF-8 is written as
+/// text. Raw values are decoded and serialized like any other.
+///
+/// Formats without a byte type (like JSON) write bytes as a list of numbers, which reads back as
+/// a [Value::List]. Non-UTF-8 strings therefore only survive a round trip through such formats if
+/// they are encoded first (e.g. by storing the [encoded](Value::encode) value as bytes).
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let v = dict! { "a" => list![int!(1), str!("x")] };
+/// assert_eq!(to_bytes(&v).unwrap(), v.encode());
+/// assert_eq!(from_bytes::<Value>(&v.encode()).unwrap(), v);
+/// ```
+impl Serialize for Value<'_> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Int(i) => s.serialize_i64(*i),
+            Value::Str(x) => Bytes(x).serialize(s),
+            Value::List(l) => s.collect_seq(l),
+            Value::Dict(d) => {
+                let mut m = s.serialize_map(Some(d.len()))?;
+                for (k, v) in d {
+                    m.serialize_entry(&Bytes(k), v)?;
+                }
+                m.end()
+            }
+            Value::Raw(x) => match Value::unraw(x) {
+                Ok(v) => v.serialize(s),
+                Err(e) => Err(serde::ser::Error::custom(e)),
+            },
+        }
+    }
+}
+
+struct Bytes<'b>(&'b [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match std::str::from_utf8(self.0) {
+            Ok(x) if s.is_human_readable() => s.serialize_str(x),
+            _ => s.serialize_bytes(self.0),
+        }
+    }
+}
+
+/// Strings are copied, so the value may outlive the input (like a `Value<'static>` field)
+///
+/// Booleans are read as `0` or `1`. Floats, nulls and map keys other than strings are rejected.
+/// Use [Value::deserialize_borrowed] to borrow strings from the input instead.
+///
+/// ```rust
+/// use bencode_minimal::*;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Handshake {
+///     v: String,
+///     #[serde(flatten)]
+///     extensions: Value<'static>,
+/// }
+///
+/// let h: Handshake = from_bytes(b"d1:md6:ut_pexi1ee1:v5:Tix 1e").unwrap();
+/// assert_eq!(h.v, "Tix 1");
+/// assert_eq!(h.extensions.get_path("m.ut_pex"), Some(&int!(1)));
+/// ```
+impl<'de, 'a> Deserialize<'de> for Value<'a> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_any(ValueVisitor(Copied, PhantomData))
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Deserialize a value borrowing its strings from the input where the format allows it
+    ///
+    /// Meant for `#[serde(deserialize_with = "Value::deserialize_borrowed")]` on fields of types
+    /// that borrow from the input anyway.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    /// use serde::Deserialize;
+    /// use std::borrow::Cow;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Query<'a> {
+    ///     #[serde(borrow, deserialize_with = "Value::deserialize_borrowed")]
+    ///     a: Value<'a>,
+    /// }
+    ///
+    /// let q: Query = from_bytes(b"d1:ad2:id3:abcee").unwrap();
+    /// assert!(matches!(q.a.get_path("id"), Some(Value::Str(Cow::Borrowed(_)))));
+    /// ```
+    pub fn deserialize_borrowed<D: Deserializer<'a>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_any(ValueVisitor(Borrowed, PhantomData))
+    }
+}
+
+/// How a visitor turns strings borrowed from the input into strings of the value
+trait Strings<'de, 'a> {
+    fn bytes(b: &'de [u8]) -> Str<'a>;
+}
+
+#[derive(Clone, Copy)]
+struct Copied;
+
+impl<'a> Strings<'_, 'a> for Copied {
+    fn bytes(b: &[u8]) -> Str<'a> {
+        Cow::Owned(b.to_vec())
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Borrowed;
+
+impl<'de> Strings<'de, 'de> for Borrowed {
+    fn bytes(b: &'de [u8]) -> Str<'de> {
+        Cow::Borrowed(b)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ValueVisitor<'a, S>(S, PhantomData<Value<'a>>);
+
+impl<'de, 'a, S: Strings<'de, 'a> + Copy> DeserializeSeed<'de> for ValueVisitor<'a, S> {
+    type Value = Value<'a>;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Value<'a>, D::Error> {
+        d.deserialize_any(self)
+    }
+}
+
+impl<'de, 'a, S: Strings<'de, 'a> + Copy> Visitor<'de> for ValueVisitor<'a, S> {
+    type Value = Value<'a>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("an integer, string, list or map")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value<'a>, E> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value<'a>, E> {
+        Ok(Value::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value<'a>, E> {
+        let i = v.try_into().map_err(|_| E::invalid_value(de::Unexpected::Unsigned(v), &self))?;
+        Ok(Value::Int(i))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value<'a>, E> {
+        Ok(Value::Str(Cow::Owned(v.into())))
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Value<'a>, E> {
+        Ok(Value::Str(S::bytes(v.as_bytes())))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value<'a>, E> {
+        Ok(Value::Str(Cow::Owned(v.into_bytes())))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Value<'a>, E> {
+        Ok(Value::Str(Cow::Owned(v.into())))
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, v: &'de [u8]) -> Result<Value<'a>, E> {
+        Ok(Value::Str(S::bytes(v)))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Value<'a>, E> {
+        Ok(Value::Str(Cow::Owned(v)))
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<Value<'a>, D::Error> {
+        d.deserialize_any(self)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, d: D) -> Result<Value<'a>, D::Error> {
+        d.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value<'a>, A::Error> {
+        let mut l = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(v) = seq.next_element_seed(self)? {
+            l.push(v);
+        }
+        Ok(Value::List(l))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value<'a>, A::Error> {
+        let mut d = BTreeMap::new();
+        while let Some(mut k) = map.next_key_seed(self)? {
+            let Value::Str(k) = &mut k else {
+                return Err(de::Error::custom("dictionary keys must be strings"));
+            };
+            let k = std::mem::take(k);
+            if d.contains_key(&k) {
+                return Err(de::Error::custom("duplicate dictionary key"));
+            }
+            d.insert(k, map.next_value_seed(self)?);
+        }
+        Ok(Value::Dict(d))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_bytes, to_bytes};
+    use serde::Deserialize;
+
+    fn s(x: &[u8]) -> Value<'_> {
+        Value::Str(Cow::Borrowed(x))
+    }
+
+    fn sample() -> Value<'static> {
+        let node = Value::Dict(
+            [(Cow::Borrowed(b"id".as_ref()), s(b"\xff\x00")), (Cow::Borrowed(b"port"), Value::Int(-1))].into(),
+        );
+        Value::List(vec![node, s(b"text"), Value::List(vec![])])
+    }
+
+    #[test]
+    fn test_bencode() {
+        let v = sample();
+        let buf = to_bytes(&v).unwrap();
+        assert_eq!(buf, v.encode());
+        let w: Value = from_bytes(&buf).unwrap();
+        assert_eq!(w, v);
+        let r = Value::List(vec![Value::raw(v.encode()).unwrap()]);
+        assert_eq!(to_bytes(&r).unwrap(), r.encode());
+        assert_eq!(
+            crate::Deserializer::new(r).deserialize_any(ValueVisitor(Borrowed, PhantomData)).unwrap(),
+            Value::List(vec![v.clone()])
+        );
+        assert!(matches!(w.get_path("[1]"), Some(Value::Str(Cow::Owned(_)))));
+        let w = Value::deserialize_borrowed(crate::Deserializer::new(Value::decode(&buf, 100).unwrap())).unwrap();
+        assert!(matches!(w.get_path("[1]"), Some(Value::Str(Cow::Borrowed(_)))));
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Message {
+        t: String,
+        extensions: Value<'static>,
+    }
+
+    fn parse<T: de::DeserializeOwned>(buf: &[u8]) -> T {
+        from_bytes(buf).unwrap()
+    }
+
+    #[test]
+    fn test_owned_field() {
+        let buf = b"d10:extensionsd1:ai1e1:bl1:xee1:t2:aae".to_vec();
+        let m: Message = parse(&buf);
+        drop(buf);
+        assert_eq!(m.t, "aa");
+        assert_eq!(m.extensions, Value::decode(b"d1:ai1e1:bl1:xee", 10).unwrap());
+        assert!(matches!(m.extensions.get_path("b[0]"), Some(Value::Str(Cow::Owned(_)))));
+        let m: Message = serde_json::from_str(r#"{"t":"aa","extensions":{"x":"y"}}"#).unwrap();
+        assert_eq!(m.extensions.encode(), b"d1:x1:ye");
+    }
+
+    #[test]
+    fn test_json() {
+        let v = sample();
+        let json = serde_json::to_string(&v).unwrap();
+        assert_eq!(json, r#"[{"id":[255,0],"port":-1},"text",[]]"#);
+        let w: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(w.get_path("[1]"), Some(&s(b"text")));
+        assert_eq!(w.get_path("[0].id"), Some(&Value::List(vec![Value::Int(255), Value::Int(0)])));
+
+        let v: Value = serde_json::from_str(r#"{"a":true,"b":{"c":"ä\n"}}"#).unwrap();
+        assert_eq!(v.encode(), b"d1:ai1e1:bd1:c3:\xc3\xa4\nee");
+        assert!(serde_json::from_str::<Value>(r#"{"a":1.5}"#).is_err());
+        assert!(serde_json::from_str::<Value>(r#"{"a":null}"#).is_err());
+        assert!(serde_json::from_str::<Value>(r#"{"a":1,"a":2}"#).is_err());
+        assert!(serde_json::from_str::<Value>("18446744073709551615").is_err());
+    }
+}
@@ -0,0 +1,251 @@
+use super::path::{self, PathTree, Segment, Step};
+use super::{Encodable, Encoder, Kind, Path, PathError, Value};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// An immutable [Value] with structurally shared, reference-counted nodes
+///
+/// Cloning is O(1) and gives an independent snapshot. Modifications through [Self::set_path] and
+/// [Self::remove_path] copy only the nodes along the path and keep sharing everything else with
+/// earlier snapshots, so a service can hand out snapshots of a large state while it keeps updating
+/// it. The value does not borrow from its input and is `Send + Sync`.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let v = dict! { "nodes" => list![str!("a"), str!("b")], "peers" => dict! { "x" => int!(1) } };
+/// let mut state = SharedValue::from(&v);
+/// let snapshot = state.clone();
+///
+/// state.set_path("peers.y", int!(2).into()).unwrap();
+/// assert_eq!(snapshot.to_value(), v);
+/// assert_eq!(state.get_path("peers.y"), Some(&SharedValue::Int(2)));
+/// // The untouched list is still shared between both versions
+/// assert!(state.get(b"nodes").unwrap().ptr_eq(snapshot.get(b"nodes").unwrap()));
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub enum SharedValue {
+    Int(i64),
+    Str(Arc<[u8]>),
+    List(Arc<Vec<SharedValue>>),
+    Dict(Arc<BTreeMap<Arc<[u8]>, SharedValue>>),
+}
+
+impl SharedValue {
+    pub fn kind(&self) -> Kind {
+        match self {
+            Self::Int(_) => Kind::Int,
+            Self::Str(_) => Kind::Str,
+            Self::List(_) => Kind::List,
+            Self::Dict(_) => Kind::Dict,
+        }
+    }
+
+    /// Whether both values share the same node (equal integers count as shared)
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => a == b,
+            (Self::Str(a), Self::Str(b)) => Arc::ptr_eq(a, b),
+            (Self::List(a), Self::List(b)) => Arc::ptr_eq(a, b),
+            (Self::Dict(a), Self::Dict(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// Get the value for `key` if this is a dictionary
+    pub fn get(&self, key: &[u8]) -> Option<&Self> {
+        match self {
+            Self::Dict(d) => d.get(key),
+            _ => None,
+        }
+    }
+
+    /// Get the item at `index` if this is a list
+    pub fn get_index(&self, index: usize) -> Option<&Self> {
+        match self {
+            Self::List(l) => l.get(index),
+            _ => None,
+        }
+    }
+
+    /// Get the value at `path` (see [Value::get_path])
+    pub fn get_path(&self, path: impl Into<Path>) -> Option<&Self> {
+        path.into().segments().iter().try_fold(self, |v, s| match s {
+            Segment::Key(k) => v.get(k),
+            Segment::Index(i) => v.get_index(*i),
+        })
+    }
+
+    /// Set the value at `path` and return the previous one (see [Value::set_path])
+    ///
+    /// Nodes along the path that are shared with other snapshots are copied first. Copying a node
+    /// clones the references to all of its children, so the cost is the total width of the
+    /// containers along the path rather than its depth. Nothing is copied if the path is invalid.
+    pub fn set_path(&mut self, path: impl Into<Path>, value: SharedValue) -> Result<Option<Self>, PathError> {
+        path::set_path(self, &path.into(), value)
+    }
+
+    /// Remove the value at `path` and return it (see [Value::remove_path])
+    pub fn remove_path(&mut self, path: impl Into<Path>) -> Option<Self> {
+        let path = path.into();
+        let (last, parents) = path.segments().split_last()?;
+        // Check first to not copy any nodes if there is nothing to remove
+        self.get_path(&path)?;
+        let mut v = self;
+        for s in parents {
+            v = match (v, s) {
+                (Self::Dict(d), Segment::Key(k)) => Arc::make_mut(d).get_mut(k.as_slice())?,
+                (Self::List(l), Segment::Index(i)) => Arc::make_mut(l).get_mut(*i)?,
+                _ => return None,
+            };
+        }
+        match (v, last) {
+            (Self::Dict(d), Segment::Key(k)) => Arc::make_mut(d).remove(k.as_slice()),
+            (Self::List(l), Segment::Index(i)) => Some(Arc::make_mut(l).remove(*i)),
+            _ => None,
+        }
+    }
+
+    /// Convert into a [Value] borrowing the strings from this one
+    pub fn to_value(&self) -> Value<'_> {
+        match self {
+            Self::Int(i) => Value::Int(*i),
+            Self::Str(s) => Value::Str(Cow::Borrowed(s)),
+            Self::List(l) => Value::List(l.iter().map(Self::to_value).collect()),
+            Self::Dict(d) => Value::Dict(d.iter().map(|(k, v)| (Cow::Borrowed(k.as_ref()), v.to_value())).collect()),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        Encoder::new(&mut buf).encode(self);
+        buf
+    }
+}
+
+impl PathTree for SharedValue {
+    fn container_for(segment: &Segment) -> Self {
+        match segment {
+            Segment::Key(_) => Self::Dict(Arc::default()),
+            Segment::Index(_) => Self::List(Arc::default()),
+        }
+    }
+
+    fn step(&self, segment: &Segment) -> Step<'_, Self> {
+        match (self, segment) {
+            (Self::Dict(d), Segment::Key(k)) => d.get(k.as_slice()).map_or(Step::Missing, Step::Child),
+            (Self::List(l), Segment::Index(n)) => match l.get(*n) {
+                Some(x) => Step::Child(x),
+                None if *n == l.len() => Step::Missing,
+                None => Step::OutOfRange,
+            },
+            _ => Step::NotAContainer,
+        }
+    }
+
+    fn child_mut(&mut self, segment: &Segment, next: &Segment) -> &mut Self {
+        match (self, segment) {
+            (Self::Dict(d), Segment::Key(k)) => {
+                let d = Arc::make_mut(d);
+                if !d.contains_key(k.as_slice()) {
+                    d.insert(k.as_slice().into(), Self::container_for(next));
+                }
+                d.get_mut(k.as_slice()).expect("inserted if missing")
+            }
+            (Self::List(l), Segment::Index(n)) => {
+                let l = Arc::make_mut(l);
+                if *n == l.len() {
+                    l.push(Self::container_for(next));
+                }
+                &mut l[*n]
+            }
+            _ => unreachable!("the path is checked before"),
+        }
+    }
+
+    fn replace(&mut self, segment: &Segment, value: Self) -> Option<Self> {
+        match (self, segment) {
+            (Self::Dict(d), Segment::Key(k)) => Arc::make_mut(d).insert(k.as_slice().into(), value),
+            (Self::List(l), Segment::Index(n)) if *n == l.len() => {
+                Arc::make_mut(l).push(value);
+                None
+            }
+            (Self::List(l), Segment::Index(n)) => Some(std::mem::replace(&mut Arc::make_mut(l)[*n], value)),
+            _ => unreachable!("the path is checked before"),
+        }
+    }
+}
+
+impl From<&Value<'_>> for SharedValue {
+    fn from(value: &Value<'_>) -> Self {
+        match value {
+            Value::Int(i) => Self::Int(*i),
+            Value::Str(s) => Self::Str(s.as_ref().into()),
+            Value::List(l) => Self::List(Arc::new(l.iter().map(Self::from).collect())),
+            Value::Dict(d) => Self::Dict(Arc::new(d.iter().map(|(k, v)| (k.as_ref().into(), v.into())).collect())),
+            Value::Raw(s) => Self::from(Value::unraw_lossy(s)),
+        }
+    }
+}
+
+impl From<Value<'_>> for SharedValue {
+    fn from(value: Value<'_>) -> Self {
+        Self::from(&value)
+    }
+}
+
+impl Encodable for SharedValue {
+    fn encode(&self, e: &mut Encoder) {
+        match self {
+            Self::Int(i) => e.int(*i),
+            Self::Str(s) => e.str(s),
+            Self::List(l) => e.encode(l.as_slice()),
+            Self::Dict(d) => e.encode(d.as_ref()),
+        }
+    }
+}
+
+impl std::fmt::Debug for SharedValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self.to_value(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(x: &str) -> Value<'_> {
+        Value::Str(Cow::Borrowed(x.as_bytes()))
+    }
+
+    #[test]
+    fn test_copy_on_write() {
+        let v = Value::List(vec![Value::Int(1), Value::Dict([(Cow::Borrowed(b"a".as_ref()), s("x"))].into())]);
+        let mut a = SharedValue::from(&v);
+        let b = a.clone();
+        assert_eq!(a.encode(), v.encode());
+        assert_eq!(format!("{:?}", a), format!("{:?}", v));
+
+        assert_eq!(a.set_path("[1].b[0]", SharedValue::Int(2)), Ok(None));
+        assert_eq!(a.set_path("[0].x", SharedValue::Int(2)), Err(PathError::NotAContainer("[0]".into())));
+        assert_eq!(a.set_path("[3]", SharedValue::Int(2)), Err(PathError::OutOfRange("[3]".into())));
+        // Failing paths neither modify nor unshare anything
+        let c = a.clone();
+        assert_eq!(a.set_path("[1].x.y[3]", SharedValue::Int(2)), Err(PathError::OutOfRange("[1].x.y[3]".into())));
+        assert_eq!(a.set_path("[1].a.b", SharedValue::Int(2)), Err(PathError::NotAContainer("[1].a".into())));
+        assert!(a.ptr_eq(&c));
+        assert_eq!(a.encode(), b"li1ed1:a1:x1:bli2eeee");
+        assert_eq!(b.to_value(), v);
+        assert!(a.get_path("[1].a").unwrap().ptr_eq(b.get_path("[1].a").unwrap()));
+
+        let c = a.clone();
+        assert_eq!(a.remove_path("[1].c"), None);
+        assert!(a.ptr_eq(&c));
+        assert_eq!(a.remove_path("[1].b"), Some(SharedValue::from(Value::List(vec![Value::Int(2)]))));
+        assert_eq!(a, b);
+        assert!(!a.ptr_eq(&b));
+        assert_eq!(a.set_path("", SharedValue::Int(3)), Ok(Some(b)));
+    }
+}
@@ -0,0 +1,160 @@
+use super::decoder::{Decoder, DICT_ENTRY_SIZE, LIST_ITEM_SIZE};
+use super::{DecodeError, DecodeOptions, Path, Segment, Value};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// The input ranges of a decoded value and all of its sub-values (see [Value::decode_with_spans])
+///
+/// The tree mirrors the value: lists have the spans of their items and dictionaries those of their
+/// values by key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Spans {
+    range: Range<usize>,
+    children: Children,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Children {
+    None,
+    List(Vec<Spans>),
+    Dict(BTreeMap<Vec<u8>, Spans>),
+}
+
+impl Spans {
+    /// The range of the value in the input
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// Assume a dictionary and get the spans of the value for the given key
+    pub fn get(&self, key: &[u8]) -> Option<&Self> {
+        match &self.children {
+            Children::Dict(d) => d.get(key),
+            _ => None,
+        }
+    }
+
+    /// Assume a list and get the spans of the item at the given index
+    pub fn get_index(&self, index: usize) -> Option<&Self> {
+        match &self.children {
+            Children::List(l) => l.get(index),
+            _ => None,
+        }
+    }
+
+    /// The range of the value at `path` in the input (see [Value::get_path])
+    pub fn get_path(&self, path: impl Into<Path>) -> Option<Range<usize>> {
+        let path = path.into();
+        let mut s = self;
+        for segment in path.segments() {
+            s = match segment {
+                Segment::Key(k) => s.get(k)?,
+                Segment::Index(i) => s.get_index(*i)?,
+            };
+        }
+        Some(s.range())
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Like [Self::decode_with], but also return where each sub-value is located in `buf`
+    ///
+    /// This allows to process the exact input bytes of a sub-value, like hashing the `info`
+    /// dictionary of a torrent file as it was received even if it is not canonically encoded.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let buf = b"d8:announce3:foo4:infod6:lengthi042eee";
+    /// let (v, spans) = Value::decode_with_spans(buf, &DecodeOptions::default()).unwrap();
+    /// assert_eq!(spans.range(), 0..buf.len());
+    /// let info = spans.get_path("info").unwrap();
+    /// assert_eq!(&buf[info], b"d6:lengthi042ee");
+    /// assert_eq!(v.get_path("info").unwrap().encode(), b"d6:lengthi42ee");
+    /// ```
+    pub fn decode_with_spans(buf: &'a [u8], opts: &DecodeOptions) -> Result<(Self, Spans), DecodeError> {
+        let mut d = Decoder::with_options(buf, opts);
+        let r = take(&mut d, buf);
+        d.finish(r.as_ref().map(|(v, _)| v.kind()).map_err(|e| *e));
+        r
+    }
+}
+
+fn take<'a>(d: &mut Decoder<'a>, buf: &'a [u8]) -> Result<(Value<'a>, Spans), DecodeError> {
+    let start = buf.len() - d.remaining();
+    let (value, children) = match d.peek_u8()? {
+        b'l' => {
+            d.begin_list()?;
+            let (mut list, mut spans) = (Vec::new(), Vec::new());
+            while d.next_item()? {
+                d.alloc(LIST_ITEM_SIZE)?;
+                let (v, s) = take(d, buf)?;
+                list.push(v);
+                spans.push(s);
+            }
+            (Value::List(list), Children::List(spans))
+        }
+        b'd' => {
+            d.begin_dict()?;
+            let (mut dict, mut spans) = (BTreeMap::new(), BTreeMap::new());
+            while let Some(k) = d.next_key()? {
+                d.alloc(DICT_ENTRY_SIZE)?;
+                let (v, s) = take(d, buf)?;
+                let path_key = k.to_vec();
+                if d.insert_entry(&mut dict, k, v)? {
+                    spans.insert(path_key, s);
+                }
+            }
+            (Value::Dict(dict), Children::Dict(spans))
+        }
+        _ => (d.take_any()?, Children::None),
+    };
+    Ok((value, Spans { range: start..buf.len() - d.remaining(), children }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DuplicateKeys;
+
+    #[test]
+    fn test_spans() {
+        let buf = b"d1:bli1e3:abcd1:xleee1:ai-0ee";
+        let opts = DecodeOptions::default();
+        let (v, spans) = Value::decode_with_spans(buf, &opts).unwrap();
+        assert_eq!(v, Value::decode_with(buf, &opts).unwrap());
+        for (path, raw) in [
+            ("", buf.as_ref()),
+            ("a", b"i-0e"),
+            ("b", b"li1e3:abcd1:xleee"),
+            ("b[0]", b"i1e"),
+            ("b[1]", b"3:abc"),
+            ("b[2].x", b"le"),
+        ] {
+            assert_eq!(&buf[spans.get_path(path).unwrap()], raw, "{}", path);
+        }
+        assert_eq!(spans.get_path("b[3]"), None);
+        assert_eq!(spans.get_path("a.x"), None);
+        assert_eq!(spans.get(b"b").and_then(|s| s.get_index(1)).map(Spans::range), Some(8..13));
+    }
+
+    #[test]
+    fn test_spans_errors() {
+        let opts = DecodeOptions::default();
+        assert_eq!(Value::decode_with_spans(b"d1:ai1e1:ai2ee", &opts), Err(DecodeError::Invalid));
+        assert_eq!(Value::decode_with_spans(b"li1e", &opts), Err(DecodeError::Incomplete { needed: 1 }));
+        assert_eq!(Value::decode_with_spans(b"llee", &opts.clone().with_max_depth(1)), Err(DecodeError::LimitExceeded));
+
+        for (policy, raw) in [(DuplicateKeys::FirstWins, b"i1e"), (DuplicateKeys::LastWins, b"i2e")] {
+            let buf = b"d1:ai1e1:ai2ee";
+            let (v, spans) = Value::decode_with_spans(buf, &opts.clone().with_duplicate_keys(policy)).unwrap();
+            assert_eq!(&buf[spans.get_path("a").unwrap()], raw);
+            assert_eq!(v.get_path("a").unwrap().encode(), raw);
+        }
+
+        let opts = DecodeOptions::default().with_strict(true);
+        assert_eq!(Value::decode_with_spans(b"d1:bi1e1:ai1ee", &opts), Err(DecodeError::Invalid));
+        assert_eq!(Value::decode_with_spans(b"ld1:bi1e1:ai1eee", &opts), Err(DecodeError::Invalid));
+        assert!(Value::decode_with_spans(b"d1:ad1:bi1ee1:bi1ee", &opts).is_ok());
+    }
+}
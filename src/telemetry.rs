@@ -0,0 +1,83 @@
+//! Metrics emitted with the `metrics` feature
+//!
+//! - `bencode_decoded_total` (counter, label `kind`): values decoded by [Value::decode] and friends
+//! - `bencode_decode_failures_total` (counter, label `error`: `incomplete`, `invalid`,
+//!   `limit_exceeded`, `length_too_large` or `cancelled`): failed decodes, `limit_exceeded` counting
+//!   inputs that exceeded the budget
+//! - `bencode_encoded_bytes` (histogram): encoded sizes of [Value::encode] and friends
+use super::{DecodeError, Kind};
+
+pub(crate) fn record_decode(r: Result<Kind, DecodeError>) {
+    // Literal labels make static keys, so recording does not allocate
+    #[cfg(feature = "metrics")]
+    macro_rules! count {
+        ($name:literal, $label:literal => $value:literal) => {
+            metrics::counter!($name, $label => $value).increment(1)
+        };
+    }
+    #[cfg(feature = "metrics")]
+    match r {
+        Ok(Kind::Int) => count!("bencode_decoded_total", "kind" => "int"),
+        Ok(Kind::Str) => count!("bencode_decoded_total", "kind" => "str"),
+        Ok(Kind::List) => count!("bencode_decoded_total", "kind" => "list"),
+        Ok(Kind::Dict) => count!("bencode_decoded_total", "kind" => "dict"),
+        Ok(Kind::Raw) => count!("bencode_decoded_total", "kind" => "raw"),
+        Err(DecodeError::Incomplete { .. }) => count!("bencode_decode_failures_total", "error" => "incomplete"),
+        Err(DecodeError::Invalid) => count!("bencode_decode_failures_total", "error" => "invalid"),
+        Err(DecodeError::LimitExceeded) => count!("bencode_decode_failures_total", "error" => "limit_exceeded"),
+        Err(DecodeError::LengthTooLarge) => count!("bencode_decode_failures_total", "error" => "length_too_large"),
+        Err(DecodeError::Cancelled) => count!("bencode_decode_failures_total", "error" => "cancelled"),
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = r;
+}
+
+pub(crate) fn record_encode(len: usize) {
+    #[cfg(feature = "metrics")]
+    metrics::histogram!("bencode_encoded_bytes").record(len as f64);
+    #[cfg(not(feature = "metrics"))]
+    let _ = len;
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use crate::Value;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    #[test]
+    fn test_metrics() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            let _ = Value::decode(b"li1ee", 10);
+            let _ = Value::decode(b"li1ee", 0);
+            let _ = Value::decode(b"li1e", 10);
+            let _ = Value::Int(42).encode();
+        });
+        let mut m: Vec<_> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(k, _, _, v)| {
+                let k = k.key();
+                let labels: Vec<_> = k.labels().map(|l| format!("{}={}", l.key(), l.value())).collect();
+                let v = match v {
+                    DebugValue::Counter(n) => n.to_string(),
+                    DebugValue::Histogram(h) => format!("{:?}", h.iter().map(|x| x.0).collect::<Vec<_>>()),
+                    DebugValue::Gauge(g) => g.0.to_string(),
+                };
+                format!("{}{:?} {}", k.name(), labels, v)
+            })
+            .collect();
+        m.sort();
+        assert_eq!(
+            m,
+            vec![
+                "bencode_decode_failures_total[\"error=incomplete\"] 1",
+                "bencode_decode_failures_total[\"error=limit_exceeded\"] 1",
+                "bencode_decoded_total[\"kind=list\"] 1",
+                "bencode_encoded_bytes[] [4.0]",
+            ]
+        );
+    }
+}
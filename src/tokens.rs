@@ -0,0 +1,241 @@
+use super::{DecodeError, DecodeOptions, Decoder, Int, Kind, Str};
+use std::iter::FusedIterator;
+
+/// A piece of an encoded value as produced by [Tokens]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Token<'a> {
+    Int(Int),
+    /// A byte string, which is a key if it follows [Token::DictStart] or a value in a dictionary
+    Str(Str<'a>),
+    ListStart,
+    DictStart,
+    /// The end of the innermost open list or dictionary
+    End,
+}
+
+/// A pull parser yielding the tokens of a single value without building it
+///
+/// This allows to process large documents (like a torrent with thousands of files) in constant
+/// memory apart from the nesting depth. The syntax, nesting depth, step limit and string limit
+/// are checked like when decoding, but as nothing is built the alloc and size limits do not apply
+/// and duplicate dictionary keys are not detected (unsorted ones are in
+/// [strict](DecodeOptions::strict) mode). The iterator ends after the value or after yielding the
+/// first error.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let tokens: Result<Vec<_>, _> = Tokens::new(b"d1:ali1e1:bee", &DecodeOptions::default()).collect();
+/// assert_eq!(
+///     tokens.unwrap(),
+///     [
+///         Token::DictStart,
+///         Token::Str(b"a".into()),
+///         Token::ListStart,
+///         Token::Int(1),
+///         Token::Str(b"b".into()),
+///         Token::End,
+///         Token::End,
+///     ]
+/// );
+/// ```
+pub struct Tokens<'a> {
+    d: Decoder<'a>,
+    // Open containers innermost last, with whether a dictionary expects a key next
+    stack: Vec<Frame>,
+    done: bool,
+    // Kind of the value, known from its first token
+    kind: Kind,
+}
+
+#[derive(Clone, Copy)]
+enum Frame {
+    List,
+    Dict { key_next: bool },
+}
+
+impl<'a> Tokens<'a> {
+    /// Tokenize the value at the start of `buf`
+    pub fn new(buf: &'a [u8], opts: &DecodeOptions) -> Self {
+        Decoder::with_options(buf, opts).into()
+    }
+
+    /// The number of input bytes after the current position (see [Decoder::remaining])
+    ///
+    /// Once the iterator has ended without error, this is the number of bytes after the value.
+    pub fn remaining(&self) -> usize {
+        self.d.remaining()
+    }
+
+    /// The number of lists and dictionaries that are open at the current position
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token<'a>>, DecodeError> {
+        let Some(frame) = self.stack.last_mut() else {
+            return self.value().map(Some);
+        };
+        match frame {
+            Frame::List => {
+                if !self.d.next_item()? {
+                    self.stack.pop();
+                    return Ok(Some(Token::End));
+                }
+            }
+            Frame::Dict { key_next: key_next @ true } => {
+                let Some(key) = self.d.next_key()? else {
+                    self.stack.pop();
+                    return Ok(Some(Token::End));
+                };
+                *key_next = false;
+                return Ok(Some(Token::Str(key)));
+            }
+            Frame::Dict { key_next } => *key_next = true,
+        }
+        self.value().map(Some)
+    }
+
+    fn value(&mut self) -> Result<Token<'a>, DecodeError> {
+        match self.d.peek_u8()? {
+            b'i' => self.d.take_int().map(Token::Int),
+            b'0'..=b'9' => self.d.take_str().map(Token::Str),
+            b'l' => {
+                self.d.begin_list()?;
+                self.stack.push(Frame::List);
+                Ok(Token::ListStart)
+            }
+            b'd' => {
+                self.d.begin_dict()?;
+                self.stack.push(Frame::Dict { key_next: true });
+                Ok(Token::DictStart)
+            }
+            _ => Err(DecodeError::Invalid),
+        }
+    }
+}
+
+/// Continue with the next value of the decoder, e.g. one created with [Decoder::chained]
+impl<'a> From<Decoder<'a>> for Tokens<'a> {
+    fn from(d: Decoder<'a>) -> Self {
+        Self { d, stack: Vec::new(), done: false, kind: Kind::Int }
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<Token<'a>, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let top = self.stack.is_empty();
+        let r = self.next_token().transpose()?;
+        if let (true, Ok(token)) = (top, &r) {
+            self.kind = match token {
+                Token::Int(_) => Kind::Int,
+                Token::Str(_) => Kind::Str,
+                Token::ListStart => Kind::List,
+                Token::DictStart | Token::End => Kind::Dict,
+            };
+        }
+        self.done = r.is_err() || self.stack.is_empty();
+        if self.done {
+            self.d.finish(r.as_ref().map(|_| self.kind).map_err(|e| *e));
+        }
+        Some(r)
+    }
+}
+
+impl FusedIterator for Tokens<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+    use std::borrow::Cow;
+
+    fn tokens(buf: &[u8], opts: &DecodeOptions) -> Vec<Result<Token<'static>, DecodeError>> {
+        Tokens::new(buf, opts).map(|t| t.map(owned)).collect()
+    }
+
+    fn owned(t: Token<'_>) -> Token<'static> {
+        match t {
+            Token::Int(i) => Token::Int(i),
+            Token::Str(s) => Token::Str(Cow::Owned(s.into_owned())),
+            Token::ListStart => Token::ListStart,
+            Token::DictStart => Token::DictStart,
+            Token::End => Token::End,
+        }
+    }
+
+    #[test]
+    fn test_tokens() {
+        let opts = DecodeOptions::default();
+        assert_eq!(tokens(b"i-3e", &opts), [Ok(Token::Int(-3))]);
+        assert_eq!(tokens(b"0:", &opts), [Ok(Token::Str(Cow::Borrowed(b"")))]);
+        assert_eq!(tokens(b"le", &opts), [Ok(Token::ListStart), Ok(Token::End)]);
+        assert_eq!(
+            tokens(b"d1:bde1:ai0ee", &opts),
+            [
+                Ok(Token::DictStart),
+                Ok(Token::Str(Cow::Borrowed(b"b"))),
+                Ok(Token::DictStart),
+                Ok(Token::End),
+                Ok(Token::Str(Cow::Borrowed(b"a"))),
+                Ok(Token::Int(0)),
+                Ok(Token::End),
+            ]
+        );
+
+        // Only the first value is tokenized
+        let mut t = Tokens::new(b"li1eei2e", &opts);
+        assert_eq!(t.by_ref().count(), 3);
+        assert_eq!((t.remaining(), t.depth()), (3, 0));
+
+        let mut t = Tokens::from(Decoder::chained(&[b"l2:a", b"be"], &opts));
+        assert_eq!(t.nth(1), Some(Ok(Token::Str(Cow::Owned(b"ab".to_vec())))));
+        assert_eq!(t.depth(), 1);
+    }
+
+    #[test]
+    fn test_tokens_errors() {
+        let opts = DecodeOptions::default();
+        assert_eq!(tokens(b"", &opts), [Err(DecodeError::Incomplete { needed: 1 })]);
+        assert_eq!(
+            tokens(b"li1e", &opts),
+            [Ok(Token::ListStart), Ok(Token::Int(1)), Err(DecodeError::Incomplete { needed: 1 })]
+        );
+        assert_eq!(tokens(b"lxe", &opts), [Ok(Token::ListStart), Err(DecodeError::Invalid)]);
+        assert_eq!(tokens(b"di1ei1ee", &opts), [Ok(Token::DictStart), Err(DecodeError::Invalid)]);
+        assert_eq!(
+            tokens(b"d1:ae", &opts),
+            [Ok(Token::DictStart), Ok(Token::Str(Cow::Borrowed(b"a"))), Err(DecodeError::Invalid)]
+        );
+        assert_eq!(
+            tokens(b"lle", &opts.clone().with_max_depth(1)),
+            [Ok(Token::ListStart), Err(DecodeError::LimitExceeded)]
+        );
+
+        // Duplicate keys and the alloc limit are not checked
+        let opts = opts.with_max_allocs(0);
+        assert_eq!(tokens(b"d1:ai0e1:ai0ee", &opts).len(), 6);
+        assert!(Value::decode_with(b"d1:ai0e1:ai0ee", &opts).is_err());
+
+        let opts = DecodeOptions::default().with_strict(true);
+        let t = tokens(b"d1:bi1e1:ai1ee", &opts);
+        assert_eq!(t.last(), Some(&Err(DecodeError::Invalid)));
+        assert_eq!(t.len(), 4);
+        assert!(tokens(b"d1:ad1:bi1ee1:bi1ee", &opts).iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn test_tokens_deep() {
+        let n = 100_000;
+        let buf = [b"l".repeat(n), b"e".repeat(n)].concat();
+        let opts = DecodeOptions::default().with_max_depth(n);
+        let mut t = Tokens::new(&buf, &opts);
+        assert_eq!(t.by_ref().filter(|t| *t == Ok(Token::End)).count(), n);
+        assert_eq!((t.remaining(), t.depth()), (0, 0));
+    }
+}
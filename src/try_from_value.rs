@@ -1,8 +1,38 @@
-use super::{Dict, List, Value};
+use super::{ConversionError, DecodeOptions, Dict, List, Value};
 
 /// Conversion from [Value]
+///
+/// All implementing types except tuples also implement the standard [TryFrom] for `&Value`, so
+/// generic code bound on [TryFrom] works as well. Its error is the one of [Self::convert]. Note
+/// that with both traits in scope, `T::try_from` is ambiguous and must be written as
+/// `<T as TryFromValue>::try_from`.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// fn parse<'v, T: TryFrom<&'v Value<'v>>>(v: &'v Value<'v>) -> Option<T> {
+///     T::try_from(v).ok()
+/// }
+///
+/// let v = str!("abc");
+/// assert_eq!(parse::<&str>(&v), Some("abc"));
+/// assert_eq!(parse::<i64>(&v), None);
+/// let id: Result<[u8; 3], _> = v.try_into();
+/// assert_eq!(id, Ok(*b"abc"));
+/// ```
 pub trait TryFromValue<'a>: Sized {
+    /// Name of the type as reported in a [ConversionError], e.g. `"i64"`
+    const NAME: &'static str;
+
     fn try_from(value: &'a Value) -> Option<Self>;
+
+    /// Like [Self::try_from], but tell which value failed to convert and why
+    ///
+    /// The default reports `Self` as expected for the whole value. Implementations for containers
+    /// should override it to pass on the error of the offending item together with its position.
+    fn convert(value: &'a Value) -> Result<Self, ConversionError> {
+        Self::try_from(value).ok_or_else(|| ConversionError::new::<Self>(value))
+    }
 }
 
 macro_rules! from {
@@ -16,53 +46,256 @@ macro_rules! from {
 }
 
 impl<'a> TryFromValue<'a> for i64 {
+    const NAME: &'static str = "i64";
+
     fn try_from(value: &'a Value<'a>) -> Option<Self> {
         from!(Int, value as v => Some(*v))
     }
 }
 
 impl<'a> TryFromValue<'a> for &'a [u8] {
+    const NAME: &'static str = "&[u8]";
+
     fn try_from(value: &'a Value) -> Option<Self> {
         from!(Str, value as v => Some(v.as_ref()))
     }
 }
 
 impl<'a, const N: usize> TryFromValue<'a> for [u8; N] {
+    const NAME: &'static str = "byte array";
+
     fn try_from(value: &'a Value) -> Option<Self> {
         from!(Str, value as v => TryFrom::try_from(v.as_ref()).ok())
     }
 }
 
 impl<'a, A: TryFromValue<'a>, B: TryFromValue<'a>> TryFromValue<'a> for (A, B) {
+    const NAME: &'static str = "pair";
+
     fn try_from(value: &'a Value) -> Option<Self> {
         from!(List, value as v => {
-            let a = v.get(0).map(A::try_from)?;
+            let a = v.first().map(A::try_from)?;
             let b = v.get(1).map(B::try_from)?;
             a.zip(b)
         })
     }
+
+    fn convert(value: &'a Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::List(l) if l.len() >= 2 => {
+                Ok((A::convert(&l[0]).map_err(|e| e.at_index(0))?, B::convert(&l[1]).map_err(|e| e.at_index(1))?))
+            }
+            _ => Err(ConversionError::new::<Self>(value)),
+        }
+    }
 }
 
 impl<'a> TryFromValue<'a> for &'a str {
+    const NAME: &'static str = "&str";
+
     fn try_from(value: &'a Value) -> Option<Self> {
         from!(Str, value as v => std::str::from_utf8(v).ok())
     }
 }
 
 impl<'a> TryFromValue<'a> for &'a List<'a> {
+    const NAME: &'static str = "List";
+
     fn try_from(value: &'a Value) -> Option<Self> {
         from!(List, value as v => Some(v))
     }
 }
 
 impl<'a> TryFromValue<'a> for &'a Dict<'a> {
+    const NAME: &'static str = "Dict";
+
     fn try_from(value: &'a Value) -> Option<Self> {
         from!(Dict, value as v => Some(v))
     }
 }
 
+/// Fails for integers out of range of the target type
+macro_rules! int_try_from_value {
+    ($($t:ty),*) => {$(
+        impl<'a> TryFromValue<'a> for $t {
+    const NAME: &'static str = stringify!($t);
+
+            fn try_from(value: &'a Value) -> Option<Self> {
+                from!(Int, value as v => (*v).try_into().ok())
+            }
+        }
+    )*};
+}
+
+int_try_from_value!(i32, u32, u64, usize);
+
+impl<'a> TryFromValue<'a> for String {
+    const NAME: &'static str = "String";
+
+    fn try_from(value: &'a Value) -> Option<Self> {
+        from!(Str, value as v => std::str::from_utf8(v).ok().map(Into::into))
+    }
+}
+
+impl<'a> TryFromValue<'a> for Vec<u8> {
+    const NAME: &'static str = "Vec<u8>";
+
+    fn try_from(value: &'a Value) -> Option<Self> {
+        from!(Str, value as v => Some(v.to_vec()))
+    }
+}
+
+impl<'a, T: TryFromValue<'a>> TryFromValue<'a> for Vec<T> {
+    const NAME: &'static str = "list";
+
+    fn try_from(value: &'a Value) -> Option<Self> {
+        from!(List, value as v => v.iter().map(T::try_from).collect())
+    }
+
+    fn convert(value: &'a Value) -> Result<Self, ConversionError> {
+        let Value::List(l) = value else {
+            return Err(ConversionError::new::<Self>(value));
+        };
+        l.iter().enumerate().map(|(i, v)| T::convert(v).map_err(|e| e.at_index(i))).collect()
+    }
+}
+
 impl<'a> TryFromValue<'a> for &'a Value<'a> {
+    const NAME: &'static str = "Value";
+
     fn try_from(value: &'a Value) -> Option<Self> {
         Some(value)
     }
 }
+
+macro_rules! std_try_from {
+    ($($t:ty),*) => {$(
+        impl<'a, 'b> TryFrom<&'a Value<'b>> for $t {
+            type Error = ConversionError;
+
+            fn try_from(value: &'a Value<'b>) -> Result<Self, ConversionError> {
+                <$t as TryFromValue>::convert(value)
+            }
+        }
+    )*};
+}
+
+std_try_from!(i64, i32, u32, u64, usize, String, Vec<u8>, &'a [u8], &'a str, &'a List<'a>, &'a Dict<'a>);
+
+impl<'a, 'b, T: TryFromValue<'a>> TryFrom<&'a Value<'b>> for Vec<T> {
+    type Error = ConversionError;
+
+    fn try_from(value: &'a Value<'b>) -> Result<Self, ConversionError> {
+        <Self as TryFromValue>::convert(value)
+    }
+}
+
+impl<'a, 'b, const N: usize> TryFrom<&'a Value<'b>> for [u8; N] {
+    type Error = ConversionError;
+
+    fn try_from(value: &'a Value<'b>) -> Result<Self, ConversionError> {
+        <Self as TryFromValue>::convert(value)
+    }
+}
+
+impl TryFrom<Value<'_>> for i64 {
+    type Error = ConversionError;
+
+    fn try_from(value: Value<'_>) -> Result<Self, ConversionError> {
+        TryFrom::try_from(&value)
+    }
+}
+
+impl<const N: usize> TryFrom<Value<'_>> for [u8; N] {
+    type Error = ConversionError;
+
+    fn try_from(value: Value<'_>) -> Result<Self, ConversionError> {
+        TryFrom::try_from(&value)
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for List<'a> {
+    type Error = ConversionError;
+
+    fn try_from(mut value: Value<'a>) -> Result<Self, ConversionError> {
+        match &mut value {
+            Value::List(l) => Ok(std::mem::take(l)),
+            _ => Err(ConversionError::new::<&List>(&value)),
+        }
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for Dict<'a> {
+    type Error = ConversionError;
+
+    fn try_from(mut value: Value<'a>) -> Result<Self, ConversionError> {
+        match &mut value {
+            Value::Dict(d) => Ok(std::mem::take(d)),
+            _ => Err(ConversionError::new::<&Dict>(&value)),
+        }
+    }
+}
+
+/// Decode a [Value] and convert it into `T` in one go
+///
+/// Fails if decoding or the conversion fails. `T` must not borrow from the decoded value.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let id = decode_as::<[u8; 4]>(b"4:abcd", &DecodeOptions::default());
+/// assert_eq!(id, Some(*b"abcd"));
+/// assert_eq!(decode_as::<i64>(b"4:abcd", &DecodeOptions::default()), None);
+/// ```
+pub fn decode_as<T>(buf: &[u8], opts: &DecodeOptions) -> Option<T>
+where
+    T: for<'v> TryFromValue<'v>,
+{
+    let v = Value::decode_with(buf, opts).ok()?;
+    T::try_from(&v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Kind, Path};
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_std_try_from() {
+        let v = Value::Str(Cow::Borrowed(b"\xff"));
+        assert_eq!(TryFrom::try_from(&v), Ok(b"\xff".as_ref()));
+        let e = <&str as TryFrom<_>>::try_from(&v).unwrap_err();
+        assert_eq!(e, ConversionError { expected: "&str", actual: Some(Kind::Str), path: Path::new() });
+        assert_eq!(e.to_string(), "cannot convert Str to &str");
+        assert_eq!(TryFrom::try_from(Value::Int(-1)), Ok(-1));
+        assert_eq!(<i64 as TryFrom<_>>::try_from(&v).unwrap_err().actual, Some(Kind::Str));
+        assert_eq!(List::try_from(Value::List(vec![v.clone()])), Ok(vec![v.clone()]));
+        assert!(Dict::try_from(v).is_err());
+    }
+
+    #[test]
+    fn test_owned_and_ints() {
+        let v = Value::List(vec![Value::Int(1), Value::Int(-1)]);
+        assert_eq!(<Vec<i64> as TryFromValue>::try_from(&v), Some(vec![1, -1]));
+        assert_eq!(<Vec<u32> as TryFromValue>::try_from(&v), None);
+        assert_eq!(<i32 as TryFrom<_>>::try_from(&Value::Int(1 << 31)).unwrap_err().expected, "i32");
+        let v = Value::Str(Cow::Borrowed(b"ab"));
+        assert_eq!(<String as TryFromValue>::try_from(&v), Some("ab".into()));
+        assert_eq!(<Vec<u8> as TryFrom<_>>::try_from(&v), Ok(b"ab".to_vec()));
+        assert_eq!(<String as TryFromValue>::try_from(&Value::Str(Cow::Borrowed(b"\xff"))), None);
+    }
+
+    #[test]
+    fn test_convert_path() {
+        let s = |x: &'static [u8]| Value::Str(Cow::Borrowed(x));
+        let pair = |a, b| Value::List(vec![s(a), Value::Int(b)]);
+        let v = Value::List(vec![pair(b"a", 1), pair(b"\xff", 2)]);
+        let e = <Vec<(&str, i64)> as TryFromValue>::convert(&v).unwrap_err();
+        assert_eq!((e.expected, e.actual, e.path.to_string()), ("&str", Some(Kind::Str), "[1][0]".into()));
+        assert_eq!(<Vec<(&[u8], i64)> as TryFrom<_>>::try_from(&v).map(|x| x.len()), Ok(2));
+        let e = <Vec<(&str, i64)> as TryFromValue>::convert(&Value::List(vec![s(b"a")])).unwrap_err();
+        assert_eq!(e.to_string(), "cannot convert Str to pair at [0]");
+        assert_eq!(ConversionError::missing::<i64>(b"port").to_string(), "missing i64 at port");
+    }
+}
@@ -1,8 +1,15 @@
 use super::decoder::Decoder;
-use super::encoder::Encoder;
-use super::TryFromValue;
+use super::encoder::{default_encode_capacity, digits, str_len, Encoder};
+use super::sha1::Sha1;
+use super::telemetry;
+use super::xxh64::Xxh64;
+use super::{
+    ConversionError, DecodeError, DecodeOptions, DecodeReport, DecodeStats, DisplayBencode, EncodeError, IntoStr, Path,
+    TryFromValue,
+};
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, TryReserveError};
 
 /// An alias for [i64]
 pub type Int = i64;
@@ -16,6 +23,19 @@ pub type List<'a> = Vec<Value<'a>>;
 /// An alias for a dictionary mapping byte strings to [Value]s
 pub type Dict<'a> = BTreeMap<Cow<'a, [u8]>, Value<'a>>;
 
+/// A piece of the encoding of a [Value] (see [Value::walk])
+pub(crate) enum Piece<'v> {
+    Int(Int),
+    /// A string or dictionary key
+    Str(&'v [u8]),
+    List,
+    Dict,
+    /// The end of a list or dictionary
+    End,
+    /// The content of a [Value::Raw]
+    Raw(&'v [u8]),
+}
+
 /// A Bencode value is either an [Int], a [Str], a [List] or a [Dict]
 ///
 /// Note that [Value] carries a lifetime parameter for borrowed data. This is useful for
@@ -58,9 +78,174 @@ pub enum Value<'a> {
     Str(Str<'a>),
     List(List<'a>),
     Dict(Dict<'a>),
+    /// An already encoded value that is written as is (see [Value::raw])
+    ///
+    /// This lets relays forward sub-documents without decoding and re-encoding them. Decoding never
+    /// produces it and accessors treat it as opaque. It is only equal to a `Raw` with the same bytes
+    /// and never to a decoded value, use [Value::encoded_cmp] to compare the encodings instead.
+    Raw(Str<'a>),
+}
+
+/// Dropping does not recurse per level, so values nested arbitrarily deep can be dropped safely
+///
+/// Because of this, the payload cannot be moved out of a [Value] by matching on it. Match on a
+/// mutable reference and [take](std::mem::take) the payload instead.
+impl Drop for Value<'_> {
+    fn drop(&mut self) {
+        fn container(v: &Value<'_>) -> bool {
+            match v {
+                Value::List(l) => !l.is_empty(),
+                Value::Dict(d) => !d.is_empty(),
+                _ => false,
+            }
+        }
+        // Whether dropping `v` recurses more than one level
+        fn nested(v: &Value<'_>) -> bool {
+            match v {
+                Value::List(l) => l.iter().any(container),
+                Value::Dict(d) => d.values().any(container),
+                _ => false,
+            }
+        }
+        // Move the nested children of `v` to `stack`, so `v` is at most two levels deep
+        fn take_nested<'a>(v: &mut Value<'a>, stack: &mut Vec<Value<'a>>) {
+            let take = |c: &mut Value<'a>| nested(c).then(|| std::mem::replace(c, Value::Int(0)));
+            match v {
+                Value::List(l) => stack.extend(l.iter_mut().filter_map(take)),
+                Value::Dict(d) => stack.extend(d.values_mut().filter_map(take)),
+                _ => (),
+            }
+        }
+        let shallow = match self {
+            Value::List(l) => !l.iter().any(nested),
+            Value::Dict(d) => !d.values().any(nested),
+            _ => true,
+        };
+        if shallow {
+            return;
+        }
+        let mut stack = Vec::new();
+        take_nested(self, &mut stack);
+        while let Some(mut v) = stack.pop() {
+            take_nested(&mut v, &mut stack);
+        }
+    }
+}
+
+/// The payload of a [Value], which can be moved out unlike from a [Value] itself
+pub(crate) enum Unpacked<'a> {
+    Int(Int),
+    Str(Str<'a>),
+    List(List<'a>),
+    Dict(Dict<'a>),
+    Raw(Str<'a>),
+}
+
+/// The type of a [Value] without its content
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Kind {
+    Int,
+    Str,
+    List,
+    Dict,
+    Raw,
 }
 
 impl<'a> Value<'a> {
+    /// An empty [Value::Dict] (also the [Default])
+    pub const fn empty_dict() -> Self {
+        Value::Dict(BTreeMap::new())
+    }
+
+    /// An empty [Value::List]
+    pub const fn empty_list() -> Self {
+        Value::List(Vec::new())
+    }
+
+    /// Move the payload out
+    pub(crate) fn unpack(mut self) -> Unpacked<'a> {
+        match &mut self {
+            Value::Int(i) => Unpacked::Int(*i),
+            Value::Str(s) => Unpacked::Str(std::mem::take(s)),
+            Value::List(l) => Unpacked::List(std::mem::take(l)),
+            Value::Dict(d) => Unpacked::Dict(std::mem::take(d)),
+            Value::Raw(s) => Unpacked::Raw(std::mem::take(s)),
+        }
+    }
+
+    /// A [Value::Str] borrowing a static string
+    ///
+    /// Like [str!](super::str!) but usable in `const` contexts.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// const PING: Value = Value::from_str_static("ping");
+    /// assert_eq!(PING, str!("ping"));
+    /// assert_eq!(Value::default(), Value::empty_dict());
+    /// ```
+    pub const fn from_str_static(s: &'static str) -> Self {
+        Value::Str(Cow::Borrowed(s.as_bytes()))
+    }
+
+    /// A [Value::Raw] for the encoded value in `buf`
+    ///
+    /// Fails unless `buf` is exactly one value that decodes with the default [DecodeOptions] (but
+    /// without a size limit), so the content is always available to the accessors.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let ext = Value::raw(b"d1:md6:ut_pexi1eee".as_ref()).unwrap();
+    /// let v = dict! { "e" => int!(0), "x" => ext };
+    /// assert_eq!(v.encode(), b"d1:ei0e1:xd1:md6:ut_pexi1eeee");
+    /// assert!(Value::raw(b"i1ei2e".as_ref()).is_err());
+    ///
+    /// let one = Value::raw(b"i1e".as_ref()).unwrap();
+    /// assert_ne!(one, int!(1));
+    /// assert_eq!(one.encoded_cmp(&int!(1)), std::cmp::Ordering::Equal);
+    /// ```
+    pub fn raw(buf: impl Into<Str<'a>>) -> Result<Self, DecodeError> {
+        let buf = buf.into();
+        let mut d = Decoder::with_options(&buf, &Self::raw_options());
+        d.take_any()?;
+        if d.remaining() > 0 {
+            return Err(DecodeError::Invalid);
+        }
+        Ok(Value::Raw(buf))
+    }
+
+    fn raw_options() -> DecodeOptions {
+        DecodeOptions { max_size_factor: None, ..Default::default() }
+    }
+
+    /// Decode the content of a [Value::Raw]
+    pub(crate) fn unraw(s: &Str<'a>) -> Result<Self, DecodeError> {
+        let opts = Self::raw_options();
+        match s {
+            Cow::Borrowed(b) => Decoder::with_options(b, &opts).take_any(),
+            Cow::Owned(b) => Decoder::with_options(b, &opts).take_any().map(Value::into_owned),
+        }
+    }
+
+    /// Like [Self::unraw], but keep invalid content as a byte string for infallible conversions
+    ///
+    /// Only a [Value::Raw] built directly rather than with [Self::raw] can be invalid.
+    pub(crate) fn unraw_lossy(s: &Str<'a>) -> Self {
+        Self::unraw(s).unwrap_or_else(|_| Value::Str(s.clone()))
+    }
+
+    pub fn kind(&self) -> Kind {
+        match self {
+            Value::Int(_) => Kind::Int,
+            Value::Str(_) => Kind::Str,
+            Value::List(_) => Kind::List,
+            Value::Dict(_) => Kind::Dict,
+            Value::Raw(_) => Kind::Raw,
+        }
+    }
+
     /// Assume the value is a dictionary and get the value for the given key, converted into the desired type
     /// using [TryFromValue]
     ///
@@ -98,6 +283,153 @@ impl<'a> Value<'a> {
         x.try_into()
     }
 
+    /// Like [Self::get], but tell why the entry could not be retrieved
+    ///
+    /// A missing key is told apart from a value of the wrong type with
+    /// [ConversionError::is_missing], so absent optional fields need not be treated as errors. If
+    /// this is not a dictionary, the error expects a `Dict` at the root.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let v = dict! { "port" => str!("6881") };
+    /// let e = v.try_get::<i64>("port").unwrap_err();
+    /// assert!(!e.is_missing());
+    /// assert_eq!(e.to_string(), "cannot convert Str to i64 at port");
+    /// assert!(v.try_get::<i64>("interval").unwrap_err().is_missing());
+    /// assert_eq!(v.try_get::<&str>("port"), Ok("6881"));
+    /// assert_eq!(int!(1).try_get::<i64>("port").unwrap_err().to_string(), "cannot convert Int to Dict");
+    /// ```
+    pub fn try_get<'b, T: TryFromValue<'b>>(&'b self, key: &'static str) -> Result<T, ConversionError> {
+        let Value::Dict(d) = self else {
+            return Err(ConversionError { expected: "Dict", actual: Some(self.kind()), path: Path::new() });
+        };
+        let v = d.get(key.as_bytes()).ok_or_else(|| ConversionError::missing::<T>(key.as_bytes()))?;
+        T::convert(v).map_err(|e| e.at_key(key.as_bytes()))
+    }
+
+    /// Get the list for `key` with every item converted using [TryFromValue]
+    ///
+    /// Fails if this is not a dictionary, the key is missing, the value is not a list or any of its
+    /// items cannot be converted.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let v = dict! { "urls" => list![str!("a"), str!("b")], "mixed" => list![str!("a"), int!(1)] };
+    /// assert_eq!(v.get_list_of::<&str>("urls"), Some(vec!["a", "b"]));
+    /// assert_eq!(v.get_list_of::<&str>("mixed"), None);
+    /// ```
+    pub fn get_list_of<'b, T: TryFromValue<'b>>(&'b self, key: &'static str) -> Option<Vec<T>> {
+        self.get::<&'b List<'b>>(key)?.iter().map(T::try_from).collect()
+    }
+
+    /// Get the dictionary for `key` with keys and values converted into `K` and `T`
+    ///
+    /// Keys are converted with [TryFrom], so they may be taken as `&[u8]`, `Vec<u8>` or fixed size
+    /// arrays like info hashes. Fails if this is not a dictionary, the key is missing, the value is not
+    /// a dictionary or any of its entries cannot be converted.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    /// use std::collections::BTreeMap;
+    ///
+    /// let v = dict! { "files" => dict! { "aaaa" => int!(1), "bbbb" => int!(2) } };
+    /// let files: BTreeMap<[u8; 4], i64> = v.get_dict_of("files").unwrap();
+    /// assert_eq!(files, BTreeMap::from([(*b"aaaa", 1), (*b"bbbb", 2)]));
+    /// assert_eq!(v.get_dict_of::<[u8; 3], i64>("files"), None);
+    /// ```
+    pub fn get_dict_of<'b, K, T>(&'b self, key: &'static str) -> Option<BTreeMap<K, T>>
+    where
+        K: TryFrom<&'b [u8]> + Ord,
+        T: TryFromValue<'b>,
+    {
+        let d = self.get::<&'b Dict<'b>>(key)?;
+        d.iter().map(|(k, v)| Some((K::try_from(k).ok()?, T::try_from(v)?))).collect()
+    }
+
+    /// The keys in ascending order if this is a dictionary (nothing otherwise)
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let v = dict! { "b" => int!(2), "a" => str!("x") };
+    /// assert_eq!(v.keys().collect::<Vec<_>>(), [b"a", b"b"]);
+    /// assert_eq!(v.values_as::<i64>().collect::<Vec<_>>(), [(b"a".as_ref(), None), (b"b".as_ref(), Some(2))]);
+    /// ```
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.entries().map(|(k, _)| k)
+    }
+
+    /// The entries with values converted using [TryFromValue] if this is a dictionary
+    ///
+    /// Values that cannot be converted are yielded as [None] (see [Self::keys]).
+    pub fn values_as<'b, T: TryFromValue<'b>>(&'b self) -> impl Iterator<Item = (&'b [u8], Option<T>)> {
+        self.entries().map(|(k, v)| (k, T::try_from(v)))
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (&[u8], &Value<'a>)> {
+        let d = match self {
+            Value::Dict(d) => Some(d),
+            _ => None,
+        };
+        d.into_iter().flatten().map(|(k, v)| (k.as_ref(), v))
+    }
+
+    /// Insert an entry into a dictionary and return the previous value for `key`
+    ///
+    /// Panics if this is not a dictionary.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let mut r = dict! {};
+    /// r.insert_str("id", b"abcdefghij0123456789").insert_int("port", 6881);
+    /// r.insert("values", list![]);
+    /// r.get_path_mut("values").unwrap().push_str("peer1").push_int(2);
+    /// assert_eq!(r.encode(), b"d2:id20:abcdefghij01234567894:porti6881e6:valuesl5:peer1i2eee");
+    /// ```
+    pub fn insert(&mut self, key: impl IntoStr<'a>, value: Value<'a>) -> Option<Value<'a>> {
+        match self {
+            Value::Dict(d) => d.insert(key.into_str(), value),
+            _ => panic!("not a dictionary"),
+        }
+    }
+
+    /// Insert a [Value::Int] into a dictionary (see [Self::insert])
+    pub fn insert_int(&mut self, key: impl IntoStr<'a>, i: Int) -> &mut Self {
+        self.insert(key, Value::Int(i));
+        self
+    }
+
+    /// Insert a [Value::Str] into a dictionary (see [Self::insert])
+    pub fn insert_str(&mut self, key: impl IntoStr<'a>, s: impl IntoStr<'a>) -> &mut Self {
+        self.insert(key, Value::Str(s.into_str()));
+        self
+    }
+
+    /// Append an item to a list
+    ///
+    /// Panics if this is not a list.
+    pub fn push(&mut self, value: Value<'a>) {
+        match self {
+            Value::List(l) => l.push(value),
+            _ => panic!("not a list"),
+        }
+    }
+
+    /// Append a [Value::Int] to a list (see [Self::push])
+    pub fn push_int(&mut self, i: Int) -> &mut Self {
+        self.push(Value::Int(i));
+        self
+    }
+
+    /// Append a [Value::Str] to a list (see [Self::push])
+    pub fn push_str(&mut self, s: impl IntoStr<'a>) -> &mut Self {
+        self.push(Value::Str(s.into_str()));
+        self
+    }
+
     /// Try to convert the [Value] into the desired type using [TryFromValue]
     ///
     /// Fails if the value cannot be converted into the desired type.
@@ -107,13 +439,28 @@ impl<'a> Value<'a> {
 
     /// Quick encoding into a [Vec]<[u8]>
     ///
-    /// The returned vector is freshly allocated and has a capacity of 1500 bytes to
-    /// avoid multiple reallocations for typical use cases. Its length is adjusted to the
-    /// actual encoded size.
+    /// The returned vector is freshly allocated with [default_encode_capacity] (1500 bytes unless
+    /// changed) to avoid multiple reallocations for typical use cases. Its length is adjusted to
+    /// the actual encoded size.
     pub fn encode(&self) -> Vec<u8> {
-        let mut v = Vec::with_capacity(1500);
+        self.encode_with_capacity(default_encode_capacity())
+    }
+
+    /// Like [Self::encode] but with an initial capacity of `n` bytes
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let v = dict! { "y" => str!("r") };
+    /// let buf = v.encode_with_capacity(300);
+    /// assert_eq!(buf, b"d1:y1:re");
+    /// assert!(buf.capacity() >= 300);
+    /// ```
+    pub fn encode_with_capacity(&self, n: usize) -> Vec<u8> {
+        let mut v = Vec::with_capacity(n);
         let mut e = Encoder::new(&mut v);
         e.value(self);
+        telemetry::record_encode(v.len());
         v
     }
 
@@ -128,6 +475,387 @@ impl<'a> Value<'a> {
         let mut e = Encoder::new(buf);
         e.clear();
         e.value(self);
+        telemetry::record_encode(buf.len());
+    }
+
+    /// Encode into a thread-local buffer and pass the encoding to `f`
+    ///
+    /// This saves allocating a [Vec] per message when the bytes are only hashed or sent. The buffer is
+    /// reused by later calls on the same thread unless it grew beyond [Self::MAX_RETAINED_CAPACITY].
+    /// Nested calls from within `f` work, but allocate.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let v = dict! { "y" => str!("q"), "q" => str!("ping") };
+    /// let len = v.with_encoded(|bytes| bytes.len());
+    /// assert_eq!(len, 17);
+    /// ```
+    pub fn with_encoded<R>(&self, f: impl FnOnce(&[u8]) -> R) -> R {
+        thread_local! {
+            static BUF: std::cell::Cell<Vec<u8>> = const { std::cell::Cell::new(Vec::new()) };
+        }
+        let mut buf = BUF.take();
+        self.encode_into(&mut buf);
+        let r = f(&buf);
+        if buf.capacity() <= Self::MAX_RETAINED_CAPACITY {
+            BUF.set(buf);
+        }
+        r
+    }
+
+    /// Buffers above this capacity are not kept by [Self::with_encoded]
+    pub const MAX_RETAINED_CAPACITY: usize = 64 * 1024;
+
+    /// Like [Self::encode], but fail instead of aborting if memory cannot be allocated
+    ///
+    /// The exact size is computed upfront with [Self::encoded_len] and reserved in a single allocation.
+    pub fn try_encode(&self) -> Result<Vec<u8>, TryReserveError> {
+        let mut v = Vec::new();
+        self.try_encode_into(&mut v)?;
+        Ok(v)
+    }
+
+    /// Like [Self::encode_into], but fail instead of aborting if memory cannot be allocated
+    ///
+    /// The buffer is left empty on failure.
+    pub fn try_encode_into(&self, buf: &mut Vec<u8>) -> Result<(), TryReserveError> {
+        buf.clear();
+        buf.try_reserve_exact(self.encoded_len())?;
+        let mut e = Encoder::new(buf);
+        e.value(self);
+        telemetry::record_encode(buf.len());
+        Ok(())
+    }
+
+    /// The number of bytes [Self::encode] would produce
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let v = dict! { "age" => int!(-42), "name" => str!("John") };
+    /// assert_eq!(v.encoded_len(), v.encode().len());
+    /// ```
+    pub fn encoded_len(&self) -> usize {
+        let mut len = 0;
+        self.walk(|p| {
+            len += match p {
+                Piece::Int(i) => 2 + usize::from(i < 0) + digits(i.unsigned_abs()),
+                Piece::Str(s) => str_len(s),
+                Piece::List | Piece::Dict | Piece::End => 1,
+                Piece::Raw(s) => s.len(),
+            }
+        });
+        len
+    }
+
+    /// A stable 64-bit hash of the canonical encoding
+    ///
+    /// This is the XXH64 hash (seed 0) of [Self::encode], but computed without encoding into a
+    /// buffer. It is fast and stable across versions and platforms, so it can serve as a key for
+    /// dedup tables and caches. It is not a cryptographic hash.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let a = dict! { "a" => int!(1), "b" => list![str!("x")] };
+    /// let b = Value::decode(b"d1:ai1e1:bl1:xee", 10).unwrap();
+    /// assert_eq!(a.fingerprint64(), b.fingerprint64());
+    /// assert_ne!(a.fingerprint64(), int!(1).fingerprint64());
+    /// ```
+    pub fn fingerprint64(&self) -> u64 {
+        let mut h = Xxh64::new(0);
+        self.write_encoded(&mut |x| h.update(x));
+        h.finish()
+    }
+
+    /// Compare by canonical encoding
+    ///
+    /// The derived [Ord] orders by variant first (integers, strings, lists, dictionaries) and then by
+    /// content, so `int!(10) > int!(9)`. This instead orders values exactly like their encodings
+    /// compare as byte strings, where `i10e < i9e` and strings (starting with a digit) come first.
+    /// Sorting values with this gives the same order as sorting their encodings, without encoding.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    /// use std::cmp::Ordering;
+    ///
+    /// assert_eq!(int!(10).cmp(&int!(9)), Ordering::Greater);
+    /// assert_eq!(int!(10).encoded_cmp(&int!(9)), Ordering::Less);
+    /// assert_eq!(int!(1).encoded_cmp(&str!("a")), int!(1).encode().cmp(&str!("a").encode()));
+    /// ```
+    pub fn encoded_cmp(&self, other: &Value<'_>) -> Ordering {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => match (*a < 0, *b < 0) {
+                (x, y) if x != y => y.cmp(&x), // `-` sorts before digits
+                _ => decimal(a.unsigned_abs(), b'e', &mut [0; 21]).cmp(decimal(b.unsigned_abs(), b'e', &mut [0; 21])),
+            },
+            (Value::Str(a), Value::Str(b)) => str_cmp(a, b),
+            (Value::List(a), Value::List(b)) => {
+                seq_cmp(a.iter(), b.iter(), |x, y| x.encoded_cmp(y), |x| x.first_encoded_byte())
+            }
+            (Value::Dict(a), Value::Dict(b)) => seq_cmp(
+                a.iter(),
+                b.iter(),
+                |x, y| str_cmp(x.0, y.0).then_with(|| x.1.encoded_cmp(y.1)),
+                |x| decimal(x.0.len() as u64, b':', &mut [0; 21])[0],
+            ),
+            (Value::Raw(_), _) | (_, Value::Raw(_)) => self.encode().cmp(&other.encode()),
+            _ => self.first_encoded_byte().cmp(&other.first_encoded_byte()),
+        }
+    }
+
+    fn first_encoded_byte(&self) -> u8 {
+        match self {
+            Value::Int(_) => b'i',
+            Value::Str(s) => decimal(s.len() as u64, b':', &mut [0; 21])[0],
+            Value::List(_) => b'l',
+            Value::Dict(_) => b'd',
+            Value::Raw(s) => s.first().copied().unwrap_or(0),
+        }
+    }
+
+    /// Return a copy with byte strings longer than `max_len` replaced by a placeholder
+    ///
+    /// The placeholder is a string like `<16384 bytes, sha1=...>` identifying the original content, so
+    /// decoded messages can be logged without dumping megabytes of piece data. Keys are kept as is.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let v = dict! { "piece" => int!(0), "block" => str!(vec![0u8; 16384]) };
+    /// assert_eq!(
+    ///     format!("{:?}", v.redacted(32)),
+    ///     r#"{"block": "<16384 bytes, sha1=897256b6709e1a4da9daba92b6bde39ccfccd8c1>", "piece": 0}"#
+    /// );
+    /// ```
+    pub fn redacted(&self, max_len: usize) -> Value<'a> {
+        match self {
+            Value::Int(i) => Value::Int(*i),
+            Value::Str(s) if s.len() > max_len => {
+                let mut h = Sha1::new();
+                h.update(s);
+                let mut r = format!("<{} bytes, sha1=", s.len());
+                h.finish().iter().for_each(|b| r.push_str(&format!("{:02x}", b)));
+                r.push('>');
+                Value::Str(Cow::Owned(r.into_bytes()))
+            }
+            Value::Str(s) => Value::Str(s.clone()),
+            Value::List(l) => Value::List(l.iter().map(|v| v.redacted(max_len)).collect()),
+            Value::Dict(d) => Value::Dict(d.iter().map(|(k, v)| (k.clone(), v.redacted(max_len))).collect()),
+            Value::Raw(s) => Value::unraw_lossy(s).redacted(max_len),
+        }
+    }
+
+    /// Remove all nested values for which `f` returns `false`
+    ///
+    /// Values are visited top-down with their [Path], so `f` sees a container before its children and
+    /// the children of removed containers are not visited at all. The root itself is not passed to `f`.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let mut v = dict! {
+    ///     "info" => dict! { "name" => str!("foo"), "source" => str!("tracker") },
+    ///     "comment" => str!("secret"),
+    ///     "url-list" => list![],
+    /// };
+    /// v.retain(|path, _| !matches!(path.to_string().as_str(), "comment" | "info.source"));
+    /// v.prune_empty();
+    /// assert_eq!(v.encode(), b"d4:infod4:name3:fooee");
+    /// ```
+    pub fn retain(&mut self, mut f: impl FnMut(&Path, &Value<'a>) -> bool) {
+        fn go<'a>(v: &mut Value<'a>, path: &mut Path, f: &mut impl FnMut(&Path, &Value<'a>) -> bool) {
+            match v {
+                Value::List(l) => {
+                    let mut i = 0;
+                    l.retain_mut(|x| {
+                        path.push_index(i);
+                        i += 1;
+                        let keep = f(path, x);
+                        if keep {
+                            go(x, path, f);
+                        }
+                        path.pop();
+                        keep
+                    })
+                }
+                Value::Dict(d) => d.retain(|k, x| {
+                    path.push_key(k);
+                    let keep = f(path, x);
+                    if keep {
+                        go(x, path, f);
+                    }
+                    path.pop();
+                    keep
+                }),
+                _ => {}
+            }
+        }
+        go(self, &mut Path::new(), &mut f)
+    }
+
+    /// Recursively remove empty lists and dictionaries
+    ///
+    /// Containers that become empty by this are removed as well. The root is kept even if empty.
+    pub fn prune_empty(&mut self) {
+        fn is_empty(v: &Value) -> bool {
+            match v {
+                Value::List(l) => l.is_empty(),
+                Value::Dict(d) => d.is_empty(),
+                _ => false,
+            }
+        }
+        match self {
+            Value::List(l) => l.retain_mut(|x| {
+                x.prune_empty();
+                !is_empty(x)
+            }),
+            Value::Dict(d) => d.retain(|_, x| {
+                x.prune_empty();
+                !is_empty(x)
+            }),
+            _ => {}
+        }
+    }
+
+    /// Pass the canonical encoding to `f` piece by piece
+    fn write_encoded<F: FnMut(&[u8])>(&self, f: &mut F) {
+        fn write_uint<F: FnMut(&[u8])>(n: u64, f: &mut F) {
+            let mut buf = [0u8; 20];
+            let len = digits(n);
+            let mut n = n;
+            for b in buf[..len].iter_mut().rev() {
+                *b = b'0' + (n % 10) as u8;
+                n /= 10;
+            }
+            f(&buf[..len]);
+        }
+        self.walk(|p| match p {
+            Piece::Int(i) => {
+                f(if i < 0 { b"i-" } else { b"i" });
+                write_uint(i.unsigned_abs(), f);
+                f(b"e");
+            }
+            Piece::Str(s) => {
+                write_uint(s.len() as u64, f);
+                f(b":");
+                f(s);
+            }
+            Piece::List => f(b"l"),
+            Piece::Dict => f(b"d"),
+            Piece::End => f(b"e"),
+            Piece::Raw(s) => f(s),
+        });
+    }
+
+    /// Pass the pieces of the encoding to `f` in order
+    ///
+    /// Containers are tracked on the heap instead of recursing, so encoding cannot overflow the
+    /// stack however deep a value is nested.
+    pub(crate) fn walk<'v>(&'v self, mut f: impl FnMut(Piece<'v>)) {
+        enum Frame<'v, 'a> {
+            List(std::slice::Iter<'v, Value<'a>>),
+            Dict(std::collections::btree_map::Iter<'v, Str<'a>, Value<'a>>),
+        }
+        let mut stack = Vec::new();
+        let mut next = self;
+        loop {
+            match next {
+                Value::Int(i) => f(Piece::Int(*i)),
+                Value::Str(s) => f(Piece::Str(s)),
+                Value::List(l) => {
+                    f(Piece::List);
+                    stack.push(Frame::List(l.iter()));
+                }
+                Value::Dict(d) => {
+                    f(Piece::Dict);
+                    stack.push(Frame::Dict(d.iter()));
+                }
+                Value::Raw(s) => f(Piece::Raw(s)),
+            }
+            next = loop {
+                match stack.last_mut() {
+                    None => return,
+                    Some(Frame::List(items)) => {
+                        if let Some(v) = items.next() {
+                            break v;
+                        }
+                    }
+                    Some(Frame::Dict(entries)) => {
+                        if let Some((k, v)) = entries.next() {
+                            f(Piece::Str(k));
+                            break v;
+                        }
+                    }
+                }
+                stack.pop();
+                f(Piece::End);
+            };
+        }
+    }
+
+    /// Like [Self::encode], but fail if the encoding would exceed `max` bytes
+    ///
+    /// This is useful to enforce protocol limits like the 1000 byte limit for BEP 44 values or a
+    /// UDP datagram size. The error points to the innermost value that crosses the limit.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let v = dict! { "a" => dict! { "id" => str!([0u8; 20]), "v" => str!([0u8; 1000]) } };
+    /// let Err(EncodeError::TooLarge { path, len, max }) = v.encode_capped(1000) else { panic!() };
+    /// assert_eq!(path.to_string(), "a.v");
+    /// assert_eq!((len, max), (1042, 1000));
+    /// ```
+    pub fn encode_capped(&self, max: usize) -> Result<Vec<u8>, EncodeError> {
+        let mut v = Vec::new();
+        self.encode_into_capped(&mut v, max)?;
+        Ok(v)
+    }
+
+    /// Like [Self::encode_into], but fail if the encoding would exceed `max` bytes
+    ///
+    /// The buffer is left empty on failure.
+    pub fn encode_into_capped(&self, buf: &mut Vec<u8>, max: usize) -> Result<(), EncodeError> {
+        fn locate(v: &Value, pos: usize, path: &mut Path) {
+            let mut offset = 1;
+            match v {
+                Value::List(l) => {
+                    for (i, x) in l.iter().enumerate() {
+                        let len = x.encoded_len();
+                        if (offset..offset + len).contains(&pos) {
+                            path.push_index(i);
+                            return locate(x, pos - offset, path);
+                        }
+                        offset += len;
+                    }
+                }
+                Value::Dict(d) => {
+                    for (k, x) in d {
+                        let len = str_len(k) + x.encoded_len();
+                        if (offset..offset + len).contains(&pos) {
+                            path.push_key(k);
+                            return locate(x, pos.saturating_sub(offset + str_len(k)), path);
+                        }
+                        offset += len;
+                    }
+                }
+                _ => (),
+            }
+        }
+        buf.clear();
+        let len = self.encoded_len();
+        if len > max {
+            let mut path = Path::new();
+            locate(self, max, &mut path);
+            return Err(EncodeError::TooLarge { path, len, max });
+        }
+        buf.reserve(len);
+        let mut e = Encoder::new(buf);
+        e.value(self);
+        telemetry::record_encode(buf.len());
+        Ok(())
     }
 
     /// Try to decode a [Value] from the provided buffer
@@ -135,12 +863,162 @@ impl<'a> Value<'a> {
     /// The `max_allocs` parameter limits the number of allocations that may be performed during decoding.
     /// This is useful to avoid denial-of-service attacks by providing maliciously crafted input that would
     /// cause excessive memory allocations. Each list item and dictionary entry counts as one allocation.
-    /// If the limit is exceeded, decoding fails and `None` is returned.
+    /// If the limit is exceeded, decoding fails. Nesting is limited to
+    /// [DecodeOptions::DEFAULT_MAX_DEPTH] levels. On failure, the [DecodeReport] tells what went
+    /// wrong, at which offset and in which sub-value.
     ///
     /// The returned [Value] borrows all byte strings from the input buffer. The value can therefor not outlive
     /// the input buffer. Either deconstruct the value right away (recommended) or use [Self::into_owned].
-    pub fn decode(buf: &'a [u8], max_allocs: usize) -> Option<Self> {
-        Decoder::new(buf, max_allocs).take_value()
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let e = Value::decode(b"d1:ai1e1:ai2ee", 10).unwrap_err();
+    /// assert_eq!((e.kind, e.offset, e.path.to_string()), (DecodeErrorKind::DuplicateKey, 13, "a".into()));
+    /// ```
+    pub fn decode(buf: &'a [u8], max_allocs: usize) -> Result<Self, DecodeReport> {
+        let mut d = Decoder::new(buf, max_allocs);
+        d.take_value().map_err(|e| DecodeReport::from_decoder(buf, &d, e))
+    }
+
+    /// Try to decode a [Value] from the provided buffer with the given [DecodeOptions]
+    ///
+    /// Unlike [Self::decode], this allows to limit the decoded size relative to the input length
+    /// instead of (or in addition to) a fixed number of allocations. [DecodeOptions::default]
+    /// is a reasonable protection for any input size.
+    ///
+    /// On failure, the [DecodeError] tells truncated input apart from invalid input:
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let opts = DecodeOptions::default();
+    /// assert_eq!(Value::decode_with(b"5:hel", &opts), Err(DecodeError::Incomplete { needed: 2 }));
+    /// assert_eq!(Value::decode_with(b"5-hello", &opts), Err(DecodeError::Invalid));
+    /// ```
+    pub fn decode_with(buf: &'a [u8], opts: &DecodeOptions) -> Result<Self, DecodeError> {
+        Decoder::with_options(buf, opts).take_value()
+    }
+
+    /// Like [Self::decode_with], but decode into `self` reusing its lists and their capacity
+    ///
+    /// Meant for processing many similar messages from one long-lived buffer (like a file or a
+    /// batch of datagrams): the lists of the previous message are overwritten in place, so the
+    /// steady state performs fewer allocations. `self` is left in an unspecified state on error.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let buf = b"d5:peersl1:a1:bee";
+    /// let opts = DecodeOptions::default();
+    /// let mut v = Value::decode_with(b"d5:peersl1:x1:y1:zee", &opts).unwrap();
+    /// v.decode_into(buf, &opts).unwrap();
+    /// assert_eq!(v, Value::decode_with(buf, &opts).unwrap());
+    /// ```
+    pub fn decode_into(&mut self, buf: &'a [u8], opts: &DecodeOptions) -> Result<(), DecodeError> {
+        Decoder::with_options(buf, opts).take_value_into(self)
+    }
+
+    /// Like [Self::decode_with], but also report how much of the budget was consumed
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let (v, stats) = Value::decode_with_stats(b"d1:ali1ei2eee", &DecodeOptions::default()).unwrap();
+    /// assert_eq!(stats.allocs, 3);
+    /// assert_eq!(stats.steps, 4);
+    /// assert_eq!(stats.depth, 2);
+    /// ```
+    pub fn decode_with_stats(buf: &'a [u8], opts: &DecodeOptions) -> Result<(Self, DecodeStats), DecodeError> {
+        let mut d = Decoder::with_options(buf, opts);
+        let v = d.take_value()?;
+        Ok((v, d.stats()))
+    }
+
+    /// Decode a [Value] starting at `offset` in `buf` and return it together with its end offset
+    ///
+    /// This is meant for protocols embedding Bencode at known positions inside larger binary frames.
+    /// Bytes before `offset` and after the value are ignored.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let frame = b"\x14\x00d1:ai1ee\xff\xff";
+    /// let (v, end) = Value::decode_at(frame, 2, &DecodeOptions::default()).unwrap();
+    /// assert_eq!(v, dict! { "a" => int!(1) });
+    /// assert_eq!(&frame[end..], b"\xff\xff");
+    /// ```
+    pub fn decode_at(buf: &'a [u8], offset: usize, opts: &DecodeOptions) -> Result<(Self, usize), DecodeError> {
+        let needed = || DecodeError::Incomplete { needed: offset.saturating_sub(buf.len()).saturating_add(1) };
+        let rest = buf.get(offset..).ok_or_else(needed)?;
+        let mut d = Decoder::with_options(rest, opts);
+        let v = d.take_value()?;
+        Ok((v, buf.len() - d.remaining()))
+    }
+
+    /// Decode a [Value] from the front of `buf` and return it together with the bytes after it
+    ///
+    /// Messages like the extension messages of the peer wire protocol carry a binary payload right
+    /// after a bencoded header, so the input does not end with the value.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let msg = b"d8:msg_typei1e5:piecei0ee\x01\x02\x03";
+    /// let (v, payload) = Value::decode_prefix(msg, &DecodeOptions::default()).unwrap();
+    /// assert_eq!(v, dict! { "msg_type" => int!(1), "piece" => int!(0) });
+    /// assert_eq!(payload, b"\x01\x02\x03");
+    /// ```
+    pub fn decode_prefix(buf: &'a [u8], opts: &DecodeOptions) -> Result<(Self, &'a [u8]), DecodeError> {
+        let mut d = Decoder::with_options(buf, opts);
+        let v = d.take_value()?;
+        Ok((v, &buf[buf.len() - d.remaining()..]))
+    }
+
+    /// Decode a [Value] from a sequence of slices as if they were concatenated
+    ///
+    /// This avoids compacting data that is split across ring buffer wrap-arounds or multiple network
+    /// segments into a contiguous buffer. Byte strings (and keys) spanning more than one slice are
+    /// copied, everything else is borrowed as usual. Copied bytes count against the size limit.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let v = Value::decode_chained(&[b"d4:na", b"me5:hel", b"loe"], &DecodeOptions::default());
+    /// assert_eq!(v, Ok(dict! { "name" => str!("hello") }));
+    /// ```
+    pub fn decode_chained(parts: &'a [&'a [u8]], opts: &DecodeOptions) -> Result<Self, DecodeError> {
+        Decoder::chained(parts, opts).take_value()
+    }
+
+    /// Like [Self::decode_with], but hand byte strings longer than `threshold` to `sink`
+    ///
+    /// Each such string value is replaced in the resulting tree by the placeholder returned from
+    /// `sink`, e.g. its length. The sink is called as soon as a string is decoded, so it sees the
+    /// strings in input order (which is the encoded order for canonical input) and even if decoding
+    /// fails later on. A sink appending to a single writer can thus be split up again using the
+    /// placeholders. Dictionary keys are never passed to the sink, but values of duplicate keys
+    /// that are dropped are.
+    ///
+    /// ```rust
+    /// use bencode_minimal::*;
+    ///
+    /// let mut pieces = Vec::new();
+    /// let buf = b"d6:lengthi4e6:pieces8:abcdefghe";
+    /// let v = Value::decode_with_sink(buf, &DecodeOptions::default(), 4, |s| {
+    ///     pieces.extend_from_slice(s);
+    ///     int!(s.len() as i64)
+    /// });
+    /// assert_eq!(v, Ok(dict! { "length" => int!(4), "pieces" => int!(8) }));
+    /// assert_eq!(pieces, b"abcdefgh");
+    /// ```
+    pub fn decode_with_sink(
+        buf: &'a [u8],
+        opts: &DecodeOptions,
+        threshold: usize,
+        mut sink: impl FnMut(&[u8]) -> Value<'a>,
+    ) -> Result<Self, DecodeError> {
+        Decoder::with_options(buf, opts).take_value_with_sink(threshold, &mut sink)
     }
 
     /// Convert the value into an owned version
@@ -149,41 +1027,183 @@ impl<'a> Value<'a> {
     /// are moved into the new value without cloning. All [Vec]s and [BTreeMap]s get unfortunately
     /// recreated since there is no way to recycle them.
     pub fn into_owned(self) -> Value<'static> {
-        match self {
-            Value::Int(i) => Value::Int(i),
-            Value::Str(s) => Value::Str(Cow::Owned(s.into_owned())),
-            Value::List(l) => Value::List(l.into_iter().map(Value::into_owned).collect()),
-            Value::Dict(d) => {
+        match self.unpack() {
+            Unpacked::Int(i) => Value::Int(i),
+            Unpacked::Str(s) => Value::Str(Cow::Owned(s.into_owned())),
+            Unpacked::Raw(s) => Value::Raw(Cow::Owned(s.into_owned())),
+            Unpacked::List(l) => Value::List(l.into_iter().map(Value::into_owned).collect()),
+            Unpacked::Dict(d) => {
                 Value::Dict(d.into_iter().map(|(k, v)| (Cow::Owned(k.into_owned()), v.into_owned())).collect())
             }
         }
     }
+
+    /// Like [Self::into_owned], but fail instead of aborting if memory cannot be allocated
+    ///
+    /// Byte strings and lists are allocated fallibly. [BTreeMap] offers no fallible insertion, so
+    /// dictionary nodes are still allocated the regular way. They are small compared to the byte
+    /// strings they contain in all practical cases.
+    pub fn try_into_owned(self) -> Result<Value<'static>, TryReserveError> {
+        fn owned(s: Cow<'_, [u8]>) -> Result<Cow<'static, [u8]>, TryReserveError> {
+            match s {
+                Cow::Owned(s) => Ok(Cow::Owned(s)),
+                Cow::Borrowed(s) => {
+                    let mut v = Vec::new();
+                    v.try_reserve_exact(s.len())?;
+                    v.extend_from_slice(s);
+                    Ok(Cow::Owned(v))
+                }
+            }
+        }
+        match self.unpack() {
+            Unpacked::Int(i) => Ok(Value::Int(i)),
+            Unpacked::Str(s) => Ok(Value::Str(owned(s)?)),
+            Unpacked::Raw(s) => Ok(Value::Raw(owned(s)?)),
+            Unpacked::List(l) => {
+                let mut v = Vec::new();
+                v.try_reserve_exact(l.len())?;
+                for x in l {
+                    v.push(x.try_into_owned()?);
+                }
+                Ok(Value::List(v))
+            }
+            Unpacked::Dict(d) => {
+                let mut m = BTreeMap::new();
+                for (k, v) in d {
+                    m.insert(owned(k)?, v.try_into_owned()?);
+                }
+                Ok(Value::Dict(m))
+            }
+        }
+    }
+}
+
+/// Write `n` in decimal followed by `end` into `buf` and return the written part
+fn decimal(n: u64, end: u8, buf: &mut [u8; 21]) -> &[u8] {
+    let mut i = buf.len() - 1;
+    buf[i] = end;
+    let mut n = n;
+    loop {
+        i -= 1;
+        buf[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    &buf[i..]
+}
+
+/// Compare two strings by encoding (see [Value::encoded_cmp])
+fn str_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    // Differing length headers decide as neither is a prefix of the other (both end with `:`)
+    let h = decimal(a.len() as u64, b':', &mut [0; 21]).cmp(decimal(b.len() as u64, b':', &mut [0; 21]));
+    h.then_with(|| a.cmp(b))
+}
+
+/// Compare two lists or dictionaries by encoding item by item (see [Value::encoded_cmp])
+///
+/// Encodings are prefix-free, so the first differing item decides. If one side ends first, its
+/// closing `e` is compared with the first byte of the other side's next item.
+fn seq_cmp<T>(
+    mut a: impl Iterator<Item = T>,
+    mut b: impl Iterator<Item = T>,
+    cmp: impl Fn(&T, &T) -> Ordering,
+    first_byte: impl Fn(&T) -> u8,
+) -> Ordering {
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(y)) => return b'e'.cmp(&first_byte(&y)),
+            (Some(x), None) => return first_byte(&x).cmp(&b'e'),
+            (Some(x), Some(y)) => match cmp(&x, &y) {
+                Ordering::Equal => continue,
+                o => return o,
+            },
+        }
+    }
 }
 
+/// The default is an empty dictionary as most messages and files are dictionaries at the top level
+impl Default for Value<'_> {
+    fn default() -> Self {
+        Self::empty_dict()
+    }
+}
+
+/// Single-line by default, while `{:#?}` prints one item or entry per line with nesting guides:
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let v = dict! { "info" => dict! { "length" => int!(42), "path" => list![str!("a")] }, "x" => list![] };
+/// assert_eq!(format!("{:?}", v), r#"{"info": {"length": 42, "path": ["a"]}, "x": []}"#);
+/// assert_eq!(
+///     format!("{:#?}", v),
+///     r#"{
+/// │ "info": {
+/// │ │ "length": 42,
+/// │ │ "path": [
+/// │ │ │ "a",
+/// │ │ ],
+/// │ },
+/// │ "x": [],
+/// }"#
+/// );
+/// ```
 impl std::fmt::Debug for Value<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            return self.fmt_pretty(f, 0);
+        }
         match self {
             Value::Int(i) => write!(f, "{}", i),
-            Value::Str(s) => match std::str::from_utf8(s) {
-                Ok(s) => write!(f, "{:?}", s),
-                Err(_) => {
-                    for i in s.iter() {
-                        write!(f, "{:02x}", i)?;
-                    }
-                    Ok(())
-                }
-            },
+            Value::Str(s) => write!(f, "{:?}", DebugStr(s)),
+            Value::Raw(s) => write!(f, "{:?}", DisplayBencode(s)),
             Value::List(l) => f.debug_list().entries(l.iter()).finish(),
-            Value::Dict(d) => f
-                .debug_map()
-                .entries(d.iter().map(|(k, v)| {
-                    let k = match std::str::from_utf8(k) {
-                        Ok(s) => s.to_string(),
-                        Err(_) => format!("{:?}", k),
-                    };
-                    (k, v)
-                }))
-                .finish(),
+            Value::Dict(d) => f.debug_map().entries(d.iter().map(|(k, v)| (DebugStr(k), v))).finish(),
+        }
+    }
+}
+
+/// Shows a byte string or key as quoted text if valid UTF-8 and as hex otherwise
+struct DebugStr<'s>(&'s [u8]);
+
+impl std::fmt::Debug for DebugStr<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match std::str::from_utf8(self.0) {
+            Ok(s) => write!(f, "{:?}", s),
+            Err(_) => self.0.iter().try_for_each(|b| write!(f, "{:02x}", b)),
+        }
+    }
+}
+
+impl Value<'_> {
+    fn fmt_pretty(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        let indent = |f: &mut std::fmt::Formatter<'_>, depth| (0..depth).try_for_each(|_| f.write_str("│ "));
+        match self {
+            Value::List(l) if !l.is_empty() => {
+                f.write_str("[\n")?;
+                for v in l {
+                    indent(f, depth + 1)?;
+                    v.fmt_pretty(f, depth + 1)?;
+                    f.write_str(",\n")?;
+                }
+                indent(f, depth)?;
+                f.write_str("]")
+            }
+            Value::Dict(d) if !d.is_empty() => {
+                f.write_str("{\n")?;
+                for (k, v) in d {
+                    indent(f, depth + 1)?;
+                    write!(f, "{:?}: ", DebugStr(k))?;
+                    v.fmt_pretty(f, depth + 1)?;
+                    f.write_str(",\n")?;
+                }
+                indent(f, depth)?;
+                f.write_str("}")
+            }
+            v => write!(f, "{:?}", v),
         }
     }
 }
@@ -198,7 +1218,7 @@ mod tests {
         let encoded = value.encode();
         assert_eq!(&encoded, b"i0e");
         let value_ = Value::decode(&encoded, 0);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
@@ -207,7 +1227,7 @@ mod tests {
         let encoded = value.encode();
         assert_eq!(&encoded, b"i1e");
         let value_ = Value::decode(&encoded, 0);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
@@ -216,7 +1236,7 @@ mod tests {
         let encoded = value.encode();
         assert_eq!(&encoded, b"i-1e");
         let value_ = Value::decode(&encoded, 0);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
@@ -225,7 +1245,7 @@ mod tests {
         let encoded = value.encode();
         assert_eq!(&encoded, b"i10e");
         let value_ = Value::decode(&encoded, 0);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
@@ -234,7 +1254,7 @@ mod tests {
         let encoded = value.encode();
         assert_eq!(&encoded, b"i-10e");
         let value_ = Value::decode(&encoded, 0);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
@@ -243,7 +1263,7 @@ mod tests {
         let encoded = value.encode();
         assert_eq!(&encoded, b"i42e");
         let value_ = Value::decode(&encoded, 0);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
@@ -252,7 +1272,7 @@ mod tests {
         let encoded = value.encode();
         assert_eq!(&encoded, b"i-42e");
         let value_ = Value::decode(&encoded, 0);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
@@ -261,7 +1281,7 @@ mod tests {
         let encoded = value.encode();
         assert_eq!(&encoded, b"0:");
         let value_ = Value::decode(&encoded, 0);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
@@ -270,7 +1290,7 @@ mod tests {
         let encoded = value.encode();
         assert_eq!(&encoded, b"1::");
         let value_ = Value::decode(&encoded, 0);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
@@ -279,7 +1299,7 @@ mod tests {
         let encoded = value.encode();
         assert_eq!(&encoded, b"5:hello");
         let value_ = Value::decode(&encoded, 0);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
@@ -288,7 +1308,7 @@ mod tests {
         let encoded = value.encode();
         assert_eq!(&encoded, b"10:helloworld");
         let value_ = Value::decode(&encoded, 0);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
@@ -297,7 +1317,7 @@ mod tests {
         let encoded = value.encode();
         assert_eq!(&encoded, b"le");
         let value_ = Value::decode(&encoded, 10);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
@@ -306,7 +1326,7 @@ mod tests {
         let encoded = value.encode();
         assert_eq!(&encoded, b"li42e5:helloe");
         let value_ = Value::decode(&encoded, 10);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
@@ -315,7 +1335,7 @@ mod tests {
         let encoded = value.encode();
         assert_eq!(&encoded, b"de");
         let value_ = Value::decode(&encoded, 10);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
@@ -327,7 +1347,7 @@ mod tests {
         let encoded = value.encode();
         assert_eq!(&encoded, b"d3:agei42e4:name4:Johne");
         let value_ = Value::decode(&encoded, 10);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
@@ -339,35 +1359,35 @@ mod tests {
 
         let encoded = b"d4:name4:John3:agei42ee";
         let value_ = Value::decode(encoded.as_ref(), 10);
-        assert_eq!(value_, Some(value));
+        assert_eq!(value_, Ok(value));
     }
 
     #[test]
     fn test_dict_04_duplicate_keys() {
         let encoded = b"d3:agei30e3:agei40ee";
         let value = Value::decode(encoded.as_ref(), 10);
-        assert!(value.is_none());
+        assert!(value.is_err());
     }
 
     #[test]
     fn test_max_alloc_int() {
         let encoded = b"i42e";
         let value = Value::decode(encoded.as_ref(), 0);
-        assert!(value.is_some());
+        assert!(value.is_ok());
     }
 
     #[test]
     fn test_max_alloc_str() {
         let encoded = b"5:hello";
         let value_ = Value::decode(encoded.as_ref(), 0);
-        assert!(value_.is_some());
+        assert!(value_.is_ok());
     }
 
     #[test]
     fn test_max_alloc_list_empty() {
         let encoded = b"le";
         let value = Value::decode(encoded.as_ref(), 0);
-        assert!(value.is_some());
+        assert!(value.is_ok());
     }
 
     #[test]
@@ -375,10 +1395,10 @@ mod tests {
         let encoded = b"li42ee";
 
         let value = Value::decode(encoded.as_ref(), 0);
-        assert!(value.is_none());
+        assert!(value.is_err());
 
         let value = Value::decode(encoded.as_ref(), 1);
-        assert!(value.is_some());
+        assert!(value.is_ok());
     }
 
     #[test]
@@ -386,17 +1406,17 @@ mod tests {
         let encoded = b"li1ei2ee";
 
         let value = Value::decode(encoded.as_ref(), 1);
-        assert!(value.is_none());
+        assert!(value.is_err());
 
         let value = Value::decode(encoded.as_ref(), 2);
-        assert!(value.is_some());
+        assert!(value.is_ok());
     }
 
     #[test]
     fn test_max_alloc_dict_empty() {
         let encoded = b"de";
         let value = Value::decode(encoded.as_ref(), 0);
-        assert!(value.is_some());
+        assert!(value.is_ok());
     }
 
     #[test]
@@ -404,10 +1424,10 @@ mod tests {
         let encoded = b"d3:agei42ee";
 
         let value = Value::decode(encoded.as_ref(), 0);
-        assert!(value.is_none());
+        assert!(value.is_err());
 
         let value = Value::decode(encoded.as_ref(), 1);
-        assert!(value.is_some());
+        assert!(value.is_ok());
     }
 
     #[test]
@@ -415,9 +1435,340 @@ mod tests {
         let encoded = b"d3:agei42e4:name4:Johne";
 
         let value = Value::decode(encoded.as_ref(), 1);
-        assert!(value.is_none());
+        assert!(value.is_err());
 
         let value = Value::decode(encoded.as_ref(), 2);
-        assert!(value.is_some());
+        assert!(value.is_ok());
+    }
+
+    #[test]
+    fn test_max_size_factor_list() {
+        let encoded = b"l0:0:0:0:e";
+        let item = std::mem::size_of::<Value>();
+
+        let opts = DecodeOptions { max_size_factor: Some(4 * item / encoded.len()), ..Default::default() };
+        let value = Value::decode_with(encoded.as_ref(), &opts);
+        assert!(value.is_err());
+
+        let opts = DecodeOptions { max_size_factor: Some(4 * item / encoded.len() + 1), ..Default::default() };
+        let value = Value::decode_with(encoded.as_ref(), &opts);
+        assert!(value.is_ok());
+    }
+
+    #[test]
+    fn test_max_size_factor_default() {
+        let encoded = b"d1:ai1e1:bl0:0:ee";
+        let value = Value::decode_with(encoded.as_ref(), &DecodeOptions::default());
+        assert!(value.is_ok());
+
+        let opts = DecodeOptions { max_size_factor: None, max_allocs: 3, ..Default::default() };
+        let value = Value::decode_with(encoded.as_ref(), &opts);
+        assert!(value.is_err());
+    }
+
+    #[test]
+    fn test_max_steps() {
+        let encoded = b"d1:ai1e1:bl0:0:ee";
+
+        let opts = DecodeOptions { max_steps: 4, ..Default::default() };
+        let value = Value::decode_with(encoded.as_ref(), &opts);
+        assert!(value.is_err());
+
+        let opts = DecodeOptions { max_steps: 5, ..Default::default() };
+        let value = Value::decode_with(encoded.as_ref(), &opts);
+        assert!(value.is_ok());
+    }
+
+    #[test]
+    fn test_chained() {
+        let encoded = b"d3:agei42e4:name4:Johne";
+        let value = Value::decode(encoded.as_ref(), 10).ok();
+        for i in 0..=encoded.len() {
+            for j in i..=encoded.len() {
+                let parts = [&encoded[..i], &encoded[i..j], &encoded[j..]];
+                let value_ = Value::decode_chained(&parts, &DecodeOptions::default());
+                assert_eq!(value_.ok(), value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_chained_borrowed() {
+        let parts: [&[u8]; 3] = [b"l2:ab2:c", b"", b"de"];
+        let value = Value::decode_chained(&parts, &DecodeOptions::default());
+        let Ok(Value::List(l)) = &value else { panic!() };
+        assert!(matches!(&l[0], Value::Str(Cow::Borrowed(b"ab"))));
+        assert!(matches!(&l[1], Value::Str(Cow::Owned(_))));
+    }
+
+    #[test]
+    fn test_try_encode() {
+        let value = Value::List(vec![Value::Int(i64::MIN), Value::Int(0), Value::Str(Cow::Borrowed(&[0; 10]))]);
+        let encoded = value.try_encode().unwrap();
+        assert_eq!(encoded, value.encode());
+        assert_eq!(encoded.len(), value.encoded_len());
+        assert_eq!(encoded.capacity(), value.encoded_len());
+    }
+
+    #[test]
+    fn test_try_into_owned() {
+        let encoded = b"d3:agei42e4:namel4:Johnee";
+        let value = Value::decode(encoded.as_ref(), 10).unwrap();
+        assert_eq!(value.clone().try_into_owned(), Ok(value.into_owned()));
+    }
+
+    #[test]
+    fn test_encode_capped() {
+        let value = Value::List(vec![Value::Int(1), Value::List(vec![Value::Int(2), Value::Int(3)])]);
+        assert_eq!(value.encode_capped(13), Ok(value.encode()));
+        let Err(EncodeError::TooLarge { path, len, max }) = value.encode_capped(11) else { panic!() };
+        assert_eq!((path.to_string(), len, max), ("[1]".to_string(), 13, 11));
+        let Err(EncodeError::TooLarge { path, .. }) = value.encode_capped(12) else { panic!() };
+        assert!(path.is_root());
+        let Err(EncodeError::TooLarge { path, .. }) = value.encode_capped(6) else { panic!() };
+        assert_eq!(path.to_string(), "[1][0]");
+        let Err(EncodeError::TooLarge { path, .. }) = value.encode_capped(0) else { panic!() };
+        assert!(path.is_root());
+    }
+
+    #[test]
+    fn test_decode_at() {
+        let buf = b"xxi42e3:abc";
+        let opts = DecodeOptions::default();
+        assert_eq!(Value::decode_at(buf, 2, &opts), Ok((Value::Int(42), 6)));
+        assert_eq!(Value::decode_at(buf, 6, &opts), Ok((Value::Str(Cow::Borrowed(b"abc")), 11)));
+        assert_eq!(Value::decode_at(buf, 11, &opts), Err(DecodeError::Incomplete { needed: 1 }));
+        assert_eq!(Value::decode_at(buf, 13, &opts), Err(DecodeError::Incomplete { needed: 3 }));
+        assert_eq!(Value::decode_at(buf, 0, &opts), Err(DecodeError::Invalid));
+        assert_eq!(Value::decode_at(&[], usize::MAX, &opts), Err(DecodeError::Incomplete { needed: usize::MAX }));
+    }
+
+    #[test]
+    fn test_decode_prefix() {
+        let opts = DecodeOptions::default();
+        assert_eq!(Value::decode_prefix(b"i42e", &opts), Ok((Value::Int(42), b"".as_ref())));
+        assert_eq!(Value::decode_prefix(b"3:abci1e", &opts), Ok((Value::Str(Cow::Borrowed(b"abc")), b"i1e".as_ref())));
+        assert_eq!(Value::decode_prefix(b"li1e", &opts), Err(DecodeError::Incomplete { needed: 1 }));
+        assert_eq!(Value::decode_prefix(b"", &opts), Err(DecodeError::Incomplete { needed: 1 }));
+        assert_eq!(Value::decode_prefix(b"x", &opts), Err(DecodeError::Invalid));
+    }
+
+    #[test]
+    fn test_decode_with_sink() {
+        let opts = DecodeOptions::default();
+        let mut seen = Vec::new();
+        let mut sink = |s: &[u8]| {
+            seen.push(s.to_vec());
+            Value::Int(s.len() as i64)
+        };
+        let v = Value::decode_with_sink(b"d1:bl3:abc1:xe1:a4:wxyze", &opts, 1, &mut sink);
+        let expected = Value::decode(b"d1:ai4e1:bli3e1:xee", 10).unwrap();
+        assert_eq!(v, Ok(expected));
+
+        // Strings are passed on in input order and before decoding fails
+        assert_eq!(Value::decode_with_sink(b"l2:aax", &opts, 1, &mut sink), Err(DecodeError::Invalid));
+        assert_eq!(seen, [b"abc".to_vec(), b"wxyz".to_vec(), b"aa".to_vec()]);
+    }
+
+    #[test]
+    fn test_fingerprint64() {
+        let v = Value::decode(b"d1:ai-120e1:bl0:i0ed1:c3:xyzeee", usize::MAX).unwrap();
+        let mut h = Xxh64::new(0);
+        h.update(&v.encode());
+        assert_eq!(v.fingerprint64(), h.finish());
+        assert_ne!(Value::Int(0).fingerprint64(), Value::Int(-0x100).fingerprint64());
+    }
+
+    #[test]
+    fn test_raw() {
+        for buf in [b"".as_ref(), b"i1", b"i1ei2e", b"d1:bi0e", b"x", b"d1:bi0e1:bi0ee"] {
+            assert!(Value::raw(buf).is_err(), "{:?}", buf);
+        }
+        let r = Value::raw(b"d1:bi0e1:ai0ee".as_ref()).unwrap();
+        let v = Value::List(vec![r.clone(), Value::Int(1)]);
+        assert_eq!(v.encode(), b"ld1:bi0e1:ai0eei1ee");
+        assert_eq!(v.encoded_len(), v.encode().len());
+        assert_eq!((r.kind(), r.get::<i64>("a")), (Kind::Raw, None));
+        assert_ne!(r, Value::decode(b"d1:ai0e1:bi0ee", 10).unwrap());
+        assert_eq!(r.encoded_cmp(&Value::Int(0)), Ordering::Less);
+        assert_eq!(format!("{:?}", v), r#"[{"b": 0, "a": 0}, 1]"#);
+        assert_eq!(v.redacted(10).encode(), b"ld1:ai0e1:bi0eei1ee");
+        assert_eq!(v.clone().into_owned(), v);
+        assert_eq!(v.clone().try_into_owned().unwrap().fingerprint64(), v.fingerprint64());
+    }
+
+    #[test]
+    fn test_encode_deep() {
+        let n = 1_000_000;
+        let t = std::thread::Builder::new().stack_size(1 << 16).spawn(move || {
+            let mut v = Value::Int(0);
+            for i in 0..n {
+                v = if i % 2 == 0 {
+                    Value::List(vec![v])
+                } else {
+                    Value::Dict([(Cow::Borrowed(b"k".as_ref()), v)].into())
+                };
+            }
+            let buf = v.encode();
+            assert_eq!(buf.len(), v.encoded_len());
+            assert_eq!(buf, [b"d1:kl".repeat(n / 2), b"i0e".to_vec(), b"e".repeat(n)].concat());
+            let mut h = Xxh64::new(0);
+            h.update(&buf);
+            assert_eq!(v.fingerprint64(), h.finish());
+            drop(v);
+        });
+        t.unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn test_redacted() {
+        let v = Value::List(vec![Value::Str(Cow::Borrowed(b"abc")), Value::Str(Cow::Borrowed(b"abcd"))]);
+        let Value::List(r) = &v.redacted(3) else { panic!() };
+        assert_eq!(r[0], Value::Str(Cow::Borrowed(b"abc")));
+        assert_eq!(format!("{:?}", r[1]), "\"<4 bytes, sha1=81fe8bfe87576c3ecb22426f8e57847382917acf>\"");
+        assert_eq!(v.redacted(4), v);
+    }
+
+    #[test]
+    fn test_with_encoded() {
+        let v = Value::List(vec![Value::Int(1)]);
+        let w = Value::Str(Cow::Borrowed(b"abc"));
+        let r = v.with_encoded(|a| w.with_encoded(|b| [a, b].concat()));
+        assert_eq!(r, b"li1ee3:abc");
+        assert_eq!(w.with_encoded(<[u8]>::to_vec), b"3:abc");
+        let big = Value::Str(Cow::Owned(vec![0; Value::MAX_RETAINED_CAPACITY + 1]));
+        assert_eq!(big.with_encoded(|b| b.len()), Value::MAX_RETAINED_CAPACITY + 7);
+    }
+
+    #[test]
+    fn test_decode_dict_order() {
+        let v = Value::decode(b"d1:ai1e1:ci3e1:bi2e1:di4ee", usize::MAX).unwrap();
+        assert_eq!(v.encode(), b"d1:ai1e1:bi2e1:ci3e1:di4ee");
+        for buf in [b"d1:ai1e1:ai1ee".as_ref(), b"d1:ai1e1:bi1e1:ai1ee", b"d1:bi1e1:ai1e1:bi1ee"] {
+            assert_eq!(Value::decode_with(buf, &DecodeOptions::default()), Err(DecodeError::Invalid));
+        }
+        let buf = b"d1:bi1e1:ai1e1:ai1e";
+        assert_eq!(Value::decode_with(buf, &DecodeOptions::default()), Err(DecodeError::Invalid));
+    }
+
+    #[test]
+    fn test_encoded_cmp() {
+        let mut values = vec![Value::Int(i64::MIN), Value::Int(-1), Value::Int(0), Value::Int(i64::MAX)];
+        for seed in 0..300 {
+            let buf = crate::fuzz::mutate(b"d1:ad1:bli-10ei9ei1ee2:cc3:xyzee1:bl0:10:aaaaaaaaaaee", seed);
+            values.extend(Value::decode(&buf, 100).map(Value::into_owned));
+        }
+        assert!(values.len() > 50);
+        for a in &values {
+            for b in &values {
+                assert_eq!(a.encoded_cmp(b), a.encode().cmp(&b.encode()), "{:?} {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_list_of() {
+        let v = Value::decode(b"d8:announceld1:ai1eel3:fooee1:bi0e5:peersl6:aaaaaa6:bbbbbbee", 10).unwrap();
+        assert_eq!(v.get_list_of::<[u8; 6]>("peers"), Some(vec![*b"aaaaaa", *b"bbbbbb"]));
+        assert_eq!(v.get_list_of::<&Value>("announce").map(|l| l.len()), Some(2));
+        assert_eq!(v.get_list_of::<&List>("announce"), None);
+        assert_eq!(v.get_list_of::<i64>("b"), None);
+        assert_eq!(v.get_list_of::<i64>("c"), None);
+        assert_eq!(Value::Int(1).get_list_of::<i64>("peers"), None);
+    }
+
+    #[test]
+    fn test_get_dict_of() {
+        let v = Value::decode(b"d5:filesd1:ad1:ci1ee1:bd1:ci2eee1:xi0ee", 10).unwrap();
+        let files = v.get_dict_of::<&[u8], &Value>("files").unwrap();
+        assert_eq!(files.keys().collect::<Vec<_>>(), [b"a", b"b"]);
+        assert_eq!(files[b"b".as_ref()].get::<i64>("c"), Some(2));
+        assert_eq!(v.get_dict_of::<Vec<u8>, i64>("files"), None);
+        assert_eq!(v.get_dict_of::<Vec<u8>, i64>("x"), None);
+        assert_eq!(v.get_dict_of::<Vec<u8>, i64>("y"), None);
+    }
+
+    #[test]
+    fn test_keys_and_values_as() {
+        assert_eq!(Value::Int(1).keys().count(), 0);
+        assert_eq!(Value::List(vec![Value::Int(1)]).values_as::<i64>().count(), 0);
+        let v = Value::decode(b"d1:a3:foo1:b3:\xff\xfe\xfde", 10).unwrap();
+        let x: Vec<_> = v.values_as::<&str>().collect();
+        assert_eq!(x, [(b"a".as_ref(), Some("foo")), (b"b".as_ref(), None)]);
+    }
+
+    #[test]
+    fn test_insert_and_push() {
+        let mut v = Value::Dict(Dict::new());
+        assert_eq!(v.insert("a", Value::Int(1)), None);
+        assert_eq!(v.insert(b"a".to_vec(), Value::Int(2)), Some(Value::Int(1)));
+        v.insert_str("b", String::from("x")).insert_int("a", 3);
+        assert_eq!(v.encode(), b"d1:ai3e1:b1:xe");
+        let mut l = Value::List(List::new());
+        l.push_int(-1).push_str(b"y");
+        assert_eq!(l.encode(), b"li-1e1:ye");
+    }
+
+    #[test]
+    #[should_panic(expected = "not a list")]
+    fn test_push_wrong_kind() {
+        Value::Int(1).push_int(1);
+    }
+
+    #[test]
+    fn test_retain_and_prune() {
+        let mut v = Value::decode(b"d1:ali1ei2ei3ee1:bd1:cldeee1:ci0ee", 10).unwrap();
+        let mut seen = Vec::new();
+        v.retain(|p, x| {
+            seen.push(p.to_string());
+            *x != Value::Int(2) && p.to_string() != "c"
+        });
+        assert_eq!(seen, ["a", "a[0]", "a[1]", "a[2]", "b", "b.c", "b.c[0]", "c"]);
+        assert_eq!(v.encode(), b"d1:ali1ei3ee1:bd1:cldeeee");
+        v.prune_empty();
+        assert_eq!(v.encode(), b"d1:ali1ei3eee");
+        v.retain(|_, _| false);
+        assert_eq!(v, Value::empty_dict());
+    }
+
+    #[test]
+    fn test_debug_pretty() {
+        let v = Value::decode(b"l1:\xffi-1ed1:\xfeleee", 10).unwrap();
+        assert_eq!(format!("{:#?}", v), "[\n│ ff,\n│ -1,\n│ {\n│ │ fe: [],\n│ },\n]");
+        assert_eq!(format!("{:#?}", Value::Int(1)), "1");
+        assert_eq!(format!("{:?}", v), r#"[ff, -1, {fe: []}]"#);
+    }
+
+    #[test]
+    fn test_decode_into_reuses_lists() {
+        let opts = DecodeOptions::default();
+        let buf = b"d1:ali1ei2ei3ee1:bli0eeed1:alee1:bi0e1:cl1:xee";
+        let (v0, n) = Value::decode_at(buf, 0, &opts).unwrap();
+        let mut v = v0.clone();
+        let ptr = |v: &Value| v.get::<&List>("a").unwrap().as_ptr() as usize;
+        let p = ptr(&v);
+        v.decode_into(&buf[n..], &opts).unwrap();
+        assert_eq!(v, Value::decode_with(&buf[n..], &opts).unwrap());
+        assert_eq!(ptr(&v), p);
+        v.decode_into(&buf[..n], &opts).unwrap();
+        assert_eq!(v, v0);
+        assert_eq!(v.decode_into(b"li1ex", &opts), Err(DecodeError::Invalid));
+        assert_eq!(v.decode_into(b"", &opts), Err(DecodeError::Incomplete { needed: 1 }));
+    }
+
+    #[test]
+    fn test_length_too_large() {
+        let opts = DecodeOptions::default();
+        assert_eq!(Value::decode_with(b"100:abc", &opts), Err(DecodeError::Incomplete { needed: 97 }));
+        let max = format!("{}:", isize::MAX);
+        assert_eq!(
+            Value::decode_with(max.as_bytes(), &opts),
+            Err(DecodeError::Incomplete { needed: isize::MAX as usize })
+        );
+        for len in [(isize::MAX as u64 + 1).to_string(), u64::MAX.to_string(), "99999999999999999999".to_string()] {
+            let buf = format!("l{}:e", len);
+            assert_eq!(Value::decode_with(buf.as_bytes(), &opts), Err(DecodeError::LengthTooLarge));
+            assert_eq!(crate::peek_len(&buf.as_bytes()[1..]), Err(DecodeError::LengthTooLarge));
+        }
     }
 }
@@ -0,0 +1,181 @@
+use super::encoder::Encoder;
+use super::{Encodable, Kind, Value};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// A modifiable view of a shared [Value] that copies only what is modified
+///
+/// Editing a single field of a large decoded value normally requires cloning it (or calling
+/// [Value::into_owned]) first. [ValueMut] instead references the original and only copies the
+/// containers on the way to a modification: descending into a dictionary or list copies that one
+/// level, with its children still referencing the original. Unmodified parts are encoded directly
+/// from the original.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let buf = b"d8:announce3:foo4:infod6:lengthi42e6:pieces4:xxxxee";
+/// let v = Value::decode(buf, 10).unwrap();
+///
+/// let mut m = ValueMut::new(&v);
+/// m.insert(b"announce", str!("bar"));
+/// m.get_mut(b"info").unwrap().insert(b"private", int!(1));
+/// assert_eq!(m.encode(), b"d8:announce3:bar4:infod6:lengthi42e6:pieces4:xxxx7:privatei1eee");
+/// assert_eq!(v.encode(), buf);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValueMut<'v, 'a>(Node<'v, 'a>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node<'v, 'a> {
+    /// Unmodified part of the original value
+    Shared(&'v Value<'a>),
+    /// A value that has been set as a whole
+    Owned(Value<'a>),
+    /// A container with some modified children
+    List(Vec<ValueMut<'v, 'a>>),
+    Dict(BTreeMap<Cow<'a, [u8]>, ValueMut<'v, 'a>>),
+}
+
+impl<'v, 'a> ValueMut<'v, 'a> {
+    pub fn new(value: &'v Value<'a>) -> Self {
+        Self(Node::Shared(value))
+    }
+
+    pub fn kind(&self) -> Kind {
+        match &self.0 {
+            Node::Shared(v) => v.kind(),
+            Node::Owned(v) => v.kind(),
+            Node::List(_) => Kind::List,
+            Node::Dict(_) => Kind::Dict,
+        }
+    }
+
+    /// Whether this is still the unmodified original
+    pub fn is_shared(&self) -> bool {
+        matches!(self.0, Node::Shared(_))
+    }
+
+    /// Replace the value
+    pub fn set(&mut self, value: Value<'a>) {
+        self.0 = Node::Owned(value);
+    }
+
+    /// Get the value for `key` for modification if this is a dictionary
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut Self> {
+        self.dict()?.get_mut(key)
+    }
+
+    /// Get the item at `index` for modification if this is a list
+    pub fn get_index_mut(&mut self, index: usize) -> Option<&mut Self> {
+        self.list()?.get_mut(index)
+    }
+
+    /// Insert an entry into a dictionary and return whether the key already existed
+    ///
+    /// Panics if this is not a dictionary.
+    pub fn insert(&mut self, key: &[u8], value: Value<'a>) -> bool {
+        let d = self.dict().expect("not a dictionary");
+        d.insert(Cow::Owned(key.to_vec()), Self(Node::Owned(value))).is_some()
+    }
+
+    /// Remove an entry from a dictionary and return whether it existed
+    pub fn remove(&mut self, key: &[u8]) -> bool {
+        self.dict().and_then(|d| d.remove(key)).is_some()
+    }
+
+    /// Append an item to a list
+    ///
+    /// Panics if this is not a list.
+    pub fn push(&mut self, value: Value<'a>) {
+        self.list().expect("not a list").push(Self(Node::Owned(value)))
+    }
+
+    /// Build the modified value (cloning the unmodified parts)
+    pub fn to_value(&self) -> Value<'a> {
+        match &self.0 {
+            Node::Shared(v) => (*v).clone(),
+            Node::Owned(v) => v.clone(),
+            Node::List(l) => Value::List(l.iter().map(Self::to_value).collect()),
+            Node::Dict(d) => Value::Dict(d.iter().map(|(k, v)| (k.clone(), v.to_value())).collect()),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        Encoder::new(&mut buf).encode(self);
+        buf
+    }
+
+    fn dict(&mut self) -> Option<&mut BTreeMap<Cow<'a, [u8]>, Self>> {
+        match &mut self.0 {
+            Node::Shared(Value::Dict(d)) => {
+                self.0 = Node::Dict(d.iter().map(|(k, v)| (k.clone(), Self::new(v))).collect());
+            }
+            Node::Owned(Value::Dict(d)) => {
+                self.0 = Node::Dict(std::mem::take(d).into_iter().map(|(k, v)| (k, Self(Node::Owned(v)))).collect());
+            }
+            _ => (),
+        }
+        match &mut self.0 {
+            Node::Dict(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    fn list(&mut self) -> Option<&mut Vec<Self>> {
+        match &mut self.0 {
+            Node::Shared(Value::List(l)) => self.0 = Node::List(l.iter().map(Self::new).collect()),
+            Node::Owned(Value::List(l)) => {
+                self.0 = Node::List(std::mem::take(l).into_iter().map(|v| Self(Node::Owned(v))).collect())
+            }
+            _ => (),
+        }
+        match &mut self.0 {
+            Node::List(l) => Some(l),
+            _ => None,
+        }
+    }
+}
+
+impl Encodable for ValueMut<'_, '_> {
+    fn encode(&self, e: &mut Encoder) {
+        match &self.0 {
+            Node::Shared(v) => e.value(v),
+            Node::Owned(v) => e.value(v),
+            Node::List(l) => e.encode(l),
+            Node::Dict(d) => e.encode(d),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shallow_copies() {
+        let v = Value::decode(b"d1:ali1ei2ee1:bd1:ci3eee", 10).unwrap();
+        let mut m = ValueMut::new(&v);
+        m.get_mut(b"a").unwrap().get_index_mut(1).unwrap().set(Value::Int(5));
+        m.get_mut(b"a").unwrap().push(Value::Int(6));
+        assert!(m.get_mut(b"b").unwrap().is_shared());
+        assert!(!m.get_mut(b"a").unwrap().is_shared());
+        assert!(m.remove(b"b"));
+        assert!(!m.remove(b"b"));
+        assert_eq!(m.encode(), b"d1:ali1ei5ei6eee");
+        assert_eq!(m.to_value().encode(), m.encode());
+    }
+
+    #[test]
+    fn test_wrong_kind() {
+        let v = Value::Int(1);
+        let mut m = ValueMut::new(&v);
+        assert_eq!(m.get_mut(b"a"), None);
+        assert_eq!(m.get_index_mut(0), None);
+        assert_eq!(m.kind(), Kind::Int);
+        m.set(Value::List(vec![Value::Int(1)]));
+        m.get_index_mut(0).unwrap().set(Value::Int(2));
+        assert_eq!(m.to_value(), Value::List(vec![Value::Int(2)]));
+    }
+}
@@ -0,0 +1,273 @@
+use super::decoder::Decoder;
+use super::encoder::Encoder;
+use super::value::Unpacked;
+use super::{DecodeError, DecodeOptions, Encodable, Kind, Value};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// A decoded value that re-encodes byte for byte as long as it is not modified
+///
+/// [Value] normalizes its input: integers like `i007e` lose their leading zeros and dictionary keys
+/// are sorted. That is fine for most uses, but an editor for `.torrent` files must not change the
+/// encoding of the `info` dictionary or its hash would change. [Verbatim] remembers the input span of
+/// every sub-value and copies it verbatim on encoding unless the sub-value (or one of its children) was
+/// obtained mutably. Only modified values and their ancestors are re-encoded canonically.
+///
+/// ```rust
+/// use bencode_minimal::*;
+///
+/// let buf = b"d4:infod6:lengthi0042ee8:announce3:fooe";
+/// let mut v = Verbatim::decode(buf, &DecodeOptions::default()).unwrap();
+/// assert_eq!(v.encode(), buf);
+///
+/// v.insert(b"announce", str!("bar"));
+/// assert_eq!(v.encode(), b"d8:announce3:bar4:infod6:lengthi0042eee");
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Verbatim<'a> {
+    raw: Option<&'a [u8]>,
+    node: Node<'a>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Node<'a> {
+    Int(i64),
+    Str(Cow<'a, [u8]>),
+    List(Vec<Verbatim<'a>>),
+    Dict(BTreeMap<Cow<'a, [u8]>, Verbatim<'a>>),
+}
+
+impl<'a> Verbatim<'a> {
+    /// Decode a value remembering the input span of each sub-value
+    ///
+    /// Limits and errors are the same as with [Value::decode_with].
+    pub fn decode(buf: &'a [u8], opts: &DecodeOptions) -> Result<Self, DecodeError> {
+        let mut d = Decoder::with_options(buf, opts);
+        let r = Self::take(&mut d, buf);
+        d.finish(r.as_ref().map(Self::kind).map_err(|e| *e));
+        r
+    }
+
+    fn take(d: &mut Decoder<'a>, buf: &'a [u8]) -> Result<Self, DecodeError> {
+        const ITEM_SIZE: usize = std::mem::size_of::<Verbatim>();
+        let start = buf.len() - d.remaining();
+        let node = match d.peek_u8()? {
+            b'l' => {
+                d.begin_list()?;
+                let mut list = Vec::new();
+                while d.next_item()? {
+                    d.alloc(ITEM_SIZE)?;
+                    list.push(Self::take(d, buf)?);
+                }
+                Node::List(list)
+            }
+            b'd' => {
+                d.begin_dict()?;
+                let mut dict = BTreeMap::new();
+                while let Some(k) = d.next_key()? {
+                    d.alloc(ITEM_SIZE + std::mem::size_of::<Cow<[u8]>>())?;
+                    let v = Self::take(d, buf)?;
+                    d.insert_entry(&mut dict, k, v)?;
+                }
+                Node::Dict(dict)
+            }
+            _ => match d.take_any()?.unpack() {
+                Unpacked::Int(i) => Node::Int(i),
+                Unpacked::Str(s) => Node::Str(s),
+                _ => unreachable!("containers are handled above"),
+            },
+        };
+        let end = buf.len() - d.remaining();
+        Ok(Self { raw: Some(&buf[start..end]), node })
+    }
+
+    pub fn kind(&self) -> Kind {
+        match self.node {
+            Node::Int(_) => Kind::Int,
+            Node::Str(_) => Kind::Str,
+            Node::List(_) => Kind::List,
+            Node::Dict(_) => Kind::Dict,
+        }
+    }
+
+    /// The original encoding if neither this value nor any of its children was modified
+    pub fn raw(&self) -> Option<&'a [u8]> {
+        self.raw
+    }
+
+    /// Whether the value will be encoded exactly like the input
+    pub fn is_verbatim(&self) -> bool {
+        self.raw.is_some()
+    }
+
+    /// Convert into a plain [Value] (losing the original encoding)
+    pub fn to_value(&self) -> Value<'a> {
+        match &self.node {
+            Node::Int(i) => Value::Int(*i),
+            Node::Str(s) => Value::Str(s.clone()),
+            Node::List(l) => Value::List(l.iter().map(Self::to_value).collect()),
+            Node::Dict(d) => Value::Dict(d.iter().map(|(k, v)| (k.clone(), v.to_value())).collect()),
+        }
+    }
+
+    /// Replace the value
+    pub fn set(&mut self, value: Value<'a>) {
+        *self = value.into();
+    }
+
+    /// Get the value for `key` if this is a dictionary
+    pub fn get(&self, key: &[u8]) -> Option<&Self> {
+        match &self.node {
+            Node::Dict(d) => d.get(key),
+            _ => None,
+        }
+    }
+
+    /// Get the value for `key` for modification
+    ///
+    /// This value will be re-encoded while the returned child keeps its original encoding until it
+    /// is modified itself.
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut Self> {
+        match &mut self.node {
+            Node::Dict(d) => {
+                let v = d.get_mut(key)?;
+                self.raw = None;
+                Some(v)
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the item at `index` if this is a list
+    pub fn get_index(&self, index: usize) -> Option<&Self> {
+        match &self.node {
+            Node::List(l) => l.get(index),
+            _ => None,
+        }
+    }
+
+    /// Get the item at `index` for modification (see [Self::get_mut])
+    pub fn get_index_mut(&mut self, index: usize) -> Option<&mut Self> {
+        match &mut self.node {
+            Node::List(l) => {
+                let v = l.get_mut(index)?;
+                self.raw = None;
+                Some(v)
+            }
+            _ => None,
+        }
+    }
+
+    /// Insert an entry into a dictionary and return the previous value
+    ///
+    /// Panics if this is not a dictionary.
+    pub fn insert(&mut self, key: &[u8], value: Value<'a>) -> Option<Self> {
+        let Node::Dict(d) = &mut self.node else {
+            panic!("not a dictionary");
+        };
+        self.raw = None;
+        d.insert(Cow::Owned(key.to_vec()), value.into())
+    }
+
+    /// Remove an entry from a dictionary
+    pub fn remove(&mut self, key: &[u8]) -> Option<Self> {
+        let Node::Dict(d) = &mut self.node else {
+            return None;
+        };
+        let v = d.remove(key)?;
+        self.raw = None;
+        Some(v)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode_into(&mut buf);
+        buf
+    }
+
+    pub fn encode_into(&self, buf: &mut Vec<u8>) {
+        Encoder::new(buf).encode(self);
+    }
+}
+
+impl<'a> From<Value<'a>> for Verbatim<'a> {
+    fn from(value: Value<'a>) -> Self {
+        let node = match value.unpack() {
+            Unpacked::Int(i) => Node::Int(i),
+            Unpacked::Str(s) => Node::Str(s),
+            Unpacked::List(l) => Node::List(l.into_iter().map(Self::from).collect()),
+            Unpacked::Dict(d) => Node::Dict(d.into_iter().map(|(k, v)| (k, v.into())).collect()),
+            Unpacked::Raw(Cow::Borrowed(s)) => match Verbatim::decode(s, &DecodeOptions::default()) {
+                Ok(v) => return v,
+                Err(_) => Node::Str(Cow::Borrowed(s)),
+            },
+            Unpacked::Raw(s) => return Value::unraw_lossy(&s).into(),
+        };
+        Self { raw: None, node }
+    }
+}
+
+impl Encodable for Verbatim<'_> {
+    fn encode(&self, e: &mut Encoder) {
+        if let Some(raw) = self.raw {
+            e.raw_slice(raw);
+            return;
+        }
+        match &self.node {
+            Node::Int(i) => e.int(*i),
+            Node::Str(s) => e.str(s),
+            Node::List(l) => e.encode(l),
+            Node::Dict(d) => e.encode(d),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DuplicateKeys;
+
+    fn decode(buf: &[u8]) -> Verbatim<'_> {
+        Verbatim::decode(buf, &DecodeOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_quirks() {
+        for buf in [b"i-0e".as_ref(), b"i007e", b"03:abc", b"d1:bi1e1:ai2ee", b"l00:d1:b0:1:a0:ee"] {
+            let v = decode(buf);
+            assert_eq!(v.encode(), buf);
+            assert_ne!(v.to_value().encode(), buf);
+        }
+    }
+
+    #[test]
+    fn test_modify_nested() {
+        let buf = b"d1:bl1:xi01ee1:ad1:ci02eee";
+        let mut v = decode(buf);
+        let a = v.get(b"a").unwrap().raw();
+        assert_eq!(a, Some(b"d1:ci02ee".as_ref()));
+        v.get_mut(b"b").unwrap().get_index_mut(1).unwrap().set(Value::Int(3));
+        assert!(!v.is_verbatim());
+        assert_eq!(v.get(b"a").unwrap().raw(), a);
+        assert_eq!(v.encode(), b"d1:ad1:ci02ee1:bl1:xi3eee");
+        assert_eq!(v.remove(b"b").map(|x| x.kind()), Some(Kind::List));
+        assert_eq!(v.encode(), b"d1:ad1:ci02eee");
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let opts = DecodeOptions::default();
+        assert_eq!(Verbatim::decode(b"d1:ai1e1:ai2ee", &opts), Err(DecodeError::Invalid));
+        let v =
+            Verbatim::decode(b"d1:ai1e1:ai2ee", &opts.clone().with_duplicate_keys(DuplicateKeys::LastWins)).unwrap();
+        assert_eq!(v.get(b"a").unwrap().raw(), Some(b"i2e".as_ref()));
+        assert_eq!(Verbatim::decode(b"li1e", &opts), Err(DecodeError::Incomplete { needed: 1 }));
+        let opts = DecodeOptions { max_allocs: 1, ..opts };
+        assert_eq!(Verbatim::decode(b"li1ei2ee", &opts), Err(DecodeError::LimitExceeded));
+
+        let opts = DecodeOptions::default().with_strict(true);
+        assert_eq!(Verbatim::decode(b"d1:bi1e1:ai1ee", &opts), Err(DecodeError::Invalid));
+        assert_eq!(Verbatim::decode(b"ld1:bi1e1:ai1eee", &opts), Err(DecodeError::Invalid));
+        assert!(Verbatim::decode(b"d1:ad1:bi1ee1:bi1ee", &opts).is_ok());
+    }
+}
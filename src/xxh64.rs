@@ -0,0 +1,134 @@
+//! A streaming implementation of the XXH64 hash function
+//!
+//! See <https://github.com/Cyan4973/xxHash/blob/dev/doc/xxhash_spec.md>.
+
+const P1: u64 = 0x9E3779B185EBCA87;
+const P2: u64 = 0xC2B2AE3D27D4EB4F;
+const P3: u64 = 0x165667B19E3779F9;
+const P4: u64 = 0x85EBCA77C2B2AE63;
+const P5: u64 = 0x27D4EB2F165667C5;
+
+pub(crate) struct Xxh64 {
+    seed: u64,
+    acc: [u64; 4],
+    buf: [u8; 32],
+    buf_len: usize,
+    total_len: u64,
+}
+
+impl Xxh64 {
+    pub fn new(seed: u64) -> Self {
+        let acc = [seed.wrapping_add(P1).wrapping_add(P2), seed.wrapping_add(P2), seed, seed.wrapping_sub(P1)];
+        Self { seed, acc, buf: [0; 32], buf_len: 0, total_len: 0 }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+        if self.buf_len > 0 {
+            let n = data.len().min(32 - self.buf_len);
+            self.buf[self.buf_len..self.buf_len + n].copy_from_slice(&data[..n]);
+            self.buf_len += n;
+            data = &data[n..];
+            if self.buf_len < 32 {
+                return;
+            }
+            let buf = self.buf;
+            self.stripe(&buf);
+            self.buf_len = 0;
+        }
+        let mut chunks = data.chunks_exact(32);
+        for c in &mut chunks {
+            self.stripe(c);
+        }
+        let rest = chunks.remainder();
+        self.buf[..rest.len()].copy_from_slice(rest);
+        self.buf_len = rest.len();
+    }
+
+    pub fn finish(&self) -> u64 {
+        let mut h = if self.total_len >= 32 {
+            let [a, b, c, d] = self.acc;
+            let mut h = a.rotate_left(1).wrapping_add(b.rotate_left(7)).wrapping_add(c.rotate_left(12));
+            h = h.wrapping_add(d.rotate_left(18));
+            for x in self.acc {
+                h = (h ^ round(0, x)).wrapping_mul(P1).wrapping_add(P4);
+            }
+            h
+        } else {
+            self.seed.wrapping_add(P5)
+        };
+        h = h.wrapping_add(self.total_len);
+        let mut rest = &self.buf[..self.buf_len];
+        while let Some((x, r)) = rest.split_first_chunk::<8>() {
+            h ^= round(0, u64::from_le_bytes(*x));
+            h = h.rotate_left(27).wrapping_mul(P1).wrapping_add(P4);
+            rest = r;
+        }
+        if let Some((x, r)) = rest.split_first_chunk::<4>() {
+            h ^= u64::from(u32::from_le_bytes(*x)).wrapping_mul(P1);
+            h = h.rotate_left(23).wrapping_mul(P2).wrapping_add(P3);
+            rest = r;
+        }
+        for x in rest {
+            h ^= u64::from(*x).wrapping_mul(P5);
+            h = h.rotate_left(11).wrapping_mul(P1);
+        }
+        h ^= h >> 33;
+        h = h.wrapping_mul(P2);
+        h ^= h >> 29;
+        h = h.wrapping_mul(P3);
+        h ^ (h >> 32)
+    }
+
+    fn stripe(&mut self, data: &[u8]) {
+        for (acc, x) in self.acc.iter_mut().zip(data.chunks_exact(8)) {
+            *acc = round(*acc, u64::from_le_bytes(x.try_into().expect("8 bytes")));
+        }
+    }
+}
+
+fn round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(P2)).rotate_left(31).wrapping_mul(P1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xxh64(data: &[u8], seed: u64) -> u64 {
+        let mut h = Xxh64::new(seed);
+        h.update(data);
+        h.finish()
+    }
+
+    #[test]
+    fn test_vectors() {
+        assert_eq!(xxh64(b"", 0), 0xEF46DB3751D8E999);
+        assert_eq!(xxh64(b"a", 0), 0xD24EC4F1A98C6E5B);
+        assert_eq!(xxh64(b"abc", 0), 0x44BC2CF5AD770999);
+        let data: Vec<u8> = (0..200u8).collect();
+        let expected = [
+            (0, 0x95F0626F6F0A4409),
+            (5, 0xA7272E99914ED24B),
+            (12, 0x17D8629FD1DF6544),
+            (32, 0xA5972D57C4AEA230),
+            (40, 0xFC9F7A1B24012CB5),
+            (63, 0x379EEAB4056E3988),
+            (200, 0x38095D110DB174CC),
+        ];
+        for (n, h) in expected {
+            assert_eq!(xxh64(&data[..n], 7), h);
+        }
+    }
+
+    #[test]
+    fn test_streaming() {
+        let data: Vec<u8> = (0..200u8).collect();
+        let h = xxh64(&data, 7);
+        for n in [1, 3, 31, 32, 33, 100] {
+            let mut s = Xxh64::new(7);
+            data.chunks(n).for_each(|c| s.update(c));
+            assert_eq!(s.finish(), h);
+        }
+    }
+}